@@ -0,0 +1,74 @@
+//! time of board operations on random stacks, the ones bots and solvers do most
+use bevy_tetris::bot::{placements, Evaluator, WEIGHTS};
+use bevy_tetris::bricks::{Board, Brick, Dot};
+use bevy_tetris::consts::{BOARD_X, BRICK_VIEWS};
+use bevy_tetris::modes::GameMode;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::prelude::*;
+
+/// stacks up to 8 lines high, most dots occupied
+fn boards() -> Vec<Board> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..100)
+        .map(|_| {
+            let mut board = Board::default();
+            for y in 0..rng.gen_range(0..8) {
+                for x in 0..BOARD_X {
+                    if rng.gen_bool(0.7) {
+                        board.occupy_dot(&Dot(x, y));
+                    }
+                }
+            }
+            board
+        })
+        .collect()
+}
+
+fn board(c: &mut Criterion) {
+    let boards = boards();
+    c.bench_function("valid_brick", |b| {
+        b.iter(|| {
+            let mut valid = 0;
+            for board in &boards {
+                for (ty, views) in BRICK_VIEWS.iter().enumerate() {
+                    for rotation in 0..views.len() {
+                        let brick = Brick { ty, rotation };
+                        for x in -3..BOARD_X {
+                            for y in -3..20 {
+                                valid += board.valid_brick(&brick, &Dot(x, y)) as u32;
+                            }
+                        }
+                    }
+                }
+            }
+            black_box(valid)
+        })
+    });
+    c.bench_function("clean_lines, holes and height", |b| {
+        b.iter(|| {
+            let mut sum = 0;
+            for board in &boards {
+                let mut board = board.clone();
+                sum += board.clean_lines() + board.holes() + board.height() as u32;
+            }
+            black_box(sum)
+        })
+    });
+    c.bench_function("placements evaluated", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for board in &boards {
+                for ty in 0..BRICK_VIEWS.len() {
+                    let brick = Brick { ty, rotation: 0 };
+                    for (_, after, lines) in placements(board, brick, GameMode::Classic, 0) {
+                        sum += WEIGHTS.evaluate(&after, lines);
+                    }
+                }
+            }
+            black_box(sum)
+        })
+    });
+}
+
+criterion_group!(benches, board);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+#!/usr/bin/env python3
+"""Dummy bot of the Tetris Bot Protocol, for trying `--bot "python3 bots/dummy_bot.py"`.
+
+Suggests hard drops of the current piece and of the piece out of hold,
+fewest holes and lowest first, and keeps its own board from the moves played.
+"""
+import json
+import sys
+
+WIDTH = 10
+ROWS = 40
+# dots of the SRS pieces pointing north, around their center
+PIECES = {
+    "I": [(-1, 0), (0, 0), (1, 0), (2, 0)],
+    "O": [(0, 0), (1, 0), (0, 1), (1, 1)],
+    "T": [(-1, 0), (0, 0), (1, 0), (0, 1)],
+    "L": [(-1, 0), (0, 0), (1, 0), (1, 1)],
+    "J": [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+    "S": [(-1, 0), (0, 0), (0, 1), (1, 1)],
+    "Z": [(-1, 1), (0, 1), (0, 0), (1, 0)],
+}
+TURNS = {
+    "north": lambda x, y: (x, y),
+    "east": lambda x, y: (y, -x),
+    "south": lambda x, y: (-x, -y),
+    "west": lambda x, y: (-y, x),
+}
+
+
+def send(message):
+    print(json.dumps(message), flush=True)
+
+
+def dots(location):
+    turn = TURNS[location["orientation"]]
+    result = []
+    for x, y in PIECES[location["type"]]:
+        x, y = turn(x, y)
+        result.append((location["x"] + x, location["y"] + y))
+    return result
+
+
+def fits(board, cells):
+    return all(0 <= x < WIDTH and 0 <= y < ROWS and board[y][x] is None for x, y in cells)
+
+
+def place(board, cells, piece):
+    for x, y in cells:
+        board[y][x] = piece
+    board[:] = [row for row in board if None in row]
+    while len(board) < ROWS:
+        board.append([None] * WIDTH)
+
+
+def holes(board):
+    count = 0
+    for x in range(WIDTH):
+        covered = False
+        for y in reversed(range(ROWS)):
+            if board[y][x] is not None:
+                covered = True
+            elif covered:
+                count += 1
+    return count
+
+
+def drops(board, piece):
+    scored = []
+    for orientation in TURNS:
+        for x in range(-2, WIDTH + 2):
+            location = {"type": piece, "orientation": orientation, "x": x, "y": 20}
+            if not fits(board, dots(location)):
+                continue
+            while fits(board, dots(dict(location, y=location["y"] - 1))):
+                location["y"] -= 1
+            after = [row[:] for row in board]
+            place(after, dots(location), piece)
+            top = max(y for _, y in dots(location))
+            scored.append((holes(after), top, {"location": location, "spin": "none"}))
+    return scored
+
+
+def suggest(board, queue, hold):
+    if not queue:
+        return []
+    scored = drops(board, queue[0])
+    held = hold or (queue[1] if len(queue) > 1 else None)
+    if held is not None and held != queue[0]:
+        scored += drops(board, held)
+    scored.sort(key=lambda s: (s[0], s[1]))
+    return [move for _, _, move in scored]
+
+
+def main():
+    board = [[None] * WIDTH for _ in range(ROWS)]
+    queue = []
+    hold = None
+    send({"type": "info", "name": "dummy", "version": "1", "author": "bevy-tetris", "features": []})
+    for line in sys.stdin:
+        message = json.loads(line)
+        kind = message["type"]
+        if kind == "rules":
+            send({"type": "ready"})
+        elif kind == "start":
+            board = [[cell for cell in row] for row in message["board"]]
+            queue = list(message["queue"])
+            hold = message["hold"]
+        elif kind == "new_piece":
+            queue.append(message["piece"])
+        elif kind == "suggest":
+            send({"type": "suggestion", "moves": suggest(board, queue, hold)})
+        elif kind == "play":
+            location = message["move"]["location"]
+            place(board, dots(location), location["type"])
+            if location["type"] != queue[0]:
+                # held first, out came the held piece or the next one
+                held = queue.pop(0)
+                if hold is None:
+                    queue.pop(0)
+                hold = held
+            else:
+                queue.pop(0)
+        elif kind == "quit":
+            break
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,430 @@
+//! python extension module `bevy_tetris`, built with `maturin develop --release`
+//! in the folder of the game (see pyproject.toml there), tested with
+//! `cargo test --no-default-features` here.
+//!
+//! Bricks are `(type, rotation)` with type an index into `BRICK_NAMES`, dots are `(x, y)`
+//! from the bottom left. Boards are numpy arrays of `uint8`, one row by line from the bottom.
+use bevy_tetris::bricks::{Board, Brick, Dot};
+use bevy_tetris::consts::{BOARD_Y, BRICKS_TYPES, BRICK_NAMES, BRICK_VIEWS};
+use bevy_tetris::engine::{Engine, Event, Input, Phase};
+use bevy_tetris::env::{Action, Env, Info, Observation, Step};
+use bevy_tetris::replay::parse_mode;
+use numpy::ndarray::Array2;
+use numpy::{AllowTypeChange, IntoPyArray, PyArray2, PyArrayLike2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// brick by type and rotation, checked against the rotation tables
+fn brick(ty: usize, rotation: usize) -> PyResult<Brick> {
+    if ty >= BRICKS_TYPES || rotation >= BRICK_VIEWS[ty].len() {
+        return Err(PyValueError::new_err(format!(
+            "no brick of type {} and rotation {}",
+            ty, rotation
+        )));
+    }
+    Ok(Brick { ty, rotation })
+}
+
+/// width of a board, a line is bits of a `u32`
+fn board_width(width: usize) -> PyResult<i8> {
+    if width == 0 || width > u32::BITS as usize {
+        return Err(PyValueError::new_err(format!(
+            "board width is 1 to {}, found {}",
+            u32::BITS,
+            width
+        )));
+    }
+    Ok(width as i8)
+}
+
+fn board_array<'py>(py: Python<'py>, board: &Board) -> Bound<'py, PyArray2<u8>> {
+    Array2::from_shape_fn((BOARD_Y as usize, board.width() as usize), |(y, x)| {
+        board.occupied_dot(&Dot(x as i8, y as i8)) as u8
+    })
+    .into_pyarray(py)
+}
+
+/// dots of a brick around its position
+#[pyfunction]
+fn brick_dots(ty: usize, rotation: usize) -> PyResult<Vec<(i8, i8)>> {
+    let brick = brick(ty, rotation)?;
+    Ok(BRICK_VIEWS[brick.ty][brick.rotation]
+        .dots
+        .iter()
+        .map(|dot| (dot.0, dot.1))
+        .collect())
+}
+
+/// rotations a brick type has
+#[pyfunction]
+fn rotations(ty: usize) -> PyResult<usize> {
+    brick(ty, 0)?;
+    Ok(BRICK_VIEWS[ty].len())
+}
+
+#[pyclass(name = "Board")]
+#[derive(Clone)]
+struct PyBoard(Board);
+
+#[pymethods]
+impl PyBoard {
+    #[new]
+    #[pyo3(signature = (width=10))]
+    fn new(width: usize) -> PyResult<Self> {
+        Ok(Self(Board::new(board_width(width)?)))
+    }
+
+    /// board of a `(lines, width)` array, other than 0 is occupied
+    #[staticmethod]
+    fn from_array(array: PyArrayLike2<'_, u8, AllowTypeChange>) -> PyResult<Self> {
+        let array = array.as_array();
+        let (lines, width) = array.dim();
+        if lines > BOARD_Y as usize {
+            return Err(PyValueError::new_err(format!(
+                "board is at most {} lines, found {}",
+                BOARD_Y, lines
+            )));
+        }
+        let mut board = Board::new(board_width(width)?);
+        for ((y, x), dot) in array.indexed_iter() {
+            if *dot != 0 {
+                board.occupy_dot(&Dot(x as i8, y as i8));
+            }
+        }
+        Ok(Self(board))
+    }
+
+    fn to_array<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        board_array(py, &self.0)
+    }
+
+    #[getter]
+    fn width(&self) -> i8 {
+        self.0.width()
+    }
+
+    fn occupied(&self, x: i8, y: i8) -> bool {
+        self.0.dot_in_board(&Dot(x, y)) && self.0.occupied_dot(&Dot(x, y))
+    }
+
+    /// brick fits at the position
+    fn valid(&self, ty: usize, rotation: usize, x: i8, y: i8) -> PyResult<bool> {
+        Ok(self.0.valid_brick(&brick(ty, rotation)?, &Dot(x, y)))
+    }
+
+    /// brick put at the position, false when it doesn't fit
+    fn place(&mut self, ty: usize, rotation: usize, x: i8, y: i8) -> PyResult<bool> {
+        let brick = brick(ty, rotation)?;
+        let valid = self.0.valid_brick(&brick, &Dot(x, y));
+        if valid {
+            self.0.occupy_brick(&brick, &Dot(x, y));
+        }
+        Ok(valid)
+    }
+
+    /// lines cleared
+    fn clean_lines(&mut self) -> u32 {
+        self.0.clean_lines()
+    }
+
+    fn holes(&self) -> u32 {
+        self.0.holes()
+    }
+
+    fn height(&self) -> i8 {
+        self.0.height()
+    }
+
+    fn column_height(&self, x: i8) -> i8 {
+        self.0.column_height(x)
+    }
+
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        let lines: Vec<String> = (0..self.0.height())
+            .rev()
+            .map(|y| {
+                (0..self.0.width())
+                    .map(|x| {
+                        if self.0.occupied_dot(&Dot(x, y)) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        format!("Board({})", lines.join("/"))
+    }
+}
+
+fn event_name(event: &Event) -> &'static str {
+    match event {
+        Event::Spawned => "SPAWNED",
+        Event::Moved => "MOVED",
+        Event::Held => "HELD",
+        Event::Locked { .. } => "LOCKED",
+        Event::Attack(_) => "ATTACK",
+        Event::BoardChanged => "BOARD_CHANGED",
+        Event::Over => "OVER",
+    }
+}
+
+/// game rules stepped frame by frame by keys as bits of
+/// left 1, right 2, soft drop 4, hard drop 8, rotate 16, rotate back 32, hold 64
+#[pyclass(name = "Engine")]
+struct PyEngine(Engine);
+
+#[pymethods]
+impl PyEngine {
+    #[new]
+    #[pyo3(signature = (mode="CLASSIC", level=0, seed=0, hold=false))]
+    fn new(mode: &str, level: u32, seed: u64, hold: bool) -> PyResult<Self> {
+        let mut engine = Engine::new(
+            parse_mode(mode).map_err(PyValueError::new_err)?,
+            level,
+            seed,
+        );
+        engine.hold_enabled = hold;
+        Ok(Self(engine))
+    }
+
+    /// one frame with the keys held, names of what happened
+    #[pyo3(signature = (keys=0))]
+    fn step(&mut self, keys: u8) -> Vec<&'static str> {
+        self.0.step(&Input::from_bits(keys));
+        self.0.take_events().iter().map(event_name).collect()
+    }
+
+    #[getter]
+    fn board(&self) -> PyBoard {
+        PyBoard(self.0.board.clone())
+    }
+
+    /// falling brick as `(type, rotation, x, y)`, `None` between bricks
+    #[getter]
+    fn moving(&self) -> Option<(usize, usize, i8, i8)> {
+        let (brick, pos) = (self.0.moving_brick, self.0.moving_pos);
+        (self.0.phase == Phase::Falling).then_some((brick.ty, brick.rotation, pos.0, pos.1))
+    }
+
+    #[getter]
+    fn next(&self) -> Option<usize> {
+        self.0.next_brick.map(|brick| brick.ty)
+    }
+
+    #[getter]
+    fn hold(&self) -> Option<usize> {
+        self.0.hold_brick.map(|brick| brick.ty)
+    }
+
+    #[getter]
+    fn can_hold(&self) -> bool {
+        self.0.can_hold()
+    }
+
+    #[getter]
+    fn phase(&self) -> &'static str {
+        match self.0.phase {
+            Phase::Spawning(_) => "SPAWNING",
+            Phase::Falling => "FALLING",
+            Phase::Clearing(_) => "CLEARING",
+            Phase::Over => "OVER",
+        }
+    }
+
+    #[getter]
+    fn score(&self) -> u32 {
+        self.0.stats.score
+    }
+
+    #[getter]
+    fn lines(&self) -> u32 {
+        self.0.stats.lines
+    }
+
+    #[getter]
+    fn level(&self) -> u32 {
+        self.0.stats.level
+    }
+
+    #[getter]
+    fn pieces(&self) -> u32 {
+        self.0.stats.pieces
+    }
+
+    #[getter]
+    fn frames(&self) -> u32 {
+        self.0.stats.frames
+    }
+
+    /// TOP_OUT, COMPLETED, FAILED or OUT_OF_BRICKS once over
+    #[getter]
+    fn outcome(&self) -> Option<&'static str> {
+        self.0.outcome.map(|outcome| outcome.name())
+    }
+
+    fn is_over(&self) -> bool {
+        self.0.is_over()
+    }
+}
+
+fn observation<'py>(py: Python<'py>, observation: &Observation) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    let lines = observation.board.len();
+    let width = observation.board.first().map_or(0, Vec::len);
+    let board = Array2::from_shape_fn((lines, width), |(y, x)| observation.board[y][x] as u8);
+    dict.set_item("board", board.into_pyarray(py))?;
+    dict.set_item(
+        "moving",
+        observation
+            .moving
+            .map(|(brick, pos)| (brick.ty, brick.rotation, pos.0, pos.1)),
+    )?;
+    dict.set_item("next", observation.next.map(|brick| brick.ty))?;
+    dict.set_item("hold", observation.hold.map(|brick| brick.ty))?;
+    dict.set_item("can_hold", observation.can_hold)?;
+    dict.set_item("score", observation.score)?;
+    dict.set_item("lines", observation.lines)?;
+    dict.set_item("level", observation.level)?;
+    dict.set_item("pieces", observation.pieces)?;
+    dict.set_item("frames", observation.frames)?;
+    dict.set_item("combo", observation.combo)?;
+    dict.set_item("back_to_back", observation.back_to_back)?;
+    // one row by placement: hold, type, rotation, x, y
+    let placements = Array2::from_shape_fn((observation.placements.len(), 5), |(i, column)| {
+        let placement = &observation.placements[i];
+        match column {
+            0 => placement.hold as i32,
+            1 => placement.brick.ty as i32,
+            2 => placement.brick.rotation as i32,
+            3 => placement.pos.0 as i32,
+            _ => placement.pos.1 as i32,
+        }
+    });
+    dict.set_item("placements", placements.into_pyarray(py))?;
+    Ok(dict)
+}
+
+fn info<'py>(py: Python<'py>, info: &Info) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("lines", info.lines)?;
+    dict.set_item("score", info.score)?;
+    dict.set_item("pieces", info.pieces)?;
+    dict.set_item("holes", info.holes)?;
+    dict.set_item("frames", info.frames)?;
+    dict.set_item("outcome", info.outcome.map(|outcome| outcome.name()))?;
+    Ok(dict)
+}
+
+type StepTuple<'py> = (Bound<'py, PyDict>, f64, bool, Bound<'py, PyDict>);
+
+fn step_tuple<'py>(py: Python<'py>, step: Step) -> PyResult<StepTuple<'py>> {
+    Ok((
+        observation(py, &step.observation)?,
+        step.reward,
+        step.done,
+        info(py, &step.info)?,
+    ))
+}
+
+/// gym-style environment, see src/env.rs.
+/// `step(i)` plays row `i` of the observation placements, `step_keys(keys)` one frame of keys.
+#[pyclass(name = "Env")]
+struct PyEnv(Env);
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (mode="CLASSIC", level=0, hold=None))]
+    fn new(mode: &str, level: u32, hold: Option<bool>) -> PyResult<Self> {
+        let mut env = Env::new(parse_mode(mode).map_err(PyValueError::new_err)?);
+        env.level = level;
+        if let Some(hold) = hold {
+            env.hold = hold;
+        }
+        Ok(Self(env))
+    }
+
+    #[pyo3(signature = (seed=0))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> PyResult<Bound<'py, PyDict>> {
+        observation(py, &self.0.reset(seed))
+    }
+
+    fn step<'py>(&mut self, py: Python<'py>, action: usize) -> PyResult<StepTuple<'py>> {
+        step_tuple(py, self.0.step(Action::Place(action)))
+    }
+
+    fn step_keys<'py>(&mut self, py: Python<'py>, keys: u8) -> PyResult<StepTuple<'py>> {
+        step_tuple(py, self.0.step(Action::Keys(Input::from_bits(keys))))
+    }
+
+    #[getter]
+    fn board(&self) -> PyBoard {
+        PyBoard(self.0.engine().board.clone())
+    }
+
+    /// weights of the reward
+    #[getter]
+    fn rewards<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let rewards = &self.0.rewards;
+        let dict = PyDict::new(py);
+        dict.set_item("lines", rewards.lines)?;
+        dict.set_item("score", rewards.score)?;
+        dict.set_item("survival", rewards.survival)?;
+        dict.set_item("holes", rewards.holes)?;
+        dict.set_item("game_over", rewards.game_over)?;
+        Ok(dict)
+    }
+
+    /// change some weights of the reward, others are kept
+    #[pyo3(signature = (lines=None, score=None, survival=None, holes=None, game_over=None))]
+    fn set_rewards(
+        &mut self,
+        lines: Option<f64>,
+        score: Option<f64>,
+        survival: Option<f64>,
+        holes: Option<f64>,
+        game_over: Option<f64>,
+    ) {
+        let rewards = &mut self.0.rewards;
+        rewards.lines = lines.unwrap_or(rewards.lines);
+        rewards.score = score.unwrap_or(rewards.score);
+        rewards.survival = survival.unwrap_or(rewards.survival);
+        rewards.holes = holes.unwrap_or(rewards.holes);
+        rewards.game_over = game_over.unwrap_or(rewards.game_over);
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "bevy_tetris")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("BRICK_NAMES", BRICK_NAMES.iter().collect::<String>())?;
+    m.add("BOARD_LINES", BOARD_Y)?;
+    m.add_function(wrap_pyfunction!(brick_dots, m)?)?;
+    m.add_function(wrap_pyfunction!(rotations, m)?)?;
+    m.add_class::<PyBoard>()?;
+    m.add_class::<PyEngine>()?;
+    m.add_class::<PyEnv>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board_widths() {
+        for width in [1, 10, 32] {
+            assert_eq!(board_width(width).unwrap(), width as i8);
+        }
+        for width in [0, 33, 128, 300] {
+            assert!(board_width(width).is_err(), "{}", width);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::bot::{Bot, DIFFICULTIES};
+use crate::engine::Engine;
+use crate::modes::GameMode;
+use crate::player::{spawn_player, Cpu, Owner, Player};
+use crate::{GameData, GameState, MenuText};
+use bevy::prelude::*;
+
+/// key to hand the game to the computer and take it back
+const AUTOPLAY_KEY: KeyCode = KeyCode::B;
+/// seconds in menu without a key pressed before the demo starts
+const DEMO_IDLE_SECS: f32 = 20.0;
+
+/// game played by the computer while nobody plays, ends by any key
+#[derive(Component)]
+pub struct Demo;
+
+/// the best computer player, the demo doesn't start external bots
+fn demo_bot() -> Bot {
+    Bot::new(&DIFFICULTIES[DIFFICULTIES.len() - 1], rand::random())
+}
+
+#[allow(clippy::type_complexity)]
+pub fn autoplay_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    game: Res<GameData>,
+    players: Query<(Entity, Option<&Cpu>), (With<Player>, Without<Demo>)>,
+) {
+    if !keyboard_input.just_pressed(AUTOPLAY_KEY) {
+        return;
+    }
+    for (entity, cpu) in &players {
+        if cpu.is_some() {
+            commands.entity(entity).remove::<Cpu>();
+        } else {
+            commands
+                .entity(entity)
+                .insert(Cpu(game.computer(rand::random())));
+        }
+    }
+}
+
+/// start the demo when the menu is left alone
+#[allow(clippy::too_many_arguments)]
+pub fn demo_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut idle: Local<f32>,
+    mut state: ResMut<NextState<GameState>>,
+    mut game: ResMut<GameData>,
+    menu: Query<Entity, With<MenuText>>,
+) {
+    if keyboard_input.get_pressed().next().is_some() {
+        *idle = 0.0;
+        return;
+    }
+    *idle += time.delta_seconds();
+    if *idle < DEMO_IDLE_SECS {
+        return;
+    }
+    *idle = 0.0;
+    for entity in &menu {
+        commands.entity(entity).despawn_recursive();
+    }
+    // rules of puzzles, missions and lessons played before don't apply
+    game.puzzle = None;
+    game.mission = None;
+    game.tutorial = None;
+    let engine = Engine::new(GameMode::Classic, 0, rand::random());
+    let player = spawn_player(&mut commands, &asset_server, 0, engine, None);
+    commands.entity(player).insert((Cpu(demo_bot()), Demo));
+    state.set(GameState::Playing);
+}
+
+/// show who plays, and go back to menu when the demo is over or a key is pressed
+#[allow(clippy::type_complexity)]
+pub fn demo_play_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut state: ResMut<NextState<GameState>>,
+    mut players: Query<(&mut Player, Option<&Cpu>, Option<&Demo>)>,
+    despawn: Query<Entity, Or<(With<Player>, With<Owner>)>>,
+) {
+    let mut demo_over = false;
+    for (mut player, cpu, demo) in &mut players {
+        if demo.is_some() {
+            player.info += "\nDEMO";
+            demo_over =
+                player.engine.is_over() || keyboard_input.get_just_pressed().next().is_some();
+        } else if cpu.is_some() {
+            player.info += "\nAUTOPLAY";
+        }
+    }
+    if demo_over {
+        for entity in &despawn {
+            commands.entity(entity).despawn_recursive();
+        }
+        state.set(GameState::Menu);
+    }
+}
@@ -0,0 +1,428 @@
+//! arena of computer players, plays versus matches between them without a window
+//! and prints win rates and Elo ratings, to choose which one the game plays against.
+//!
+//! Both players of a game get the same bricks, and every pairing plays the same seeds.
+//! Bots wait for their suggestions instead of losing frames to thinking, so games run
+//! as fast as the computer goes; bricks per second of each difficulty still hold.
+//! Run with
+//!
+//! ```text
+//! cargo run --release --bin arena -- --bot HARD --bot EXPERT --bot EXPERT@bot_weights.txt --games 20
+//! cargo run --release --bin arena -- --bot INSANE --bot "tbp:cold-clear" --tournament swiss
+//! ```
+//!
+//! Options:
+//! * `--bot SPEC`: a player, at least two. SPEC is a difficulty of src/bot.rs as `EXPERT`,
+//!   one playing with weights of a file (src/bin/train.rs) as `EXPERT@FILE`,
+//!   or an external bot speaking the Tetris Bot Protocol as `tbp:COMMAND`
+//! * `--tournament round-robin|swiss`: everyone plays everyone once, or `--rounds`
+//!   rounds against players with as many points, default round-robin
+//! * `--rounds N`: rounds of a swiss tournament, default enough to find a winner
+//! * `--games N`: games of each pairing, default 10
+//! * `--seed N`: seed of the first game of a pairing, default random
+//! * `--frames N`: frames a game is a draw after, default 10 minutes of play
+//! * `--format json|csv`: default json
+use bevy_tetris::bot::{Bot, Weights, DIFFICULTIES, WEIGHTS};
+use bevy_tetris::engine::{Engine, Event};
+use bevy_tetris::modes::GameMode;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// 10 minutes at 60 frames per second
+const DEFAULT_FRAMES: u32 = 60 * 60 * 10;
+/// rating of a player before any game
+const ELO_START: f64 = 1500.0;
+/// rating change of a game, times how unexpected the result was
+const ELO_K: f64 = 16.0;
+/// times ratings are updated by all games, so they don't depend on the order of games.
+/// Each pass moves a rating by at most `ELO_K`, so one winning all doesn't go up for ever
+const ELO_PASSES: usize = 200;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Tournament {
+    RoundRobin,
+    Swiss,
+}
+
+/// how a player plays
+enum Kind {
+    Difficulty(usize, Weights),
+    External(String),
+}
+
+struct Entrant {
+    /// SPEC of `--bot`
+    name: String,
+    kind: Kind,
+}
+
+impl Entrant {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let kind = if let Some(command) = spec.strip_prefix("tbp:") {
+            Kind::External(command.to_string())
+        } else {
+            let (name, weights) = match spec.split_once('@') {
+                Some((name, file)) => (
+                    name,
+                    Weights::load(Path::new(file)).map_err(|e| format!("{}: {}", file, e))?,
+                ),
+                None => (spec, *WEIGHTS),
+            };
+            let difficulty = DIFFICULTIES
+                .iter()
+                .position(|difficulty| difficulty.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("unknown bot `{}`", name))?;
+            Kind::Difficulty(difficulty, weights)
+        };
+        Ok(Self {
+            name: spec.to_string(),
+            kind,
+        })
+    }
+
+    fn bot(&self, seed: u64) -> Result<Bot, String> {
+        let mut bot = match &self.kind {
+            Kind::Difficulty(difficulty, weights) => {
+                let mut bot = Bot::new(&DIFFICULTIES[*difficulty], seed);
+                bot.evaluator = Box::new(*weights);
+                bot
+            }
+            Kind::External(command) => {
+                Bot::external(command, seed).map_err(|e| format!("{}: {}", command, e))?
+            }
+        };
+        bot.blocking = true;
+        Ok(bot)
+    }
+}
+
+struct Options {
+    entrants: Vec<Entrant>,
+    tournament: Tournament,
+    rounds: usize,
+    games: u64,
+    seed: u64,
+    frames: u32,
+    format: Format,
+}
+
+/// result of one game
+#[derive(Debug, Serialize)]
+struct GameResult {
+    seed: u64,
+    left: String,
+    right: String,
+    /// `None` for a draw, when both top out in the same frame or no one does in time
+    winner: Option<String>,
+    frames: u32,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Standing {
+    name: String,
+    games: u32,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    /// wins and half the draws of all games
+    win_rate: f64,
+    elo: f64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    games: &'a [GameResult],
+    standings: &'a [Standing],
+}
+
+/// value of an option as `--games 100`
+fn arg(args: &[String], name: &str) -> Option<String> {
+    args.windows(2)
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+}
+
+fn parse<T: std::str::FromStr>(args: &[String], name: &str, default: T) -> Result<T, String> {
+    match arg(args, name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("bad {} `{}`", name, value)),
+        None => Ok(default),
+    }
+}
+
+fn options(args: &[String]) -> Result<Options, String> {
+    let entrants = args
+        .windows(2)
+        .filter(|pair| pair[0] == "--bot")
+        .map(|pair| Entrant::parse(&pair[1]))
+        .collect::<Result<Vec<_>, _>>()?;
+    if entrants.len() < 2 {
+        return Err("at least two `--bot` are needed".to_string());
+    }
+    let mut names = HashSet::new();
+    if let Some(twice) = entrants.iter().find(|entrant| !names.insert(&entrant.name)) {
+        return Err(format!("`{}` is given twice", twice.name));
+    }
+    let tournament = match arg(args, "--tournament").as_deref() {
+        None | Some("round-robin") => Tournament::RoundRobin,
+        Some("swiss") => Tournament::Swiss,
+        Some(other) => return Err(format!("unknown tournament `{}`", other)),
+    };
+    let format = match arg(args, "--format").as_deref() {
+        None | Some("json") => Format::Json,
+        Some("csv") => Format::Csv,
+        Some(other) => return Err(format!("unknown format `{}`", other)),
+    };
+    // rounds a swiss tournament needs for one player to win all
+    let rounds = (entrants.len() as f64).log2().ceil() as usize;
+    Ok(Options {
+        rounds: parse(args, "--rounds", rounds)?,
+        entrants,
+        tournament,
+        games: parse(args, "--games", 10)?,
+        seed: parse(args, "--seed", rand::random())?,
+        frames: parse(args, "--frames", DEFAULT_FRAMES)?,
+        format,
+    })
+}
+
+/// a game between entrants `left` and `right`
+fn play(options: &Options, left: usize, right: usize, seed: u64) -> Result<GameResult, String> {
+    let players = [&options.entrants[left], &options.entrants[right]];
+    let mut bots = [players[0].bot(seed)?, players[1].bot(seed)?];
+    // both boards as in versus, same bricks and garbage holes
+    let mut engines = [(); 2].map(|_| {
+        let mut engine = Engine::new(GameMode::Versus, 0, seed);
+        engine.hold_enabled = true;
+        engine
+    });
+    while engines.iter().all(|engine| !engine.is_over()) && engines[0].stats.frames < options.frames
+    {
+        for (engine, bot) in engines.iter_mut().zip(&mut bots) {
+            let input = bot.input(engine);
+            engine.step(&input);
+        }
+        for slot in 0..engines.len() {
+            for event in engines[slot].take_events() {
+                if let Event::Attack(lines) = event {
+                    engines[1 - slot].garbage.receive(lines);
+                }
+            }
+        }
+    }
+    let winner = match (engines[0].is_over(), engines[1].is_over()) {
+        (false, true) => Some(players[0].name.clone()),
+        (true, false) => Some(players[1].name.clone()),
+        _ => None,
+    };
+    Ok(GameResult {
+        seed,
+        left: players[0].name.clone(),
+        right: players[1].name.clone(),
+        winner,
+        frames: engines[0].stats.frames,
+    })
+}
+
+/// `options.games` games of a pairing, the same seeds for every pairing
+fn play_pairing(
+    options: &Options,
+    left: usize,
+    right: usize,
+    results: &mut Vec<GameResult>,
+) -> Result<(), String> {
+    let (mut wins, mut losses) = (0, 0);
+    for game in 0..options.games {
+        let result = play(options, left, right, options.seed.wrapping_add(game))?;
+        match &result.winner {
+            Some(winner) if *winner == result.left => wins += 1,
+            Some(_) => losses += 1,
+            None => {}
+        }
+        results.push(result);
+    }
+    eprintln!(
+        "{} vs {}: {} wins, {} losses, {} draws",
+        options.entrants[left].name,
+        options.entrants[right].name,
+        wins,
+        losses,
+        options.games - wins - losses
+    );
+    Ok(())
+}
+
+/// wins and half the draws of each player
+fn points(options: &Options, results: &[GameResult]) -> Vec<f64> {
+    options
+        .entrants
+        .iter()
+        .map(|entrant| {
+            results
+                .iter()
+                .filter(|r| r.left == entrant.name || r.right == entrant.name)
+                .map(|r| match &r.winner {
+                    Some(winner) if *winner == entrant.name => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5,
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// rounds pairing players with as many points, who didn't play each other yet.
+/// With an odd number of players the last one sits out the round
+fn swiss(options: &Options, results: &mut Vec<GameResult>) -> Result<(), String> {
+    let mut met = HashSet::new();
+    for _ in 0..options.rounds {
+        let points = points(options, results);
+        let mut order: Vec<usize> = (0..options.entrants.len()).collect();
+        order.sort_by(|a, b| points[*b].total_cmp(&points[*a]));
+        while order.len() > 1 {
+            let left = order.remove(0);
+            // the closest in points not met yet, or the closest when all were met
+            let i = order
+                .iter()
+                .position(|right| !met.contains(&(left.min(*right), left.max(*right))))
+                .unwrap_or(0);
+            let right = order.remove(i);
+            met.insert((left.min(right), left.max(right)));
+            play_pairing(options, left, right, results)?;
+        }
+    }
+    Ok(())
+}
+
+/// ratings fitting the results of all games, on the Elo scale
+fn elo(options: &Options, results: &[GameResult]) -> Vec<f64> {
+    let index = |name: &str| {
+        options
+            .entrants
+            .iter()
+            .position(|entrant| entrant.name == name)
+            .expect("games are between entrants")
+    };
+    let games: Vec<_> = results
+        .iter()
+        .map(|r| {
+            let score = match &r.winner {
+                Some(winner) if *winner == r.left => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            (index(&r.left), index(&r.right), score)
+        })
+        .collect();
+    let mut played = vec![0.0; options.entrants.len()];
+    for &(left, right, _) in &games {
+        played[left] += 1.0;
+        played[right] += 1.0;
+    }
+    let mut ratings = vec![ELO_START; options.entrants.len()];
+    for _ in 0..ELO_PASSES {
+        let mut change = vec![0.0; ratings.len()];
+        for &(left, right, score) in &games {
+            let expected = 1.0 / (1.0 + 10f64.powf((ratings[right] - ratings[left]) / 400.0));
+            change[left] += ELO_K * (score - expected) / played[left];
+            change[right] -= ELO_K * (score - expected) / played[right];
+        }
+        for (rating, change) in ratings.iter_mut().zip(change) {
+            *rating += change;
+        }
+    }
+    ratings
+}
+
+/// standings of all players, best rated first
+fn standings(options: &Options, results: &[GameResult]) -> Vec<Standing> {
+    let ratings = elo(options, results);
+    let mut standings: Vec<_> = options
+        .entrants
+        .iter()
+        .zip(ratings)
+        .map(|(entrant, elo)| {
+            let mut standing = Standing {
+                name: entrant.name.clone(),
+                elo,
+                ..Standing::default()
+            };
+            for r in results {
+                if r.left != entrant.name && r.right != entrant.name {
+                    continue;
+                }
+                standing.games += 1;
+                match &r.winner {
+                    Some(winner) if *winner == entrant.name => standing.wins += 1,
+                    Some(_) => standing.losses += 1,
+                    None => standing.draws += 1,
+                }
+            }
+            if standing.games > 0 {
+                standing.win_rate =
+                    (standing.wins as f64 + standing.draws as f64 / 2.0) / standing.games as f64;
+            }
+            standing
+        })
+        .collect();
+    standings.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+    standings
+}
+
+/// standings, names are quoted as they may have commas
+fn print_csv(standings: &[Standing]) {
+    println!("name,games,wins,losses,draws,win_rate,elo");
+    for s in standings {
+        println!(
+            "\"{}\",{},{},{},{},{:.3},{:.0}",
+            s.name.replace('"', "\"\""),
+            s.games,
+            s.wins,
+            s.losses,
+            s.draws,
+            s.win_rate,
+            s.elo
+        );
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let options = options(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let mut results = Vec::new();
+    let played = match options.tournament {
+        Tournament::RoundRobin => (0..options.entrants.len())
+            .flat_map(|left| (left + 1..options.entrants.len()).map(move |right| (left, right)))
+            .try_for_each(|(left, right)| play_pairing(&options, left, right, &mut results)),
+        Tournament::Swiss => swiss(&options, &mut results),
+    };
+    if let Err(e) = played {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let standings = standings(&options, &results);
+    match options.format {
+        Format::Json => {
+            let report = Report {
+                games: &results,
+                standings: &standings,
+            };
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("results are plain JSON")
+            );
+        }
+        Format::Csv => print_csv(&standings),
+    }
+}
@@ -0,0 +1,451 @@
+//! lobby and relay server of online versus.
+//!
+//! Clients meet in rooms by code, say READY, get the same seed and relay keys
+//! to each other through here, so players don't need to reach each other directly.
+//! Wire protocol in src/protocol.rs. Run with
+//!
+//! ```text
+//! cargo run --bin server -- --addr 0.0.0.0:7878
+//! ```
+use bevy_tetris::protocol::{Connection, Message, PROTOCOL_VERSION, ROOM_PLAYERS, SERVER_PORT};
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+/// sleep when nothing arrived
+const IDLE: Duration = Duration::from_millis(1);
+/// letters of a room code
+const CODE_LEN: usize = 4;
+
+struct Client {
+    connection: Connection,
+    /// said HELLO with the same version
+    greeted: bool,
+    room: Option<String>,
+    ready: bool,
+    /// player index in the room once started
+    slot: u32,
+}
+
+struct Room {
+    /// clients in order of joining
+    clients: Vec<usize>,
+    started: bool,
+    /// RESULT by slot of who sent it
+    results: Vec<(u32, Option<u32>)>,
+}
+
+#[derive(Default)]
+struct Server {
+    clients: HashMap<usize, Client>,
+    rooms: HashMap<String, Room>,
+    next_id: usize,
+}
+
+impl Server {
+    fn send(&mut self, id: usize, message: &Message) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.connection.send(message);
+        }
+    }
+
+    fn error(&mut self, id: usize, text: &str) {
+        self.send(id, &Message::Error(text.to_string()));
+    }
+
+    /// tell everyone in the room how many are there
+    fn send_room(&mut self, code: &str) {
+        let Some(room) = self.rooms.get(code) else {
+            return;
+        };
+        let message = Message::Room {
+            code: code.to_string(),
+            players: room.clients.len() as u32,
+        };
+        for id in room.clients.clone() {
+            self.send(id, &message);
+        }
+    }
+
+    /// message to the other players of a started room
+    fn relay(&mut self, id: usize, message: &Message) {
+        let Some(room) = self.room_of(id) else {
+            return;
+        };
+        if !room.started {
+            return;
+        }
+        for other in room.clients.clone() {
+            if other != id {
+                self.send(other, message);
+            }
+        }
+    }
+
+    fn room_of(&self, id: usize) -> Option<&Room> {
+        let code = self.clients.get(&id)?.room.as_ref()?;
+        self.rooms.get(code)
+    }
+
+    fn new_code(&self) -> String {
+        let mut rng = rand::thread_rng();
+        loop {
+            let code: String = (0..CODE_LEN)
+                .map(|_| rng.gen_range(b'A'..=b'Z') as char)
+                .collect();
+            if !self.rooms.contains_key(&code) {
+                return code;
+            }
+        }
+    }
+
+    fn enter(&mut self, id: usize, code: String) {
+        let room = self.rooms.entry(code.clone()).or_insert(Room {
+            clients: Vec::new(),
+            started: false,
+            results: Vec::new(),
+        });
+        room.clients.push(id);
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(code.clone());
+        }
+        self.send_room(&code);
+    }
+
+    /// start the room when it is full and everyone is ready
+    fn try_start(&mut self, code: &str) {
+        let Some(room) = self.rooms.get_mut(code) else {
+            return;
+        };
+        let ready = room
+            .clients
+            .iter()
+            .all(|id| self.clients.get(id).is_some_and(|client| client.ready));
+        if room.started || room.clients.len() < ROOM_PLAYERS || !ready {
+            return;
+        }
+        room.started = true;
+        let seed = rand::random();
+        println!("room {}: started", code);
+        for (slot, id) in room.clients.clone().into_iter().enumerate() {
+            if let Some(client) = self.clients.get_mut(&id) {
+                client.slot = slot as u32;
+            }
+            self.send(
+                id,
+                &Message::Start {
+                    seed,
+                    slot: slot as u32,
+                },
+            );
+        }
+    }
+
+    /// keep a RESULT, and print the match when all players reported
+    fn report(&mut self, id: usize, winner: Option<u32>) {
+        let Some(client) = self.clients.get(&id) else {
+            return;
+        };
+        let slot = client.slot;
+        let Some(code) = client.room.clone() else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        if !room.started || room.results.iter().any(|(from, _)| *from == slot) {
+            return;
+        }
+        room.results.push((slot, winner));
+        if room.results.len() < ROOM_PLAYERS {
+            return;
+        }
+        let first = room.results[0].1;
+        if room.results.iter().any(|(_, winner)| *winner != first) {
+            println!("room {}: results disagree {:?}", code, room.results);
+        } else if let Some(winner) = first {
+            println!("room {}: player {} won", code, winner + 1);
+        } else {
+            println!("room {}: draw", code);
+        }
+    }
+
+    fn handle(&mut self, id: usize, message: Message) {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return;
+        };
+        match message {
+            Message::Hello { version, .. } if version != PROTOCOL_VERSION => {
+                self.error(id, "VERSION MISMATCH");
+                self.leave(id);
+            }
+            Message::Hello { .. } => client.greeted = true,
+            Message::Bye => self.leave(id),
+            _ if !client.greeted => self.error(id, "SAY HELLO FIRST"),
+            Message::Create | Message::Join(_) if client.room.is_some() => {
+                self.error(id, "ALREADY IN ROOM");
+            }
+            Message::Create => {
+                let code = self.new_code();
+                println!("room {}: created", code);
+                self.enter(id, code);
+            }
+            Message::Join(code) => {
+                let code = code.to_uppercase();
+                match self.rooms.get(&code) {
+                    None => self.error(id, "NO SUCH ROOM"),
+                    Some(room) if room.started || room.clients.len() >= ROOM_PLAYERS => {
+                        self.error(id, "ROOM FULL");
+                    }
+                    Some(_) => self.enter(id, code),
+                }
+            }
+            Message::Ready => {
+                client.ready = true;
+                if let Some(code) = client.room.clone() {
+                    self.try_start(&code);
+                }
+            }
+            Message::Input { .. } | Message::Hash { .. } | Message::Ping(_) | Message::Pong(_) => {
+                self.relay(id, &message);
+            }
+            Message::Result(winner) => self.report(id, winner),
+            Message::Room { .. } | Message::Start { .. } | Message::Error(_) => {}
+        }
+    }
+
+    /// drop a client, the others in a started room are told BYE
+    fn leave(&mut self, id: usize) {
+        let Some(client) = self.clients.remove(&id) else {
+            return;
+        };
+        let Some(code) = client.room else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        room.clients.retain(|other| *other != id);
+        if room.clients.is_empty() {
+            println!("room {}: closed", code);
+            self.rooms.remove(&code);
+        } else if room.started {
+            for other in room.clients.clone() {
+                self.send(other, &Message::Bye);
+            }
+        } else {
+            self.send_room(&code);
+        }
+    }
+
+    /// a client connected, it says HELLO before anything else
+    fn add(&mut self, connection: Connection) {
+        self.clients.insert(
+            self.next_id,
+            Client {
+                connection,
+                greeted: false,
+                room: None,
+                ready: false,
+                slot: 0,
+            },
+        );
+        self.next_id += 1;
+    }
+
+    /// handle what arrived from every client, returns whether anything did
+    fn receive(&mut self) -> bool {
+        let mut busy = false;
+        let ids: Vec<_> = self.clients.keys().copied().collect();
+        for id in ids {
+            let Some(client) = self.clients.get_mut(&id) else {
+                continue;
+            };
+            let messages = client.connection.receive();
+            let closed = client.connection.closed;
+            busy |= !messages.is_empty();
+            for message in messages {
+                self.handle(id, message);
+            }
+            if closed {
+                self.leave(id);
+            }
+        }
+        busy
+    }
+
+    /// accept clients and handle what arrived, returns whether anything happened
+    fn poll(&mut self, listener: &TcpListener) -> bool {
+        let mut busy = false;
+        while let Ok((stream, addr)) = listener.accept() {
+            busy = true;
+            let Ok(connection) = Connection::new(stream) else {
+                continue;
+            };
+            println!("{} connected", addr);
+            self.add(connection);
+        }
+        self.receive() || busy
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let address = args
+        .windows(2)
+        .find(|pair| pair[0] == "--addr")
+        .map_or(format!("0.0.0.0:{}", SERVER_PORT), |pair| pair[1].clone());
+    let listener = TcpListener::bind(&address).unwrap_or_else(|e| {
+        eprintln!("listen {}: {}", address, e);
+        std::process::exit(1);
+    });
+    listener
+        .set_nonblocking(true)
+        .expect("non-blocking listener");
+    println!("listening on {}", address);
+    let mut server = Server::default();
+    loop {
+        if !server.poll(&listener) {
+            thread::sleep(IDLE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a client in memory, greeted when `hello`
+    fn connect(server: &mut Server, hello: bool) -> Connection {
+        let (mut client, connection) = Connection::pair();
+        server.add(connection);
+        if hello {
+            client.send(&Message::Hello {
+                version: PROTOCOL_VERSION,
+                seed: 0,
+            });
+        }
+        client
+    }
+
+    fn error(text: &str) -> Vec<Message> {
+        vec![Message::Error(text.to_string())]
+    }
+
+    fn room_code(messages: &[Message]) -> String {
+        match messages {
+            [Message::Room { code, .. }] => code.clone(),
+            _ => panic!("no room in {:?}", messages),
+        }
+    }
+
+    #[test]
+    fn hello_first() {
+        let mut server = Server::default();
+        let mut client = connect(&mut server, false);
+        client.send(&Message::Create);
+        server.receive();
+        assert_eq!(client.receive(), error("SAY HELLO FIRST"));
+
+        let mut old = connect(&mut server, false);
+        old.send(&Message::Hello {
+            version: PROTOCOL_VERSION + 1,
+            seed: 0,
+        });
+        server.receive();
+        assert_eq!(old.receive(), error("VERSION MISMATCH"));
+        assert_eq!(server.clients.len(), 1);
+    }
+
+    #[test]
+    fn join_ready_relay_leave() {
+        let mut server = Server::default();
+        let mut a = connect(&mut server, true);
+        let mut b = connect(&mut server, true);
+        let mut c = connect(&mut server, true);
+
+        a.send(&Message::Create);
+        c.send(&Message::Join("NOPE".to_string()));
+        server.receive();
+        let code = room_code(&a.receive());
+        assert_eq!(c.receive(), error("NO SUCH ROOM"));
+
+        // joining tells both how many are in the room, lower case codes work
+        b.send(&Message::Join(code.to_lowercase()));
+        server.receive();
+        let room = vec![Message::Room {
+            code: code.clone(),
+            players: 2,
+        }];
+        assert_eq!(a.receive(), room);
+        assert_eq!(b.receive(), room);
+        c.send(&Message::Join(code.clone()));
+        server.receive();
+        assert_eq!(c.receive(), error("ROOM FULL"));
+
+        // nothing is relayed before the match starts
+        a.send(&Message::Input { frame: 0, keys: 1 });
+        a.send(&Message::Ready);
+        server.receive();
+        assert!(b.receive().is_empty());
+        b.send(&Message::Ready);
+        server.receive();
+        let (a_start, b_start) = (a.receive(), b.receive());
+        let [Message::Start { seed, slot: 0 }] = a_start[..] else {
+            panic!("{:?}", a_start);
+        };
+        assert_eq!(b_start, [Message::Start { seed, slot: 1 }]);
+
+        // relayed to the other player only
+        let hash = Message::Hash { frame: 60, hash: 9 };
+        a.send(&hash);
+        b.send(&Message::Ping(5));
+        server.receive();
+        assert_eq!(b.receive(), [hash]);
+        assert_eq!(a.receive(), [Message::Ping(5)]);
+        assert!(c.receive().is_empty());
+
+        // both report, then one leaves and the other is told
+        a.send(&Message::Result(Some(1)));
+        b.send(&Message::Result(Some(1)));
+        server.receive();
+        assert_eq!(server.rooms[&code].results.len(), 2);
+        a.close();
+        server.receive();
+        assert_eq!(b.receive(), [Message::Bye]);
+        assert_eq!(server.rooms[&code].clients.len(), 1);
+        b.send(&Message::Bye);
+        server.receive();
+        assert!(server.rooms.is_empty());
+        assert_eq!(server.clients.len(), 1);
+    }
+
+    #[test]
+    fn leave_before_start() {
+        let mut server = Server::default();
+        let mut a = connect(&mut server, true);
+        let mut b = connect(&mut server, true);
+        a.send(&Message::Create);
+        server.receive();
+        let code = room_code(&a.receive());
+        b.send(&Message::Join(code.clone()));
+        b.send(&Message::Ready);
+        server.receive();
+        a.receive();
+
+        // the one left waits in the room for another player
+        b.send(&Message::Bye);
+        server.receive();
+        let room = Message::Room {
+            code: code.clone(),
+            players: 1,
+        };
+        assert_eq!(a.receive(), [room]);
+        assert!(!server.rooms[&code].started);
+        a.send(&Message::Create);
+        server.receive();
+        assert_eq!(a.receive(), error("ALREADY IN ROOM"));
+    }
+}
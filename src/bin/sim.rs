@@ -0,0 +1,319 @@
+//! headless simulator, plays games by the rules of src/engine.rs without a window
+//! as fast as the computer goes, and prints results of each game and all of them.
+//!
+//! Games are played by the computer player of src/bot.rs, or by the keys of a replay.
+//! Run with
+//!
+//! ```text
+//! cargo run --release --bin sim -- --mode versus --games 100 --seed 1 --bot EXPERT --format csv
+//! cargo run --release --bin sim -- --replay game.replay
+//! ```
+//!
+//! Options:
+//! * `--mode NAME`: CLASSIC, MASTER, NES or VERSUS, default CLASSIC
+//! * `--level N`: start level, default 0
+//! * `--hold`: hold is allowed, always in VERSUS
+//! * `--games N`: games with seeds one after another, default 1
+//! * `--seed N`: seed of the first game, default random
+//! * `--bot NAME`: difficulty of the computer player, default EXPERT
+//! * `--replay FILE`: play the keys of a replay (format in src/replay.rs) instead
+//! * `--record DIR`: write a replay of each game to `DIR/<seed>.replay`
+//! * `--frames N`: frames a game is stopped after, default an hour of play
+//! * `--format json|csv`: default json
+//!
+//! The summary names the weights file the computer player scored boards with,
+//! or `default` without one, `none` for a replay.
+use bevy_tetris::bot::{Bot, Weights, DIFFICULTIES};
+use bevy_tetris::consts::BOT_WEIGHTS_FILE;
+use bevy_tetris::engine::{Engine, Input, Outcome};
+use bevy_tetris::modes::GameMode;
+use bevy_tetris::replay::{parse_mode, Replay};
+use serde::Serialize;
+use std::path::Path;
+
+/// an hour at 60 frames per second
+const DEFAULT_FRAMES: u32 = 60 * 60 * 60;
+/// best bot without search, INSANE searches a budget of boards for each brick and is slower
+const DEFAULT_BOT: &str = "EXPERT";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+struct Options {
+    mode: GameMode,
+    level: u32,
+    hold: bool,
+    games: u64,
+    seed: u64,
+    bot: usize,
+    replay: Option<Replay>,
+    record: Option<String>,
+    frames: u32,
+    format: Format,
+}
+
+/// result of one game
+#[derive(Debug, Serialize)]
+struct GameResult {
+    seed: u64,
+    mode: &'static str,
+    score: u32,
+    lines: u32,
+    level: u32,
+    pieces: u32,
+    frames: u32,
+    /// how the game ended: TOP_OUT, COMPLETED, FAILED, OUT_OF_BRICKS,
+    /// FRAME_LIMIT or REPLAY_END when it didn't
+    outcome: &'static str,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    games: usize,
+    top_outs: usize,
+    mean_score: f64,
+    min_score: u32,
+    max_score: u32,
+    mean_lines: f64,
+    mean_pieces: f64,
+    mean_frames: f64,
+    /// weights of the computer player, `none` for a replay
+    weights: &'static str,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    games: &'a [GameResult],
+    summary: Summary,
+}
+
+/// value of an option as `--games 100`
+fn arg(args: &[String], name: &str) -> Option<String> {
+    args.windows(2)
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+}
+
+fn parse<T: std::str::FromStr>(args: &[String], name: &str, default: T) -> Result<T, String> {
+    match arg(args, name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("bad {} `{}`", name, value)),
+        None => Ok(default),
+    }
+}
+
+fn options(args: &[String]) -> Result<Options, String> {
+    let replay = match arg(args, "--replay") {
+        Some(file) => {
+            let text = std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", file, e))?;
+            Some(
+                text.parse::<Replay>()
+                    .map_err(|e| format!("{}: {}", file, e))?,
+            )
+        }
+        None => None,
+    };
+    let mode = match arg(args, "--mode") {
+        Some(name) => parse_mode(&name)?,
+        None => GameMode::Classic,
+    };
+    if !matches!(
+        mode,
+        GameMode::Classic | GameMode::Master | GameMode::Nes | GameMode::Versus
+    ) {
+        return Err(format!("mode {} can't be simulated", mode.name()));
+    }
+    let name = arg(args, "--bot").unwrap_or_else(|| DEFAULT_BOT.to_string());
+    let bot = DIFFICULTIES
+        .iter()
+        .position(|difficulty| difficulty.name.eq_ignore_ascii_case(&name))
+        .ok_or_else(|| format!("unknown bot `{}`", name))?;
+    let format = match arg(args, "--format").as_deref() {
+        None | Some("json") => Format::Json,
+        Some("csv") => Format::Csv,
+        Some(other) => return Err(format!("unknown format `{}`", other)),
+    };
+    Ok(Options {
+        mode,
+        level: parse(args, "--level", 0)?,
+        hold: args.iter().any(|arg| arg == "--hold") || mode == GameMode::Versus,
+        games: parse(args, "--games", 1)?,
+        seed: parse(args, "--seed", rand::random())?,
+        bot,
+        replay,
+        record: arg(args, "--record"),
+        frames: parse(args, "--frames", DEFAULT_FRAMES)?,
+        format,
+    })
+}
+
+/// play a game to its end or the frame limit, keys are given by `keys` until it has none.
+/// Keys played are kept in `replay` when it is recorded.
+fn play(
+    replay: &mut Replay,
+    record: bool,
+    frames: u32,
+    mut keys: impl FnMut(&Engine) -> Option<Input>,
+) -> GameResult {
+    let mut engine = replay.engine();
+    let mut ended = false;
+    while !engine.is_over() && engine.stats.frames < frames {
+        let Some(input) = keys(&engine) else {
+            ended = true;
+            break;
+        };
+        engine.step(&input);
+        engine.take_events();
+        if record {
+            replay.inputs.push(input);
+        }
+    }
+    let outcome = match engine.outcome {
+        Some(outcome) => outcome.name(),
+        None if ended => "REPLAY_END",
+        None => "FRAME_LIMIT",
+    };
+    GameResult {
+        seed: replay.seed,
+        mode: replay.mode.name(),
+        score: engine.stats.score,
+        lines: engine.stats.lines,
+        level: engine.stats.level,
+        pieces: engine.stats.pieces,
+        frames: engine.stats.frames,
+        outcome,
+    }
+}
+
+/// weights file loaded by the computer player, as [`bevy_tetris::bot::WEIGHTS`] does
+fn weights_file() -> &'static str {
+    match Weights::load(Path::new(BOT_WEIGHTS_FILE)) {
+        Ok(_) => BOT_WEIGHTS_FILE,
+        Err(_) => "default",
+    }
+}
+
+fn summary(results: &[GameResult], weights: &'static str) -> Summary {
+    if results.is_empty() {
+        return Summary {
+            weights,
+            ..Summary::default()
+        };
+    }
+    let mean = |value: fn(&GameResult) -> u32| {
+        results.iter().map(|r| value(r) as f64).sum::<f64>() / results.len() as f64
+    };
+    Summary {
+        games: results.len(),
+        top_outs: results
+            .iter()
+            .filter(|r| r.outcome == Outcome::TopOut.name())
+            .count(),
+        mean_score: mean(|r| r.score),
+        min_score: results.iter().map(|r| r.score).min().unwrap_or(0),
+        max_score: results.iter().map(|r| r.score).max().unwrap_or(0),
+        mean_lines: mean(|r| r.lines),
+        mean_pieces: mean(|r| r.pieces),
+        mean_frames: mean(|r| r.frames),
+        weights,
+    }
+}
+
+/// write the replay when asked by `--record`
+fn save(options: &Options, replay: &Replay) {
+    let Some(dir) = &options.record else {
+        return;
+    };
+    let path = Path::new(dir).join(format!("{}.replay", replay.seed));
+    if let Err(e) = std::fs::write(&path, replay.to_string()) {
+        eprintln!("{}: {}", path.display(), e);
+    }
+}
+
+/// games, then a blank line and the summary
+fn print_csv(results: &[GameResult], summary: &Summary) {
+    println!("seed,mode,score,lines,level,pieces,frames,outcome");
+    for r in results {
+        println!(
+            "{},{},{},{},{},{},{},{}",
+            r.seed, r.mode, r.score, r.lines, r.level, r.pieces, r.frames, r.outcome
+        );
+    }
+    println!();
+    println!(
+        "games,top_outs,mean_score,min_score,max_score,mean_lines,mean_pieces,mean_frames,weights"
+    );
+    println!(
+        "{},{},{:.1},{},{},{:.1},{:.1},{:.1},{}",
+        summary.games,
+        summary.top_outs,
+        summary.mean_score,
+        summary.min_score,
+        summary.max_score,
+        summary.mean_lines,
+        summary.mean_pieces,
+        summary.mean_frames,
+        summary.weights
+    );
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let options = options(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let mut results = Vec::new();
+    let weights = if options.replay.is_some() {
+        "none"
+    } else {
+        weights_file()
+    };
+    if let Some(recorded) = &options.replay {
+        let mut replay = Replay {
+            inputs: Vec::new(),
+            ..recorded.clone()
+        };
+        let mut inputs = recorded.inputs.iter();
+        results.push(play(
+            &mut replay,
+            options.record.is_some(),
+            u32::MAX,
+            |_| inputs.next().copied(),
+        ));
+        save(&options, &replay);
+    } else {
+        for game in 0..options.games {
+            let seed = options.seed.wrapping_add(game);
+            let mut replay = Replay::new(options.mode, options.level, seed, options.hold);
+            let mut bot = Bot::new(&DIFFICULTIES[options.bot], seed);
+            // search to the end in the frame, the same games on any computer
+            bot.blocking = true;
+            results.push(play(
+                &mut replay,
+                options.record.is_some(),
+                options.frames,
+                |engine| Some(bot.input(engine)),
+            ));
+            save(&options, &replay);
+        }
+    }
+    let summary = summary(&results, weights);
+    match options.format {
+        Format::Json => {
+            let report = Report {
+                games: &results,
+                summary,
+            };
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("results are plain JSON")
+            );
+        }
+        Format::Csv => print_csv(&results, &summary),
+    }
+}
@@ -0,0 +1,385 @@
+//! trainer of the weights the computer player of src/bot.rs scores boards with.
+//!
+//! A genetic algorithm: each generation every weight vector of the population plays
+//! the same seeded games without a window, on all cores, scored by their mean score.
+//! The best ones are kept, the others are replaced by children of two of the better
+//! ones with some weights changed at random. Weights of the best of each generation are
+//! written to `--out`, which the game and the simulator load when it is bot_weights.txt
+//! in the folder they run in. Run with
+//!
+//! ```text
+//! cargo run --release --bin train -- --generations 30 --population 32 --games 8
+//! ```
+//!
+//! Options:
+//! * `--mode NAME`: CLASSIC, MASTER, NES or VERSUS, default CLASSIC
+//! * `--level N`: start level, default 0
+//! * `--bot NAME`: difficulty the weights play at, default EXPERT
+//! * `--generations N`: default 20
+//! * `--population N`: weight vectors in each generation, default 24
+//! * `--games N`: games each weight vector plays in a generation, default 6
+//! * `--pieces N`: bricks a game is stopped after, default 500
+//! * `--seed N`: seed of the first game, games of each generation have new seeds, default random
+//! * `--from FILE`: weights to start from, default the ones the game plays with
+//! * `--out FILE`: default bot_weights.txt
+//! * `--threads N`: default all cores
+use bevy_tetris::bot::{Bot, Weights, DIFFICULTIES, FEATURES, WEIGHTS};
+use bevy_tetris::consts::BOT_WEIGHTS_FILE;
+use bevy_tetris::modes::GameMode;
+use bevy_tetris::replay::{parse_mode, Replay};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// an hour at 60 frames per second, in case a game neither ends nor locks bricks
+const MAX_FRAMES: u32 = 60 * 60 * 60;
+/// bot without search, search is slower
+const DEFAULT_BOT: &str = "EXPERT";
+/// part of the population kept for the next generation
+const ELITE: f64 = 0.25;
+/// weight vectors a parent is the best of
+const TOURNAMENT: usize = 3;
+/// chance of each weight of a child to change
+const MUTATION_RATE: f64 = 0.3;
+/// spread of changes of weights in the first generation, weight vectors are of length 1
+const SIGMA: f64 = 0.2;
+/// spread of changes is this times the one of the generation before
+const SIGMA_DECAY: f64 = 0.92;
+
+type Genome = [f64; FEATURES.len()];
+
+struct Options {
+    mode: GameMode,
+    level: u32,
+    bot: usize,
+    generations: usize,
+    population: usize,
+    games: usize,
+    pieces: u32,
+    seed: u64,
+    from: Weights,
+    out: String,
+    threads: usize,
+}
+
+struct Game {
+    score: u32,
+    lines: u32,
+    top_out: bool,
+}
+
+/// what a weight vector got in its games of a generation
+#[derive(Debug, Clone, Copy, Default)]
+struct Fitness {
+    score: f64,
+    lines: f64,
+    top_outs: usize,
+}
+
+/// value of an option as `--games 100`
+fn arg(args: &[String], name: &str) -> Option<String> {
+    args.windows(2)
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+}
+
+fn parse<T: std::str::FromStr>(args: &[String], name: &str, default: T) -> Result<T, String> {
+    match arg(args, name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("bad {} `{}`", name, value)),
+        None => Ok(default),
+    }
+}
+
+fn options(args: &[String]) -> Result<Options, String> {
+    let mode = match arg(args, "--mode") {
+        Some(name) => parse_mode(&name)?,
+        None => GameMode::Classic,
+    };
+    if !matches!(
+        mode,
+        GameMode::Classic | GameMode::Master | GameMode::Nes | GameMode::Versus
+    ) {
+        return Err(format!("mode {} can't be trained in", mode.name()));
+    }
+    let name = arg(args, "--bot").unwrap_or_else(|| DEFAULT_BOT.to_string());
+    let bot = DIFFICULTIES
+        .iter()
+        .position(|difficulty| difficulty.name.eq_ignore_ascii_case(&name))
+        .ok_or_else(|| format!("unknown bot `{}`", name))?;
+    let from = match arg(args, "--from") {
+        Some(file) => Weights::load(Path::new(&file)).map_err(|e| format!("{}: {}", file, e))?,
+        None => *WEIGHTS,
+    };
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let population = parse(args, "--population", 24)?;
+    if population < 2 {
+        return Err("a population is at least 2".to_string());
+    }
+    Ok(Options {
+        mode,
+        level: parse(args, "--level", 0)?,
+        bot,
+        generations: parse(args, "--generations", 20)?,
+        population,
+        games: parse::<usize>(args, "--games", 6)?.max(1),
+        pieces: parse(args, "--pieces", 500)?,
+        seed: parse(args, "--seed", rand::random())?,
+        from,
+        out: arg(args, "--out").unwrap_or_else(|| BOT_WEIGHTS_FILE.to_string()),
+        threads: parse::<usize>(args, "--threads", cores)?.max(1),
+    })
+}
+
+/// scaled to length 1, boards rank the same by weights of any length
+fn normalize(mut genome: Genome) -> Genome {
+    let length = genome.iter().map(|w| w * w).sum::<f64>().sqrt();
+    if length > 0.0 {
+        genome.iter_mut().for_each(|w| *w /= length);
+    }
+    genome
+}
+
+/// normal distribution by Box-Muller
+fn gaussian(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+}
+
+fn mutate(mut genome: Genome, sigma: f64, rng: &mut StdRng) -> Genome {
+    for w in genome.iter_mut() {
+        if rng.gen_bool(MUTATION_RATE) {
+            *w += sigma * gaussian(rng);
+        }
+    }
+    normalize(genome)
+}
+
+/// each weight somewhere between the ones of the parents
+fn crossover(a: &Genome, b: &Genome, rng: &mut StdRng) -> Genome {
+    let mut child = *a;
+    for (w, other) in child.iter_mut().zip(b) {
+        let t: f64 = rng.gen();
+        // rounding may step out of the parents' range
+        *w = (t * *w + (1.0 - t) * other).clamp(w.min(*other), w.max(*other));
+    }
+    child
+}
+
+/// the best of a few picked at random, `ranked` is best first
+fn tournament<'a>(ranked: &'a [Genome], rng: &mut StdRng) -> &'a Genome {
+    let best = (0..TOURNAMENT)
+        .map(|_| rng.gen_range(0..ranked.len()))
+        .min()
+        .unwrap_or(0);
+    &ranked[best]
+}
+
+/// score and lines of a game up to the brick limit, and whether it topped out
+fn play(options: &Options, weights: Weights, seed: u64) -> Game {
+    let replay = Replay::new(
+        options.mode,
+        options.level,
+        seed,
+        options.mode == GameMode::Versus,
+    );
+    let mut engine = replay.engine();
+    let mut bot = Bot::new(&DIFFICULTIES[options.bot], seed);
+    bot.blocking = true;
+    bot.evaluator = Box::new(weights);
+    while !engine.is_over()
+        && engine.stats.pieces < options.pieces
+        && engine.stats.frames < MAX_FRAMES
+    {
+        let input = bot.input(&engine);
+        engine.step(&input);
+        engine.take_events();
+    }
+    Game {
+        score: engine.stats.score,
+        lines: engine.stats.lines,
+        top_out: engine.is_over(),
+    }
+}
+
+/// fitness of each genome over the same games, played by `options.threads` threads
+fn evaluate(options: &Options, genomes: &[Genome], seed: u64) -> Vec<Fitness> {
+    let jobs = genomes.len() * options.games;
+    let next = AtomicUsize::new(0);
+    let mut fitness = vec![Fitness::default(); genomes.len()];
+    let played: Vec<Vec<(usize, Game)>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads.min(jobs))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut played = Vec::new();
+                    loop {
+                        let job = next.fetch_add(1, Ordering::Relaxed);
+                        if job >= jobs {
+                            break played;
+                        }
+                        let (genome, game) = (job / options.games, job % options.games);
+                        let weights = Weights::from_values(genomes[genome]);
+                        let result = play(options, weights, seed.wrapping_add(game as u64));
+                        played.push((genome, result));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("games don't panic"))
+            .collect()
+    });
+    let games = options.games as f64;
+    for (genome, game) in played.into_iter().flatten() {
+        fitness[genome].score += game.score as f64 / games;
+        fitness[genome].lines += game.lines as f64 / games;
+        fitness[genome].top_outs += game.top_out as usize;
+    }
+    fitness
+}
+
+/// the weights started from, and ones around them
+fn first_population(options: &Options, rng: &mut StdRng) -> Vec<Genome> {
+    let start = normalize(options.from.values());
+    std::iter::once(start)
+        .chain((1..options.population).map(|_| mutate(start, SIGMA, rng)))
+        .collect()
+}
+
+/// `population` ranked by the games of `generation`, best first, and the population after it
+fn evolve(
+    options: &Options,
+    population: &[Genome],
+    generation: usize,
+    sigma: f64,
+    rng: &mut StdRng,
+) -> (Vec<(Genome, Fitness)>, Vec<Genome>) {
+    let seed = options
+        .seed
+        .wrapping_add((generation * options.games) as u64);
+    let fitness = evaluate(options, population, seed);
+    let mut ranked: Vec<_> = population.iter().copied().zip(fitness).collect();
+    ranked.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+    // best ones play again with new seeds, so a lucky one doesn't stay for long
+    let genomes: Vec<Genome> = ranked.iter().map(|(genome, _)| *genome).collect();
+    let elite = ((options.population as f64 * ELITE) as usize).max(1);
+    let mut next = genomes[..elite].to_vec();
+    while next.len() < options.population {
+        let child = crossover(tournament(&genomes, rng), tournament(&genomes, rng), rng);
+        next.push(mutate(child, sigma, rng));
+    }
+    (ranked, next)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let options = options(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut population = first_population(&options, &mut rng);
+    let mut sigma = SIGMA;
+    println!(
+        "{} x {} games of {} bricks on {} threads",
+        options.population, options.games, options.pieces, options.threads
+    );
+    for generation in 0..options.generations {
+        let (ranked, next) = evolve(&options, &population, generation, sigma, &mut rng);
+        let (best, best_fitness) = ranked[0];
+        let mean = ranked.iter().map(|(_, f)| f.score).sum::<f64>() / ranked.len() as f64;
+        println!(
+            "generation {}: best score {:.0} lines {:.1} top outs {}/{}, mean score {:.0}",
+            generation + 1,
+            best_fitness.score,
+            best_fitness.lines,
+            best_fitness.top_outs,
+            options.games,
+            mean
+        );
+        let weights = Weights::from_values(best);
+        if let Err(e) = std::fs::write(&options.out, weights.to_string()) {
+            eprintln!("{}: {}", options.out, e);
+        }
+        population = next;
+        sigma *= SIGMA_DECAY;
+    }
+    if options.generations > 0 {
+        println!("weights written to {}", options.out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length(genome: &Genome) -> f64 {
+        genome.iter().map(|w| w * w).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn normalized_to_length_one() {
+        let genome = normalize([3.0, -4.0, 0.0, 0.0, 0.0, 0.0, 12.0]);
+        assert!((length(&genome) - 1.0).abs() < 1e-12);
+        assert_eq!(genome[0] / genome[1], -0.75);
+        assert_eq!(genome[6], 12.0 / 13.0);
+        assert_eq!(normalize([0.0; FEATURES.len()]), [0.0; FEATURES.len()]);
+        assert!((length(&normalize(WEIGHTS.values())) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn children_are_between_parents() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let a = mutate(normalize(WEIGHTS.values()), 1.0, &mut rng);
+            let b = mutate(a, 1.0, &mut rng);
+            let child = crossover(&a, &b, &mut rng);
+            for ((w, a), b) in child.iter().zip(a).zip(b) {
+                assert!(
+                    a.min(b) <= *w && *w <= a.max(b),
+                    "{} not in {} .. {}",
+                    w,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    fn test_options() -> Options {
+        Options {
+            mode: GameMode::Classic,
+            level: 0,
+            bot: 3,
+            generations: 1,
+            population: 4,
+            games: 2,
+            pieces: 30,
+            seed: 11,
+            from: *WEIGHTS,
+            out: String::new(),
+            threads: 3,
+        }
+    }
+
+    #[test]
+    fn seeded_generation_repeats() {
+        let options = test_options();
+        let run = || {
+            let mut rng = StdRng::seed_from_u64(options.seed);
+            let population = first_population(&options, &mut rng);
+            let (ranked, next) = evolve(&options, &population, 0, SIGMA, &mut rng);
+            let scores: Vec<_> = ranked.iter().map(|(_, fitness)| fitness.score).collect();
+            (population, scores, next)
+        };
+        let (population, scores, next) = run();
+        assert_eq!(population.len(), options.population);
+        assert_eq!(next.len(), options.population);
+        assert_eq!(run(), (population, scores, next));
+    }
+}
@@ -0,0 +1,631 @@
+use crate::bricks::{Board, Brick, Dot};
+use crate::consts::{BOARD_Y_VALID, BOT_WEIGHTS_FILE, BRICK_START_DOT};
+use crate::engine::{self, Engine, Phase};
+use crate::modes::{GameMode, HardDrop};
+use crate::movegen::{reachable, Move, Reachable};
+use crate::search::{Search, NODES};
+use crate::tbp::{External, Suggestion};
+use instant::{Duration, Instant};
+use lazy_static::lazy_static;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::str::FromStr;
+use std::{fmt, io};
+
+/// how well a computer player plays
+#[derive(Debug)]
+pub struct Difficulty {
+    pub name: &'static str,
+    /// bricks dropped per second at most
+    pub pps: f64,
+    /// bricks looked at for a placement, 2 to look at the next brick too
+    pub depth: u32,
+    /// chance in percent to take a worse placement
+    pub mistakes: u32,
+    /// boards kept in each layer of a beam search over `depth` bricks and hold,
+    /// 0 tries every placement of the first two bricks instead
+    pub beam: usize,
+}
+
+pub const DIFFICULTIES: [Difficulty; 5] = [
+    Difficulty {
+        name: "EASY",
+        pps: 0.7,
+        depth: 1,
+        mistakes: 25,
+        beam: 0,
+    },
+    Difficulty {
+        name: "NORMAL",
+        pps: 1.2,
+        depth: 1,
+        mistakes: 10,
+        beam: 0,
+    },
+    Difficulty {
+        name: "HARD",
+        pps: 2.0,
+        depth: 2,
+        mistakes: 3,
+        beam: 0,
+    },
+    Difficulty {
+        name: "EXPERT",
+        pps: 3.0,
+        depth: 2,
+        mistakes: 0,
+        beam: 0,
+    },
+    Difficulty {
+        name: "INSANE",
+        pps: 3.5,
+        depth: 6,
+        mistakes: 0,
+        beam: 48,
+    },
+];
+
+/// worse placements a mistake chooses from
+const MISTAKE_CHOICES: usize = 4;
+/// frames without a move to give up moving, e.g. when gravity got in the way, and drop
+const STUCK_FRAMES: u32 = 120;
+/// bricks looked at for a hint, the moving and the next brick
+const HINT_DEPTH: u32 = 2;
+/// time a blocking bot waits for a suggestion of an external bot to give up and choose itself
+const SUGGESTION_TIMEOUT: Duration = Duration::from_secs(5);
+/// sleep between polls of an external bot when blocking
+const EXTERNAL_POLL: Duration = Duration::from_millis(1);
+
+/// weight of each feature of a board, features are counted as
+/// [`Features`] and summed with these weights, higher is better
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    pub height: f64,
+    pub lines: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+}
+
+/// height, lines, holes and bumpiness of
+/// https://codemyroad.wordpress.com/2013/04/14/tetris-ai-the-near-perfect-player/
+/// with penalties of wells and transitions, which keep the stack flat and tops out less
+pub const DEFAULT_WEIGHTS: Weights = Weights {
+    height: -0.51,
+    lines: 0.76,
+    holes: -0.36,
+    bumpiness: -0.18,
+    wells: -0.1,
+    row_transitions: -0.1,
+    column_transitions: -0.2,
+};
+
+/// features of [`Weights`], names in weights files
+pub const FEATURES: [&str; 7] = [
+    "height",
+    "lines",
+    "holes",
+    "bumpiness",
+    "wells",
+    "row_transitions",
+    "column_transitions",
+];
+
+lazy_static! {
+    /// weights of computer players, trained ones of `BOT_WEIGHTS_FILE`
+    /// when it is there, e.g. written by src/bin/train.rs
+    pub static ref WEIGHTS: Weights = Weights::load(Path::new(BOT_WEIGHTS_FILE))
+        .unwrap_or(DEFAULT_WEIGHTS);
+}
+
+impl Weights {
+    /// weights in the order of [`FEATURES`]
+    pub fn values(&self) -> [f64; FEATURES.len()] {
+        [
+            self.height,
+            self.lines,
+            self.holes,
+            self.bumpiness,
+            self.wells,
+            self.row_transitions,
+            self.column_transitions,
+        ]
+    }
+
+    pub fn from_values(values: [f64; FEATURES.len()]) -> Self {
+        let [height, lines, holes, bumpiness, wells, row_transitions, column_transitions] = values;
+        Self {
+            height,
+            lines,
+            holes,
+            bumpiness,
+            wells,
+            row_transitions,
+            column_transitions,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        text.parse()
+    }
+}
+
+/// `feature: weight` lines
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in FEATURES.iter().zip(self.values()) {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// features left out keep their default weight
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = DEFAULT_WEIGHTS.values();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("expect `feature: weight`, found `{}`", line))?;
+            let (name, value) = (name.trim(), value.trim());
+            let i = FEATURES
+                .iter()
+                .position(|feature| *feature == name)
+                .ok_or_else(|| format!("unknown feature `{}`", name))?;
+            values[i] = value
+                .parse()
+                .map_err(|_| format!("bad weight `{}`", value))?;
+        }
+        Ok(Self::from_values(values))
+    }
+}
+
+/// how good a board is after a placement, higher is better
+pub trait Evaluator: Send + Sync {
+    /// `lines` cleaned by the placement
+    fn evaluate(&self, board: &Board, lines: u32) -> f64;
+
+    /// reward of lines cleaned on the way to a board looked at further ahead
+    fn lines(&self, _lines: u32) -> f64 {
+        0.0
+    }
+}
+
+/// handcrafted evaluation of [`Features`]
+impl Evaluator for Weights {
+    fn evaluate(&self, board: &Board, lines: u32) -> f64 {
+        Features::new(board, lines).score(self)
+    }
+
+    fn lines(&self, lines: u32) -> f64 {
+        self.lines * lines as f64
+    }
+}
+
+/// any other evaluation, e.g. a learned one
+impl<F: Fn(&Board, u32) -> f64 + Send + Sync> Evaluator for F {
+    fn evaluate(&self, board: &Board, lines: u32) -> f64 {
+        self(board, lines)
+    }
+}
+
+/// what the bot looks at on a board after a placement
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Features {
+    /// sum of column heights
+    pub height: f64,
+    /// lines cleaned by the placement
+    pub lines: f64,
+    pub holes: f64,
+    /// sum of height differences of neighbour columns
+    pub bumpiness: f64,
+    /// sum of depths of columns lower than both neighbours, walls are high
+    pub wells: f64,
+    /// filled and empty dots side by side in rows, walls are filled
+    pub row_transitions: f64,
+    /// filled and empty dots on top of each other in columns, floor is filled
+    pub column_transitions: f64,
+}
+
+impl Features {
+    pub fn new(board: &Board, lines: u32) -> Self {
+        let width = board.width();
+        let heights: Vec<_> = (0..width).map(|x| board.column_height(x)).collect();
+        let filled = |x: i8, y: i8| x < 0 || x >= width || y < 0 || board.occupied_dot(&Dot(x, y));
+        let top = board.height();
+        let wells = (0..width as usize)
+            .map(|x| {
+                let left = if x == 0 { i8::MAX } else { heights[x - 1] };
+                let right = heights.get(x + 1).copied().unwrap_or(i8::MAX);
+                (left.min(right) - heights[x]).max(0) as f64
+            })
+            .sum();
+        let row_transitions = (0..top)
+            .map(|y| {
+                (0..=width)
+                    .filter(|x| filled(x - 1, y) != filled(*x, y))
+                    .count() as f64
+            })
+            .sum();
+        let column_transitions = (0..width)
+            .map(|x| {
+                (0..=top)
+                    .filter(|y| filled(x, y - 1) != filled(x, *y))
+                    .count() as f64
+            })
+            .sum();
+        Self {
+            height: heights.iter().map(|h| *h as f64).sum(),
+            lines: lines as f64,
+            holes: board.holes() as f64,
+            bumpiness: heights.windows(2).map(|w| (w[0] - w[1]).abs() as f64).sum(),
+            wells,
+            row_transitions,
+            column_transitions,
+        }
+    }
+
+    pub fn score(&self, weights: &Weights) -> f64 {
+        weights.height * self.height
+            + weights.lines * self.lines
+            + weights.holes * self.holes
+            + weights.bumpiness * self.bumpiness
+            + weights.wells * self.wells
+            + weights.row_transitions * self.row_transitions
+            + weights.column_transitions * self.column_transitions
+    }
+}
+
+/// every placement `brick` can reach in `mode` at `level`, with the board after cleaning lines
+pub fn placements(
+    board: &Board,
+    brick: Brick,
+    mode: GameMode,
+    level: u32,
+) -> Vec<(Reachable, Board, u32)> {
+    reachable(board, brick, BRICK_START_DOT, mode, level)
+        .into_iter()
+        .filter_map(|placement| {
+            let mut after = board.clone();
+            after.occupy_brick(&placement.brick, &placement.pos);
+            let lines = after.clean_lines();
+            // above the valid rows is game over
+            (after.height() <= BOARD_Y_VALID).then_some((placement, after, lines))
+        })
+        .collect()
+}
+
+/// placements of `brick`, best first, scored with the bricks after it up to `depth`
+fn rank_placements(
+    board: &Board,
+    brick: Brick,
+    next: &[Brick],
+    depth: u32,
+    mode: GameMode,
+    level: u32,
+    evaluator: &dyn Evaluator,
+) -> Vec<(f64, Reachable)> {
+    let mut ranked: Vec<_> = placements(board, brick, mode, level)
+        .into_iter()
+        .map(|(placement, after, lines)| {
+            let score = match next.first() {
+                Some(next_brick) if depth > 1 => rank_placements(
+                    &after,
+                    *next_brick,
+                    &next[1..],
+                    depth - 1,
+                    mode,
+                    level,
+                    evaluator,
+                )
+                .first()
+                .map_or(f64::MIN, |(score, _)| *score + evaluator.lines(lines)),
+                _ => evaluator.evaluate(&after, lines),
+            };
+            (score, placement)
+        })
+        .collect();
+    // stable, so of equal placements the one with fewer moves stays first
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranked
+}
+
+/// placements of the moving brick and of the brick out of hold with whether to hold first,
+/// best first, scored with the bricks after it up to `depth`
+fn rank_choices(
+    engine: &Engine,
+    depth: u32,
+    evaluator: &dyn Evaluator,
+) -> Vec<(f64, Reachable, bool)> {
+    let next: Vec<_> = engine.next_brick.into_iter().collect();
+    let mut ranked: Vec<_> = rank_placements(
+        &engine.board,
+        engine.moving_brick,
+        &next,
+        depth,
+        engine.mode,
+        engine.stats.level,
+        evaluator,
+    )
+    .into_iter()
+    .map(|(score, placement)| (score, placement, false))
+    .collect();
+    // out of hold comes the held brick, or the next one when hold is empty
+    let held = match engine.hold_brick {
+        Some(brick) => Some((brick, next.clone())),
+        None => engine.next_brick.map(|brick| (brick, Vec::new())),
+    };
+    if let (true, Some((brick, next))) = (engine.can_hold(), held) {
+        ranked.extend(
+            rank_placements(
+                &engine.board,
+                brick,
+                &next,
+                depth,
+                engine.mode,
+                engine.stats.level,
+                evaluator,
+            )
+            .into_iter()
+            .map(|(score, placement)| (score, placement, true)),
+        );
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    }
+    ranked
+}
+
+/// placement the bot recommends for the moving brick, and what it does to the board
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub placement: Reachable,
+    /// hold first, the placement is of the brick out of hold
+    pub hold: bool,
+    /// features of the board before and after the placement
+    pub before: Features,
+    pub after: Features,
+}
+
+/// best placement of the moving brick by `evaluator`, looking at the next brick as EXPERT does
+pub fn hint(engine: &Engine, evaluator: &dyn Evaluator) -> Option<Hint> {
+    let (_, placement, hold) = rank_choices(engine, HINT_DEPTH, evaluator)
+        .into_iter()
+        .next()?;
+    let mut after = engine.board.clone();
+    after.occupy_brick(&placement.brick, &placement.pos);
+    let lines = after.clean_lines();
+    Some(Hint {
+        before: Features::new(&engine.board, 0),
+        after: Features::new(&after, lines),
+        placement,
+        hold,
+    })
+}
+
+/// computer player, giving keys to an engine frame by frame
+/// as the keyboard does, so it plays by the rules of any mode
+pub struct Bot {
+    pub difficulty: &'static Difficulty,
+    pub evaluator: Box<dyn Evaluator>,
+    /// moves left to where the moving brick goes, chosen when it spawns
+    moves: VecDeque<Move>,
+    /// hold first, moves are for the brick coming out of hold
+    hold: bool,
+    /// bricks spawned when moves were chosen
+    pieces: u32,
+    /// frame the moving brick spawned
+    spawned_at: u32,
+    /// frame the moving brick last got closer to the target
+    moved_at: u32,
+    /// frame keys were last given for
+    frame: u32,
+    input: engine::Input,
+    rng: StdRng,
+    /// placements suggested by an external bot instead of chosen here
+    external: Option<External>,
+    /// beam search of difficulties with `beam`
+    search: Option<Search>,
+    /// waiting for the external bot or the search to suggest a placement
+    waiting: bool,
+    /// wait for the suggestion in the frame the brick spawned, for games
+    /// stepped faster than the clock, where waiting frames would cost more bricks
+    pub blocking: bool,
+}
+
+impl Bot {
+    pub fn new(difficulty: &'static Difficulty, seed: u64) -> Self {
+        Self {
+            difficulty,
+            evaluator: Box::new(*WEIGHTS),
+            moves: VecDeque::new(),
+            hold: false,
+            pieces: 0,
+            spawned_at: 0,
+            moved_at: 0,
+            frame: 0,
+            input: engine::Input::default(),
+            rng: StdRng::seed_from_u64(seed),
+            external: None,
+            search: (difficulty.beam > 0).then(|| Search::new(difficulty.beam)),
+            waiting: false,
+            blocking: false,
+        }
+    }
+
+    /// play by suggestions of an external bot run by `command`, as fast as the best difficulty
+    pub fn external(command: &str, seed: u64) -> io::Result<Self> {
+        let mut bot = Self::new(&DIFFICULTIES[DIFFICULTIES.len() - 1], seed);
+        bot.external = Some(External::spawn(command)?);
+        Ok(bot)
+    }
+
+    /// keys to hold in the next frame.
+    /// A key is released every other frame, so each press is seen by the engine.
+    pub fn input(&mut self, engine: &Engine) -> engine::Input {
+        let frame = engine.stats.frames;
+        if frame == self.frame {
+            return self.input;
+        }
+        self.frame = frame;
+        if engine.phase != Phase::Falling {
+            self.input = engine::Input::default();
+            return self.input;
+        }
+        if engine.stats.pieces != self.pieces {
+            self.pieces = engine.stats.pieces;
+            self.spawned_at = frame;
+            self.moved_at = frame;
+            if let Some(external) = &mut self.external {
+                external.suggest();
+                self.waiting = true;
+            } else if let Some(search) = &mut self.search {
+                // a blocking bot doesn't play by how fast the computer is
+                search.nodes = self.blocking.then_some(NODES);
+                search.start(engine, self.difficulty.depth as usize);
+                self.waiting = true;
+            } else {
+                let (target, hold) = self.choose(engine);
+                self.aim(target, hold);
+            }
+        }
+        if self.waiting {
+            let asked = Instant::now();
+            let suggestion = loop {
+                let suggestion = match (&mut self.external, &mut self.search) {
+                    (Some(external), _) => external.poll(engine),
+                    (None, Some(search)) => search.poll(&*self.evaluator),
+                    (None, None) => Suggestion::Failed,
+                };
+                if !self.blocking || !matches!(suggestion, Suggestion::Waiting) {
+                    break suggestion;
+                }
+                if asked.elapsed() >= SUGGESTION_TIMEOUT {
+                    break Suggestion::Failed;
+                }
+                if self.external.is_some() {
+                    std::thread::sleep(EXTERNAL_POLL);
+                }
+            };
+            match suggestion {
+                Suggestion::Waiting => {
+                    self.input = engine::Input::default();
+                    return self.input;
+                }
+                Suggestion::Placement(target, hold) => self.aim(Some(target), hold),
+                Suggestion::Failed => {
+                    let (target, hold) = self.choose(engine);
+                    self.aim(target, hold);
+                }
+            }
+            self.waiting = false;
+            self.moved_at = frame;
+        }
+        // soft drop is held down, not pressed
+        let soft_drop = self.moves.front() == Some(&Move::SoftDrop);
+        if self.input != engine::Input::default() && !soft_drop {
+            self.input = engine::Input::default();
+            return self.input;
+        }
+        if self.hold && engine.can_hold() {
+            self.input.hold = true;
+            return self.input;
+        }
+        // bricks only lock by soft drop where hard drop doesn't lock them
+        let drop = |input: &mut engine::Input| match engine.mode.hard_drop() {
+            HardDrop::Firm => input.hard_drop = true,
+            HardDrop::Sonic | HardDrop::Disabled => input.soft_drop = true,
+        };
+        let since_spawn = frame - self.spawned_at;
+        if frame - self.moved_at > STUCK_FRAMES {
+            drop(&mut self.input);
+            return self.input;
+        }
+        match self.moves.front().copied() {
+            // no placement left, top out
+            None => drop(&mut self.input),
+            Some(Move::HardDrop) => {
+                if since_spawn as f64 >= engine.mode.fps() / self.difficulty.pps {
+                    drop(&mut self.input);
+                }
+            }
+            Some(Move::SoftDrop) => {
+                let below = engine.moving_pos.down();
+                if !engine.board.valid_brick(&engine.moving_brick, &below) {
+                    self.input = engine::Input::default();
+                    self.moves.pop_front();
+                } else if engine.mode.hard_drop() == HardDrop::Sonic {
+                    // soft drop locks a landed brick there, sonic drop doesn't
+                    self.input = Move::HardDrop.input();
+                } else {
+                    self.input = Move::SoftDrop.input();
+                }
+                self.moved_at = frame;
+            }
+            Some(mv) => {
+                self.input = mv.input();
+                self.moves.pop_front();
+                self.moved_at = frame;
+            }
+        }
+        self.input
+    }
+
+    fn aim(&mut self, target: Option<Reachable>, hold: bool) {
+        self.moves = target.map_or_else(VecDeque::new, |target| target.moves.into());
+        self.hold = hold;
+    }
+
+    /// best placement and whether to hold first, or a worse one when making a mistake
+    fn choose(&mut self, engine: &Engine) -> (Option<Reachable>, bool) {
+        let mut ranked = rank_choices(engine, self.difficulty.depth, &*self.evaluator);
+        // nowhere to go, top out
+        if ranked.is_empty() {
+            return (None, false);
+        }
+        let choice = if self.rng.gen_range(0..100) < self.difficulty.mistakes {
+            self.rng.gen_range(0..ranked.len().min(MISTAKE_CHOICES + 1))
+        } else {
+            0
+        };
+        let (_, placement, hold) = ranked.swap_remove(choice);
+        (Some(placement), hold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::BOARD_X;
+
+    /// rows up to the top with a covered hole each, no line can be cleaned
+    fn full_board() -> Board {
+        let mut board = Board::default();
+        for y in 0..BOARD_Y_VALID {
+            for x in (0..BOARD_X).filter(|x| *x != y % BOARD_X) {
+                board.occupy_dot(&Dot(x, y));
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn no_placement_on_full_board() {
+        let bricks = [Brick { ty: 6, rotation: 0 }; 2];
+        let engine = Engine::with_bricks(GameMode::Puzzle, full_board(), &bricks, false);
+        // EASY makes mistakes, which once rolled a choice out of no placements
+        for seed in 0..100 {
+            let mut bot = Bot::new(&DIFFICULTIES[0], seed);
+            assert!(bot.choose(&engine).0.is_none());
+        }
+    }
+}
@@ -1,6 +1,4 @@
-use crate::consts::{BOARD_X, BOARD_X_Y, BOARD_Y, BOARD_Y_VALID, BRICKS_TYPES, BRICK_VIEWS};
-use bevy::prelude::Resource;
-use rand::prelude::*;
+use crate::consts::{BOARD_X, BOARD_Y, BOARD_Y_VALID, BRICK_MASKS, BRICK_NAMES, BRICK_VIEWS};
 #[derive(Copy, Clone, Default, Debug)]
 pub struct Dot(pub i8, pub i8);
 
@@ -8,15 +6,6 @@ impl Dot {
     pub fn with_original_dot(&self, pos: &Dot) -> Self {
         Self(self.0 + pos.0, self.1 + pos.1)
     }
-    pub fn move_left(&mut self) {
-        self.0 -= 1;
-    }
-    pub fn move_right(&mut self) {
-        self.0 += 1;
-    }
-    pub fn move_down(&mut self) {
-        self.1 -= 1;
-    }
     pub fn left(&self) -> Self {
         Self(self.0 - 1, self.1)
     }
@@ -33,7 +22,7 @@ pub struct BrickView {
     pub dots: [Dot; 4],
 }
 
-#[derive(Copy, Clone, Resource)]
+#[derive(Copy, Clone, Debug)]
 pub struct Brick {
     pub ty: usize,
     pub rotation: usize,
@@ -46,9 +35,14 @@ impl From<Brick> for BrickView {
 }
 
 impl Brick {
-    pub fn rand() -> Self {
-        let ty = rand::thread_rng().gen_range(0..BRICKS_TYPES);
-        Self { ty, rotation: 0 }
+    pub fn from_name(name: char) -> Option<Self> {
+        let ty = BRICK_NAMES
+            .iter()
+            .position(|c| *c == name.to_ascii_uppercase())?;
+        Some(Self { ty, rotation: 0 })
+    }
+    pub fn name(&self) -> char {
+        BRICK_NAMES[self.ty]
     }
     pub fn rotate(&self) -> Self {
         Self {
@@ -56,80 +50,189 @@ impl Brick {
             rotation: (self.rotation + 1) % BRICK_VIEWS[self.ty].len(),
         }
     }
+    pub fn rotate_back(&self) -> Self {
+        let views = BRICK_VIEWS[self.ty].len();
+        Self {
+            ty: self.ty,
+            rotation: (self.rotation + views - 1) % views,
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct Board(Vec<bool>);
+/// dots of a brick view as bits of rows, bit `x` of a row is the dot `left + x`
+#[derive(Copy, Clone, Debug)]
+pub struct BrickMask {
+    /// rows from `bottom` up
+    rows: [u32; 4],
+    /// lowest, leftmost and rightmost dots of the view
+    bottom: i8,
+    left: i8,
+    right: i8,
+}
+
+impl From<&BrickView> for BrickMask {
+    fn from(view: &BrickView) -> Self {
+        let left = view.dots.iter().map(|dot| dot.0).min().unwrap();
+        let right = view.dots.iter().map(|dot| dot.0).max().unwrap();
+        let bottom = view.dots.iter().map(|dot| dot.1).min().unwrap();
+        let mut rows = [0; 4];
+        for dot in view.dots {
+            rows[(dot.1 - bottom) as usize] |= 1 << (dot.0 - left);
+        }
+        Self {
+            rows,
+            bottom,
+            left,
+            right,
+        }
+    }
+}
+
+/// `bits` moved to start at column `x`, columns left of the board are dropped
+fn shift(bits: u32, x: i8) -> u32 {
+    if x >= 0 {
+        bits.checked_shl(x as u32).unwrap_or(0)
+    } else {
+        bits.checked_shr(-(x as i32) as u32).unwrap_or(0)
+    }
+}
+
+/// start of [`Board::fnv`] hashes
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x100_0000_01b3;
+
+/// dots of the board, `BOARD_Y` lines from the bottom, bit `x` of a line is column `x`
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Board {
+    width: i8,
+    rows: [u32; BOARD_Y as usize],
+}
 
 impl Default for Board {
     fn default() -> Self {
-        Self(vec![false; BOARD_X_Y])
+        Self::new(BOARD_X)
     }
 }
 impl Board {
-    fn index(dot: &Dot) -> usize {
-        dot.0 as usize + dot.1 as usize * BOARD_X as usize
+    /// empty board of `width` columns, e.g. wider for co-op
+    pub fn new(width: i8) -> Self {
+        assert!((0..=u32::BITS as i8).contains(&width));
+        Self {
+            width,
+            rows: [0; BOARD_Y as usize],
+        }
+    }
+    pub fn width(&self) -> i8 {
+        self.width
+    }
+    /// FNV-1a of the width and lines as little-endian `u32`, going on from `hash`.
+    /// Unlike the derived `Hash` it is the same on every platform, so peers can compare it.
+    pub fn fnv(&self, hash: u64) -> u64 {
+        let width = (self.width as u32).to_le_bytes();
+        let rows = self.rows.iter().flat_map(|row| row.to_le_bytes());
+        width.into_iter().chain(rows).fold(hash, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
+    /// bits of all columns of a line
+    fn full(&self) -> u32 {
+        shift(1, self.width).wrapping_sub(1)
+    }
+    fn row(&self, y: i8) -> u32 {
+        if (0..BOARD_Y).contains(&y) {
+            self.rows[y as usize]
+        } else {
+            0
+        }
+    }
+    /// line `y` and bit of column `x`, `None` out of the board
+    fn bit(&self, dot: &Dot) -> Option<(usize, u32)> {
+        (0 <= dot.0 && dot.0 < self.width && 0 <= dot.1 && dot.1 < BOARD_Y)
+            .then(|| (dot.1 as usize, 1 << dot.0))
     }
     pub fn occupy_dot(&mut self, dot: &Dot) -> &mut Self {
-        let i = Self::index(dot);
-        if i < BOARD_X_Y {
-            self.0[i] = true
+        if let Some((y, bit)) = self.bit(dot) {
+            self.rows[y] |= bit;
         }
         self
     }
-    pub fn occupy_brick_view(&mut self, brick: &BrickView, pos: &Dot) {
-        for i in 0..4 {
-            self.occupy_dot(&brick.dots[i].with_original_dot(pos));
+    pub fn free_dot(&mut self, dot: &Dot) -> &mut Self {
+        if let Some((y, bit)) = self.bit(dot) {
+            self.rows[y] &= !bit;
+        }
+        self
+    }
+    fn occupy_mask(&mut self, mask: &BrickMask, pos: &Dot) {
+        let full = self.full();
+        for (i, bits) in mask.rows.iter().enumerate() {
+            let y = pos.1 + mask.bottom + i as i8;
+            if (0..BOARD_Y).contains(&y) {
+                self.rows[y as usize] |= shift(*bits, pos.0 + mask.left) & full;
+            }
         }
     }
-
     pub fn occupy_brick(&mut self, brick: &Brick, pos: &Dot) {
-        let brick = BrickView::from(*brick);
-        self.occupy_brick_view(&brick, pos)
+        self.occupy_mask(&BRICK_MASKS[brick.ty][brick.rotation], pos)
     }
 
     pub fn occupied_dot(&self, dot: &Dot) -> bool {
-        let i = Self::index(dot);
-        if i < BOARD_X_Y {
-            self.0[i]
-        } else {
-            false
-        }
+        self.bit(dot)
+            .is_some_and(|(y, bit)| self.rows[y] & bit != 0)
     }
-    pub fn conflict_brick(&self, brick: &BrickView, pos: &Dot) -> bool {
-        self.occupied_dot(&brick.dots[0].with_original_dot(pos))
-            || self.occupied_dot(&brick.dots[1].with_original_dot(pos))
-            || self.occupied_dot(&brick.dots[2].with_original_dot(pos))
-            || self.occupied_dot(&brick.dots[3].with_original_dot(pos))
+    fn conflict_mask(&self, mask: &BrickMask, pos: &Dot) -> bool {
+        let x = pos.0 + mask.left;
+        let y = pos.1 + mask.bottom;
+        mask.rows
+            .iter()
+            .enumerate()
+            .any(|(i, bits)| self.row(y + i as i8) & shift(*bits, x) != 0)
     }
-    fn dot_in_board(dot: &Dot) -> bool {
+    pub fn dot_in_board(&self, dot: &Dot) -> bool {
         //0 <= dot.0 && dot.0 < BOARD_X && 0 <= dot.1 && dot.1 < BOARD_Y
         //BUG: should we compare Y ?
-        0 <= dot.0 && dot.0 < BOARD_X && 0 <= dot.1
+        0 <= dot.0 && dot.0 < self.width && 0 <= dot.1
     }
-    fn brick_in_board(brick: &BrickView, pos: &Dot) -> bool {
-        Self::dot_in_board(&brick.dots[0].with_original_dot(pos))
-            && Self::dot_in_board(&brick.dots[1].with_original_dot(pos))
-            && Self::dot_in_board(&brick.dots[2].with_original_dot(pos))
-            && Self::dot_in_board(&brick.dots[3].with_original_dot(pos))
+    fn mask_in_board(&self, mask: &BrickMask, pos: &Dot) -> bool {
+        0 <= pos.0 + mask.left && pos.0 + mask.right < self.width && 0 <= pos.1 + mask.bottom
     }
-    pub fn valid_brick_view(&self, brick: &BrickView, pos: &Dot) -> bool {
-        Self::brick_in_board(brick, pos) && !self.conflict_brick(brick, pos)
+    fn valid_mask(&self, mask: &BrickMask, pos: &Dot) -> bool {
+        self.mask_in_board(mask, pos) && !self.conflict_mask(mask, pos)
     }
     pub fn valid_brick(&self, brick: &Brick, pos: &Dot) -> bool {
-        self.valid_brick_view(&(*brick).into(), pos)
+        self.valid_mask(&BRICK_MASKS[brick.ty][brick.rotation], pos)
     }
-    pub fn clear(&mut self) {
-        for i in 0..BOARD_X_Y {
-            self.0[i] = false
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+    /// lines from bottom to the highest occupied dot of column `x`
+    pub fn column_height(&self, x: i8) -> i8 {
+        let bit = shift(1, x) & self.full();
+        self.rows
+            .iter()
+            .rposition(|row| row & bit != 0)
+            .map_or(0, |y| y as i8 + 1)
+    }
+    /// lines from bottom to the highest occupied dot
+    pub fn height(&self) -> i8 {
+        self.rows
+            .iter()
+            .rposition(|row| *row != 0)
+            .map_or(0, |y| y as i8 + 1)
+    }
+    /// empty dots below the highest occupied dot of their column
+    pub fn holes(&self) -> u32 {
+        let mut covered = 0;
+        let mut holes = 0;
+        for row in self.rows.iter().rev() {
+            holes += (covered & !row).count_ones();
+            covered |= row;
         }
+        holes
     }
     pub fn can_clean_line(&self, y: i8) -> bool {
         assert!(0 <= y);
         assert!(y < BOARD_Y_VALID);
-        self.0[Self::index(&Dot(0, y))..Self::index(&Dot(0, y + 1))]
-            .iter()
-            .all(|x| *x)
+        self.rows[y as usize] == self.full()
     }
     pub fn get_clean_lines(&self) -> Vec<i8> {
         let mut vec = Vec::with_capacity(4);
@@ -141,32 +244,179 @@ impl Board {
         vec
     }
     pub fn clean_lines(&mut self) -> u32 {
-        let deleted_lines = self.get_clean_lines();
-        let result = deleted_lines.len();
-        for line in deleted_lines {
-            self.clean_line(line);
+        let full = self.full();
+        let mut kept = 0;
+        for y in 0..BOARD_Y as usize {
+            if y < BOARD_Y_VALID as usize && self.rows[y] == full {
+                continue;
+            }
+            self.rows[kept] = self.rows[y];
+            kept += 1;
         }
-        result as u32
+        self.rows[kept..].fill(0);
+        (BOARD_Y as usize - kept) as u32
+    }
+
+    /// push every line up by one, and fill the bottom line except `hole`
+    pub fn push_garbage(&mut self, hole: i8) {
+        self.rows.copy_within(..BOARD_Y as usize - 1, 1);
+        self.rows[0] = self.full() & !shift(1, hole);
     }
 
     pub fn clean_line(&mut self, y: i8) {
         assert!(0 <= y);
         assert!(y < BOARD_Y_VALID);
-
-        let dst_below = Self::index(&Dot(0, y));
-        let src_below = Self::index(&Dot(0, y + 1));
-        let src_high = Self::index(&Dot(0, BOARD_Y));
-
-        //step 1.copy from tail
-        self.0.copy_within(src_below..src_high, dst_below);
-        //step 2.set last line as false
-        self.0[Self::index(&Dot(0, BOARD_Y - 1))..Self::index(&Dot(0, BOARD_Y))]
-            .iter_mut()
-            .for_each(|x| *x = false);
+        self.rows.copy_within(y as usize + 1.., y as usize);
+        self.rows[BOARD_Y as usize - 1] = 0;
     }
     // pub fn game_over(&self) -> bool {
-    //     self.0[Self::index(&Dot(0, BOARD_Y_VALIDE))..]
+    //     self.dots[self.index(&Dot(0, BOARD_Y_VALIDE))..]
     //         .iter()
     //         .any(|x| *x)
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    /// board as it was before bitmasks, a dot by dot reference
+    #[derive(Clone)]
+    struct Dots {
+        width: i8,
+        dots: Vec<bool>,
+    }
+
+    impl Dots {
+        fn new(board: &Board) -> Self {
+            let width = board.width();
+            let dots = (0..BOARD_Y)
+                .flat_map(|y| (0..width).map(move |x| Dot(x, y)))
+                .map(|dot| board.occupied_dot(&dot))
+                .collect();
+            Self { width, dots }
+        }
+        fn index(&self, dot: &Dot) -> usize {
+            dot.0 as usize + dot.1 as usize * self.width as usize
+        }
+        fn occupied_dot(&self, dot: &Dot) -> bool {
+            let i = self.index(dot);
+            i < self.dots.len() && self.dots[i]
+        }
+        fn valid_brick(&self, brick: &Brick, pos: &Dot) -> bool {
+            BrickView::from(*brick).dots.iter().all(|dot| {
+                let dot = dot.with_original_dot(pos);
+                0 <= dot.0 && dot.0 < self.width && 0 <= dot.1 && !self.occupied_dot(&dot)
+            })
+        }
+        fn column_height(&self, x: i8) -> i8 {
+            (0..BOARD_Y)
+                .rev()
+                .find(|y| (0..self.width).contains(&x) && self.occupied_dot(&Dot(x, *y)))
+                .map_or(0, |y| y + 1)
+        }
+        fn holes(&self) -> u32 {
+            (0..self.width)
+                .map(|x| {
+                    (0..self.column_height(x))
+                        .filter(|y| !self.occupied_dot(&Dot(x, *y)))
+                        .count() as u32
+                })
+                .sum()
+        }
+        fn clean_lines(&mut self) -> u32 {
+            let line = self.width as usize;
+            let full: Vec<usize> = (0..BOARD_Y_VALID as usize)
+                .rev()
+                .filter(|y| self.dots[y * line..(y + 1) * line].iter().all(|dot| *dot))
+                .collect();
+            for y in &full {
+                self.dots.copy_within((y + 1) * line.., y * line);
+                let size = self.dots.len();
+                self.dots[size - line..].fill(false);
+            }
+            full.len() as u32
+        }
+        fn push_garbage(&mut self, hole: i8) {
+            let line = self.width as usize;
+            let size = self.dots.len();
+            self.dots.copy_within(0..size - line, line);
+            for x in 0..self.width {
+                self.dots[x as usize] = x != hole;
+            }
+        }
+    }
+
+    /// stacks of random height and density, some lines full
+    fn random_board(rng: &mut StdRng, width: i8) -> Board {
+        let mut board = Board::new(width);
+        let height = rng.gen_range(0..BOARD_Y_VALID);
+        let density = rng.gen_range(0.3..1.0);
+        for y in 0..height {
+            let full = rng.gen_bool(0.2);
+            for x in 0..width {
+                if full || rng.gen_bool(density) {
+                    board.occupy_dot(&Dot(x, y));
+                }
+            }
+        }
+        board
+    }
+
+    fn same(board: &Board, dots: &Dots) -> bool {
+        Dots::new(board).dots == dots.dots
+    }
+
+    #[test]
+    fn masks_agree_with_dots() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for round in 0..300 {
+            let width = if round % 3 == 0 { 18 } else { BOARD_X };
+            let board = random_board(&mut rng, width);
+            let dots = Dots::new(&board);
+            for (ty, views) in BRICK_VIEWS.iter().enumerate() {
+                for rotation in 0..views.len() {
+                    let brick = Brick { ty, rotation };
+                    for x in -4..width + 1 {
+                        for y in -4..BOARD_Y + 1 {
+                            let pos = Dot(x, y);
+                            assert_eq!(
+                                board.valid_brick(&brick, &pos),
+                                dots.valid_brick(&brick, &pos)
+                            );
+                        }
+                    }
+                }
+            }
+            for x in -1..=width {
+                assert_eq!(board.column_height(x), dots.column_height(x));
+            }
+            assert_eq!(board.holes(), dots.holes());
+
+            let (mut cleaned, mut dots_cleaned) = (board.clone(), dots.clone());
+            assert_eq!(cleaned.clean_lines(), dots_cleaned.clean_lines());
+            assert!(same(&cleaned, &dots_cleaned));
+
+            let hole = rng.gen_range(0..width);
+            let (mut pushed, mut dots_pushed) = (board.clone(), dots.clone());
+            pushed.push_garbage(hole);
+            dots_pushed.push_garbage(hole);
+            assert!(same(&pushed, &dots_pushed));
+        }
+    }
+
+    #[test]
+    fn fnv_is_fixed() {
+        let mut board = Board::default();
+        assert_eq!(board.fnv(FNV_OFFSET), 0x0b18_9e12_fabb_69af);
+        board.occupy_dot(&Dot(0, 0));
+        board.occupy_dot(&Dot(9, 0));
+        board.occupy_dot(&Dot(4, 1));
+        assert_eq!(board.fnv(FNV_OFFSET), 0xdb79_2be4_d2c4_c238);
+        assert_ne!(
+            Board::new(9).fnv(FNV_OFFSET),
+            Board::new(10).fnv(FNV_OFFSET)
+        );
+    }
+}
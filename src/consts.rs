@@ -1,49 +1,90 @@
-use crate::bricks::{BrickView, Dot};
-use crate::Color;
+use crate::bricks::{BrickMask, BrickView, Dot};
+#[cfg(feature = "bevy")]
+use bevy::prelude::Color;
 use lazy_static::*;
 
-pub(crate) const BACKGROUND: Color = Color::rgb(158.0 / 255.0, 173.0 / 255.0, 135.0 / 255.0);
-pub(crate) const WINDOWS_WIDTH: f32 = 360.0;
-pub(crate) const WINDOWS_HEIGHT: f32 = 443.0;
+#[cfg(feature = "bevy")]
+pub const BACKGROUND: Color = Color::rgb(158.0 / 255.0, 173.0 / 255.0, 135.0 / 255.0);
+pub const WINDOWS_WIDTH: f32 = 360.0;
+pub const WINDOWS_HEIGHT: f32 = 443.0;
 
-pub(crate) const TEXT_SCORE_X: f32 = 248.0;
-pub(crate) const TEXT_SCORE_Y: f32 = 48.0;
+pub const TEXT_SCORE_X: f32 = 248.0;
+pub const TEXT_SCORE_Y: f32 = 48.0;
 
-pub(crate) const TEXT_LINES_X: f32 = 248.0;
-pub(crate) const TEXT_LINES_Y: f32 = 126.0;
+pub const TEXT_LINES_X: f32 = 248.0;
+pub const TEXT_LINES_Y: f32 = 126.0;
 
-pub(crate) const TEXT_LEVEL_X: f32 = 248.0;
-pub(crate) const TEXT_LEVEL_Y: f32 = 202.0;
+pub const TEXT_LEVEL_X: f32 = 248.0;
+pub const TEXT_LEVEL_Y: f32 = 202.0;
 
-pub(crate) const TEXT_GAME_X: f32 = 50.0;
-pub(crate) const TEXT_GAME_Y: f32 = 118.0;
+pub const TEXT_INFO_X: f32 = 248.0;
+pub const TEXT_INFO_Y: f32 = 370.0;
 
-pub(crate) const BOARD_X: i8 = 10;
-pub(crate) const BOARD_Y: i8 = 23; // board is 10x20
-pub(crate) const BOARD_X_Y: usize = 230; // we create 230 for more space for rotate brick.
+pub const TEXT_GAME_X: f32 = 50.0;
+pub const TEXT_GAME_Y: f32 = 118.0;
 
-pub(crate) const BOARD_Y_VALID: i8 = 20; // checking for game over
+pub const BOARD_X: i8 = 10;
+pub const BOARD_Y: i8 = 23; // board is 10x20
 
-pub(crate) const BOARD_LEFT_PX: f32 = 13.0;
-pub(crate) const BOARD_BOTTOM_PX: f32 = 13.0;
-pub(crate) const DOT_WIDTH_PX: f32 = 21.0;
+pub const BOARD_Y_VALID: i8 = 20; // checking for game over
 
-pub(crate) const NEXT_BRICK_LEFT_PX: f32 = 263.0;
-pub(crate) const NEXT_BRICK_BOTTOM_PX: f32 = 100.0;
+pub const BOARD_LEFT_PX: f32 = 13.0;
+pub const BOARD_BOTTOM_PX: f32 = 13.0;
+pub const DOT_WIDTH_PX: f32 = 21.0;
 
-pub(crate) const BRICK_START_DOT: Dot = Dot(3, 18);
+/// garbage meter between board and labels
+pub const METER_LEFT_PX: f32 = 229.0;
+pub const METER_WIDTH_PX: f32 = 7.0;
+/// garbage still in delay
+#[cfg(feature = "bevy")]
+pub const METER_WAITING: Color = Color::rgb(100.0 / 255.0, 110.0 / 255.0, 85.0 / 255.0);
 
-pub(crate) const BRICKS_TYPES: usize = 7;
+pub const NEXT_BRICK_LEFT_PX: f32 = 263.0;
+pub const NEXT_BRICK_BOTTOM_PX: f32 = 100.0;
 
-pub(crate) const SCORE_PER_DROP: u32 = 10;
+pub const BRICK_START_DOT: Dot = Dot(3, 18);
+
+/// co-op board is wider, each player has a half to enter
+pub const COOP_BOARD_X: i8 = 18;
+pub const COOP_START_DOTS: [Dot; 2] = [Dot(2, 18), Dot(11, 18)];
+/// next brick of player 2 is shown lower than the one of player 1
+pub const COOP_NEXT_GAP_PX: f32 = 70.0;
+/// black line around the co-op board
+pub const COOP_FRAME_PX: f32 = 4.0;
+/// empty dot drawn on the co-op board, as in screen.png
+#[cfg(feature = "bevy")]
+pub const EMPTY_DOT: Color = Color::rgb(135.0 / 255.0, 150.0 / 255.0, 115.0 / 255.0);
+
+pub const BRICKS_TYPES: usize = 7;
+/// brick letters, same order as BRICK_VIEWS
+pub const BRICK_NAMES: [char; BRICKS_TYPES] = ['O', 'I', 'J', 'L', 'S', 'Z', 'T'];
+
+pub const PUZZLES_DIR: &str = "assets/puzzles";
+/// folder of tutorial lessons
+pub const TUTORIAL_DIR: &str = "assets/tutorial";
+/// file names of solved puzzles, one per line
+pub const PUZZLES_PROGRESS_FILE: &str = "puzzles_solved.txt";
+/// weights of computer players, written by the trainer (src/bin/train.rs)
+pub const BOT_WEIGHTS_FILE: &str = "bot_weights.txt";
+
+/// online versus address without `--addr`
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+/// server address without `--server`, port of protocol::SERVER_PORT
+pub const DEFAULT_SERVER: &str = "127.0.0.1:7878";
+
+pub const SCORE_PER_DROP: u32 = 10;
 
 // pub(crate) const STRING_GAME_START: &str = "PRESS SPACE";
 // pub(crate) const STRING_GAME_PLAYING: &str = "                         ";
-pub(crate) const STRING_GAME_OVER: &str = " GAME OVER \n\nPRESS SPACE";
+pub const STRING_GAME_OVER: &str = " GAME OVER \n\nPRESS SPACE";
+pub const STRING_GAME_COMPLETED: &str = " CONGRATULATIONS \n\nPRESS SPACE";
+pub const STRING_SELECT_MODE: &str = "SELECT MODE";
+pub const STRING_PUZZLE_SOLVED: &str = " SOLVED \n\nPRESS SPACE";
+pub const STRING_PUZZLE_FAILED: &str = " FAILED \n\nPRESS SPACE";
 
 //delay = 725 * .85 ^ level + level (ms)
-pub(crate) const TIMER_FALLING_SECS: f32 = 0.725;
-pub(crate) const TIMER_KEY_SECS: f32 = 0.100;
+pub const TIMER_FALLING_SECS: f32 = 0.725;
+pub const TIMER_KEY_FRAMES: u32 = 6; // 100ms at 60 fps
 
 lazy_static! {
     pub static ref BRICK_VIEWS: Vec<Vec<BrickView>> = vec![
@@ -86,4 +127,9 @@ lazy_static! {
             BrickView{dots:[Dot(1, 0), Dot(1, 1), Dot(1, 2), Dot(2, 1)]},
         ],
     ];
+    /// BRICK_VIEWS as bits of rows, for collisions of a whole line at once
+    pub static ref BRICK_MASKS: Vec<Vec<BrickMask>> = BRICK_VIEWS
+        .iter()
+        .map(|views| views.iter().map(BrickMask::from).collect())
+        .collect();
 }
@@ -0,0 +1,467 @@
+use crate::bricks::Board;
+use crate::consts::*;
+use crate::engine::{self, Engine, Event, Outcome, Phase};
+use crate::modes::{GameMode, Stats};
+use crate::player::{Owner, PlayerText};
+use crate::versus::VERSUS_KEYS;
+use crate::{
+    init_text, spawn_board, spawn_brick_board, spawn_brick_next, Background, BoardBundle,
+    BrickBoardBundle, BrickNextBundle, GameState,
+};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::window::PrimaryWindow;
+
+const PLAYERS: usize = 2;
+
+/// rules of co-op: two players on one wide board, each with a falling brick,
+/// sharing line clears and score.
+///
+/// Each player is an [`Engine`] with bricks of its own. The board is shared, it is swapped
+/// into the engine of a player while it steps, with the falling brick of the other player
+/// as its obstacle. Score, lines, pieces, level and frames are shared the same way,
+/// counters of the falling brick such as soft drop stay with each engine.
+pub struct Coop {
+    pub mode: GameMode,
+    pub board: Board,
+    pub engines: [Engine; PLAYERS],
+    pub stats: Stats,
+    /// events with the index of the player
+    events: Vec<(usize, Event)>,
+}
+
+impl Coop {
+    pub fn new(seed: u64) -> Self {
+        let mode = GameMode::Coop;
+        let engine = |i: usize| {
+            let mut engine = Engine::new(mode, 0, seed.wrapping_add(i as u64));
+            engine.board = Board::new(COOP_BOARD_X);
+            engine.start = COOP_START_DOTS[i];
+            engine.moving_pos = engine.start;
+            engine
+        };
+        Self {
+            mode,
+            board: Board::new(COOP_BOARD_X),
+            engines: [engine(0), engine(1)],
+            stats: Stats::new(0),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<(usize, Event)> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.engines.iter().any(|engine| engine.is_over())
+    }
+
+    /// run one frame with keys of each player
+    pub fn step(&mut self, inputs: &[engine::Input; PLAYERS]) {
+        if self.is_over() {
+            return;
+        }
+        let frames = self.stats.frames;
+        // who moves first takes turns, so neither player always wins a spot both want
+        let first = (frames as usize + 1) % PLAYERS;
+        for i in [first, 1 - first] {
+            // both players step in the same frame
+            self.stats.frames = frames;
+            self.step_engine(i, &inputs[i]);
+            if self.is_over() {
+                // the other player tops out too
+                self.engines[1 - i].end(Outcome::TopOut);
+                break;
+            }
+        }
+        self.stats.frames = frames + 1;
+    }
+
+    fn step_engine(&mut self, i: usize, input: &engine::Input) {
+        let other = &self.engines[1 - i];
+        let obstacle =
+            (other.phase == Phase::Falling).then_some((other.moving_brick, other.moving_pos));
+        let engine = &mut self.engines[i];
+        engine.obstacle = obstacle;
+        std::mem::swap(&mut engine.board, &mut self.board);
+        share(&self.stats, &mut engine.stats);
+        engine.step(input);
+        share(&engine.stats, &mut self.stats);
+        std::mem::swap(&mut engine.board, &mut self.board);
+        let events = engine.take_events();
+        let cleared = events
+            .iter()
+            .any(|event| matches!(event, Event::Locked { lines, .. } if *lines > 0));
+        self.events
+            .extend(events.into_iter().map(|event| (i, event)));
+        if cleared {
+            self.lift(1 - i);
+        }
+    }
+
+    /// lines above a clear fall down, and may fall into the brick of the other player.
+    /// It is pushed up out of them.
+    fn lift(&mut self, i: usize) {
+        let engine = &mut self.engines[i];
+        if engine.phase != Phase::Falling {
+            return;
+        }
+        while !self
+            .board
+            .valid_brick(&engine.moving_brick, &engine.moving_pos)
+            && engine.moving_pos.1 < BOARD_Y
+        {
+            engine.moving_pos.1 += 1;
+            self.events.push((i, Event::Moved));
+        }
+    }
+}
+
+/// copy the stats both players share
+fn share(from: &Stats, to: &mut Stats) {
+    to.score = from.score;
+    to.lines = from.lines;
+    to.pieces = from.pieces;
+    to.level = from.level;
+    to.frames = from.frames;
+}
+
+/// co-op game in play, the entity is the render root as [`crate::player::Player`]
+#[derive(Component)]
+pub struct CoopGame {
+    pub coop: Coop,
+    /// seconds not yet run as frames
+    frame_time: f64,
+    freeze: bool,
+    /// bricks need to be redrawn
+    moved: bool,
+}
+
+/// pixels the wide board takes more than a board of [`BOARD_X`]
+fn extra_width() -> f32 {
+    (COOP_BOARD_X - BOARD_X) as f32 * DOT_WIDTH_PX
+}
+
+/// set window width, and move the menu background to the left of it
+fn set_layout(
+    width: f32,
+    window: &mut Query<&mut Window, With<PrimaryWindow>>,
+    background: &mut Query<&mut Transform, With<Background>>,
+) {
+    if let Ok(mut window) = window.get_single_mut() {
+        window.resolution.set(width, WINDOWS_HEIGHT);
+    }
+    for mut transform in background {
+        transform.translation.x = (WINDOWS_WIDTH - width) / 2.0;
+    }
+}
+
+pub fn coop_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut background: Query<&mut Transform, With<Background>>,
+) {
+    let extra = extra_width();
+    let width = WINDOWS_WIDTH + extra;
+    set_layout(width, &mut window, &mut background);
+    let root = commands
+        .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+            -width / 2.0,
+            -WINDOWS_HEIGHT / 2.0,
+            0.0,
+        )))
+        .insert(CoopGame {
+            coop: Coop::new(rand::random()),
+            frame_time: 0.0,
+            freeze: true,
+            moved: false,
+        })
+        .id();
+    commands.entity(root).with_children(|parent| {
+        // labels of the screen image beside the wide board
+        parent.spawn(SpriteBundle {
+            texture: asset_server.load("screen.png"),
+            transform: Transform::from_xyz(extra + WINDOWS_WIDTH / 2.0, WINDOWS_HEIGHT / 2.0, 0.0),
+            ..default()
+        });
+        spawn_wide_board(parent);
+    });
+    for (text, x, y) in [
+        (PlayerText::Score, TEXT_SCORE_X, TEXT_SCORE_Y),
+        (PlayerText::Lines, TEXT_LINES_X, TEXT_LINES_Y),
+        (PlayerText::Level, TEXT_LEVEL_X, TEXT_LEVEL_Y),
+    ] {
+        commands
+            .spawn(init_text("", x + extra, y, &asset_server))
+            .insert((text, Owner(root)));
+    }
+}
+
+/// frame and empty dots of the wide board, as the screen image draws them
+fn spawn_wide_board(parent: &mut ChildBuilder) {
+    let board_width = COOP_BOARD_X as f32 * DOT_WIDTH_PX;
+    let board_height = BOARD_Y_VALID as f32 * DOT_WIDTH_PX;
+    // board with `margin` around it
+    let rect = |color, margin: f32, z| SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::new(board_width, board_height) + 2.0 * margin),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        },
+        transform: Transform::from_xyz(BOARD_LEFT_PX - margin, BOARD_BOTTOM_PX - margin, z),
+        ..default()
+    };
+    parent.spawn(rect(Color::BLACK, COOP_FRAME_PX, 0.01));
+    parent.spawn(rect(BACKGROUND, COOP_FRAME_PX / 2.0, 0.02));
+    for x in 0..COOP_BOARD_X {
+        for y in 0..BOARD_Y_VALID {
+            let center = Vec2::new(
+                BOARD_LEFT_PX + (x as f32 + 0.5) * DOT_WIDTH_PX,
+                BOARD_BOTTOM_PX + (y as f32 + 0.5) * DOT_WIDTH_PX,
+            );
+            for (width, color, z) in [
+                (20.0, EMPTY_DOT, 0.03),
+                (16.0, BACKGROUND, 0.04),
+                (12.0, EMPTY_DOT, 0.05),
+            ] {
+                parent.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(width)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(center.extend(z)),
+                    ..default()
+                });
+            }
+        }
+    }
+}
+
+/// step by time with keys of both players
+pub fn coop_step_system(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut games: Query<&mut CoopGame>,
+) {
+    for mut game in &mut games {
+        let inputs = [0, 1].map(|i| VERSUS_KEYS[i].input(&keyboard_input));
+        let frame_secs = 1.0 / game.coop.mode.fps();
+        // don't catch up too many frames, e.g. after window was dragged
+        game.frame_time = (game.frame_time + time.delta_seconds_f64()).min(0.25);
+        while game.frame_time >= frame_secs {
+            game.frame_time -= frame_secs;
+            game.coop.step(&inputs);
+            for (_, event) in game.coop.take_events() {
+                match event {
+                    Event::BoardChanged => game.freeze = true,
+                    Event::Spawned | Event::Moved | Event::Locked { .. } | Event::Over => {
+                        game.moved = true
+                    }
+                    Event::Held | Event::Attack(_) => {}
+                }
+            }
+        }
+    }
+}
+
+/// redraw the board, and both bricks when one of them changed
+#[allow(clippy::type_complexity)]
+pub fn coop_draw_system(
+    mut commands: Commands,
+    mut games: Query<(Entity, &mut CoopGame)>,
+    drawn: Query<
+        (Entity, &Parent, Option<&BoardBundle>),
+        Or<(
+            With<BoardBundle>,
+            With<BrickBoardBundle>,
+            With<BrickNextBundle>,
+        )>,
+    >,
+) {
+    for (root, mut game) in &mut games {
+        let (freeze, moved) = (game.freeze, game.moved);
+        for (entity, parent, board) in &drawn {
+            if parent.get() == root && ((board.is_some() && freeze) || (board.is_none() && moved)) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        let coop = &game.coop;
+        let mut children = Vec::new();
+        if freeze {
+            children.push(spawn_board(&mut commands, &coop.board));
+        }
+        if moved {
+            for (i, engine) in coop.engines.iter().enumerate() {
+                if engine.phase == Phase::Falling {
+                    children.push(spawn_brick_board(
+                        &mut commands,
+                        engine.moving_brick.into(),
+                        engine.moving_pos,
+                    ));
+                }
+                if let Some(brick) = engine.next_brick {
+                    // next brick of player 2 under the one of player 1
+                    let next = spawn_brick_next(&mut commands, brick.into());
+                    commands.entity(next).insert(Transform::from_xyz(
+                        NEXT_BRICK_LEFT_PX + extra_width(),
+                        NEXT_BRICK_BOTTOM_PX - i as f32 * COOP_NEXT_GAP_PX,
+                        0.0,
+                    ));
+                    children.push(next);
+                }
+            }
+        }
+        commands.entity(root).push_children(&children);
+        game.freeze = false;
+        game.moved = false;
+    }
+}
+
+#[derive(Component)]
+pub struct CoopOverText;
+
+pub fn coop_text_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    games: Query<(Entity, &CoopGame)>,
+    mut texts: Query<(&mut Text, &Owner, &PlayerText)>,
+    over_texts: Query<(), With<CoopOverText>>,
+) {
+    for (mut text, owner, kind) in &mut texts {
+        let Ok((_, game)) = games.get(owner.0) else {
+            continue;
+        };
+        let stats = &game.coop.stats;
+        text.sections[0].value = match kind {
+            PlayerText::Score => format!("{:06}", stats.score),
+            PlayerText::Lines => format!("{:06}", stats.lines),
+            PlayerText::Level => format!("{:02}", stats.level),
+            PlayerText::Info => String::new(),
+        };
+    }
+    for (root, game) in &games {
+        if game.coop.is_over() && over_texts.is_empty() {
+            commands
+                .spawn(init_text(
+                    " GAME OVER\n\nPRESS SPACE",
+                    TEXT_GAME_X + extra_width() / 2.0,
+                    TEXT_GAME_Y,
+                    &asset_server,
+                ))
+                .insert((CoopOverText, Owner(root)));
+        }
+    }
+}
+
+/// back to menu when the game is over
+pub fn coop_over_system(
+    mut state: ResMut<NextState<GameState>>,
+    games: Query<&CoopGame>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if games.iter().any(|game| game.coop.is_over()) && keyboard_input.just_pressed(KeyCode::Space) {
+        state.set(GameState::Menu);
+    }
+}
+
+/// despawn the game and its texts, the window is one screen again
+#[allow(clippy::type_complexity)]
+pub fn coop_exit(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<CoopGame>, With<Owner>)>>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut background: Query<&mut Transform, With<Background>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+    set_layout(WINDOWS_WIDTH, &mut window, &mut background);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickView;
+    use rand::prelude::*;
+
+    fn dots(engine: &Engine) -> Vec<(i8, i8)> {
+        BrickView::from(engine.moving_brick)
+            .dots
+            .iter()
+            .map(|dot| dot.with_original_dot(&engine.moving_pos))
+            .map(|dot| (dot.0, dot.1))
+            .collect()
+    }
+
+    #[test]
+    fn falling_bricks_never_overlap() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut coop = Coop::new(1);
+        let mut frames = 0;
+        while !coop.is_over() && frames < 20000 {
+            let inputs = [0, 1].map(|_| engine::Input::from_bits(rng.gen::<u8>() & 0x3f));
+            coop.step(&inputs);
+            frames += 1;
+            assert_eq!(coop.stats.frames, frames);
+            let [a, b] = &coop.engines;
+            if a.phase == Phase::Falling && b.phase == Phase::Falling {
+                assert!(!dots(a).iter().any(|dot| dots(b).contains(dot)));
+            }
+            for engine in &coop.engines {
+                if engine.phase == Phase::Falling {
+                    assert!(coop
+                        .board
+                        .valid_brick(&engine.moving_brick, &engine.moving_pos));
+                }
+            }
+        }
+        assert!(coop.stats.pieces > 10);
+    }
+
+    #[test]
+    fn soft_drop_counts_per_player() {
+        let mut coop = Coop::new(3);
+        while coop
+            .engines
+            .iter()
+            .any(|engine| engine.phase != Phase::Falling)
+        {
+            coop.step(&Default::default());
+        }
+        let soft = engine::Input {
+            soft_drop: true,
+            ..Default::default()
+        };
+        let hard = engine::Input {
+            hard_drop: true,
+            ..Default::default()
+        };
+        // player 1 soft drops its brick, player 2 hard drops one meanwhile
+        let mut counters = (0, 0);
+        let mut locked = 0;
+        for frame in 0.. {
+            let drop = if frame == 4 { hard } else { Default::default() };
+            coop.step(&[soft, drop]);
+            for (i, event) in coop.take_events() {
+                match (i, event) {
+                    (0, Event::Locked { .. }) => {
+                        assert!(locked > 0 && counters.1 > 0);
+                        return;
+                    }
+                    (1, Event::Locked { .. }) => locked += 1,
+                    (_, Event::Over) => panic!("game over"),
+                    _ => {}
+                }
+            }
+            // locks of player 2 don't reset counters of player 1
+            let [one, two] = &coop.engines;
+            assert!(one.stats.soft > counters.0 && one.stats.push_down >= counters.1);
+            counters = (one.stats.soft, one.stats.push_down);
+            assert_eq!((two.stats.soft, two.stats.push_down), (0, 0));
+        }
+    }
+}
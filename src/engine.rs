@@ -0,0 +1,712 @@
+use crate::bricks::{Board, Brick, BrickView, Dot};
+use crate::consts::BRICK_START_DOT;
+use crate::garbage::{Garbage, GarbageRules};
+use crate::modes::{GameMode, HardDrop, Stats, Timing};
+use crate::randomizer::Randomizer;
+
+const T: usize = 6;
+/// all views of T brick turn around this dot
+const T_CENTER: Dot = Dot(1, 1);
+
+/// two bricks share a dot
+fn overlap(a: &Brick, a_pos: &Dot, b: &Brick, b_pos: &Dot) -> bool {
+    let dots = |brick: &Brick, pos: &Dot| {
+        BrickView::from(*brick)
+            .dots
+            .map(|dot| dot.with_original_dot(pos))
+    };
+    let theirs = dots(b, b_pos);
+    dots(a, a_pos)
+        .iter()
+        .any(|dot| theirs.iter().any(|t| t.0 == dot.0 && t.1 == dot.1))
+}
+
+/// keys held down in one frame
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Input {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    pub hard_drop: bool,
+    pub rotate: bool,
+    pub rotate_back: bool,
+    pub hold: bool,
+}
+
+impl Input {
+    /// keys as bits in field order, lowest first, as sent over network
+    pub fn to_bits(self) -> u8 {
+        [
+            self.left,
+            self.right,
+            self.soft_drop,
+            self.hard_drop,
+            self.rotate,
+            self.rotate_back,
+            self.hold,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, held)| (*held as u8) << i)
+        .sum()
+    }
+
+    pub fn from_bits(keys: u8) -> Input {
+        let held = |i: u8| keys & (1 << i) != 0;
+        Input {
+            left: held(0),
+            right: held(1),
+            soft_drop: held(2),
+            hard_drop: held(3),
+            rotate: held(4),
+            rotate_back: held(5),
+            hold: held(6),
+        }
+    }
+
+    /// keys held now, but not in `prev` frame
+    pub fn just_pressed(&self, prev: &Input) -> Input {
+        Input {
+            left: self.left && !prev.left,
+            right: self.right && !prev.right,
+            soft_drop: self.soft_drop && !prev.soft_drop,
+            hard_drop: self.hard_drop && !prev.hard_drop,
+            rotate: self.rotate && !prev.rotate,
+            rotate_back: self.rotate_back && !prev.rotate_back,
+            hold: self.hold && !prev.hold,
+        }
+    }
+}
+
+/// delayed auto shift of left and right keys
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutoShift {
+    frames: u32,
+    dir: i8,
+}
+
+impl AutoShift {
+    /// shift of this frame, -1 left, 1 right or 0
+    pub fn shift(&mut self, input: &Input, pressed: &Input, timing: &Timing) -> i8 {
+        if pressed.left || pressed.right {
+            self.dir = if pressed.left { -1 } else { 1 };
+            self.frames = 0;
+            return self.dir;
+        }
+        if self.dir == 0 && input.left != input.right {
+            // pressed while DAS was not charging, keep charging from where it was
+            self.dir = if input.left { -1 } else { 1 };
+        }
+        let held = (self.dir < 0 && input.left) || (self.dir > 0 && input.right);
+        if !held {
+            self.dir = 0;
+            return 0;
+        }
+        self.frames += 1;
+        if self.frames < timing.das {
+            return 0;
+        }
+        self.frames = timing.das - timing.arr;
+        self.dir
+    }
+
+    /// fully charged, next frame held shifts again
+    fn charge(&mut self, timing: &Timing) {
+        self.frames = timing.das;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Phase {
+    /// waiting ARE frames before spawning next brick
+    Spawning(u32),
+    /// moving brick is falling
+    Falling,
+    /// frames left before cleaned lines are removed from board
+    Clearing(u32),
+    Over,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Outcome {
+    /// new brick has no space to spawn
+    TopOut,
+    /// game mode finished, e.g. level 999 in master, or a puzzle solved
+    Completed,
+    /// a puzzle is not solved
+    Failed,
+    /// there is no next brick in a fixed queue
+    OutOfBricks,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::TopOut => "TOP_OUT",
+            Outcome::Completed => "COMPLETED",
+            Outcome::Failed => "FAILED",
+            Outcome::OutOfBricks => "OUT_OF_BRICKS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    Spawned,
+    /// moving brick moved or rotated
+    Moved,
+    /// moving brick swapped with hold
+    Held,
+    Locked {
+        brick: Brick,
+        lines: u32,
+        /// T brick rotated into place, with 3 of its 4 corners occupied
+        tspin: bool,
+        /// board is empty after cleaning lines
+        perfect_clear: bool,
+        /// stack height with the brick, before cleaning lines
+        height: i8,
+        /// holes after cleaning lines
+        holes: u32,
+    },
+    /// garbage lines to send to opponents, left after cancelling garbage received
+    Attack(u32),
+    BoardChanged,
+    Over,
+}
+
+/// game rules of one board, stepped frame by frame.
+///
+/// Engine knows nothing about bevy, it is driven by [`Engine::step`]
+/// with keys held in the frame, and reports what happened as [`Event`].
+pub struct Engine {
+    pub mode: GameMode,
+    pub board: Board,
+    pub moving_brick: Brick,
+    pub moving_pos: Dot,
+    /// where bricks enter the board
+    pub start: Dot,
+    /// falling brick of another player on the same board, in co-op.
+    /// The moving brick can't go through it, but doesn't land on it either
+    pub obstacle: Option<(Brick, Dot)>,
+    pub next_brick: Option<Brick>,
+    pub hold_brick: Option<Brick>,
+    /// hold is allowed in this game
+    pub hold_enabled: bool,
+    /// brick falls only by soft or hard drop, e.g. while tutorial waits for the player
+    pub gravity_paused: bool,
+    pub phase: Phase,
+    pub stats: Stats,
+    pub outcome: Option<Outcome>,
+    /// garbage received is pushed under the stack when a brick freezes without cleaning lines
+    pub garbage: Garbage,
+    randomizer: Randomizer,
+    prev_input: Input,
+    gravity: u32,
+    lock_frames: u32,
+    das: AutoShift,
+    /// soft drop pressed since the brick spawned, for NES controls
+    soft_drop_armed: bool,
+    /// ARE after the brick freezes
+    are: u32,
+    /// hold is used once for every brick
+    hold_used: bool,
+    /// last successful move is a rotation, for T-spin
+    rotated: bool,
+    events: Vec<Event>,
+}
+
+impl Engine {
+    pub fn new(mode: GameMode, start_level: u32, seed: u64) -> Self {
+        let mut engine =
+            Self::with_randomizer(mode, start_level, Randomizer::new(mode.randomizer(), seed));
+        engine.garbage = Garbage::new(GarbageRules::default(), seed);
+        engine
+    }
+
+    /// a game on a prepared board, with bricks known in advance
+    pub fn with_bricks(mode: GameMode, board: Board, bricks: &[Brick], hold_enabled: bool) -> Self {
+        let mut engine = Self::with_randomizer(mode, 0, Randomizer::fixed(bricks));
+        engine.board = board;
+        engine.hold_enabled = hold_enabled;
+        engine
+    }
+
+    fn with_randomizer(mode: GameMode, start_level: u32, mut randomizer: Randomizer) -> Self {
+        let next_brick = randomizer.next_brick();
+        Self {
+            mode,
+            board: Board::default(),
+            moving_brick: next_brick.unwrap_or(Brick { ty: 0, rotation: 0 }),
+            moving_pos: BRICK_START_DOT,
+            start: BRICK_START_DOT,
+            obstacle: None,
+            next_brick,
+            hold_brick: None,
+            hold_enabled: false,
+            gravity_paused: false,
+            phase: Phase::Spawning(1),
+            stats: Stats::new(start_level),
+            outcome: None,
+            garbage: Garbage::new(GarbageRules::default(), 0),
+            randomizer,
+            prev_input: Input::default(),
+            gravity: 0,
+            lock_frames: 0,
+            das: AutoShift::default(),
+            soft_drop_armed: false,
+            are: 0,
+            hold_used: false,
+            rotated: false,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// hold is allowed for the moving brick
+    pub fn can_hold(&self) -> bool {
+        self.hold_enabled && !self.hold_used
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.phase == Phase::Over
+    }
+
+    /// next `count` bricks, starting with the next brick, as the randomizer will give them
+    pub fn preview(&self, count: usize) -> Vec<Brick> {
+        let mut randomizer = self.randomizer.clone();
+        self.next_brick
+            .into_iter()
+            .chain(std::iter::from_fn(|| randomizer.next_brick()))
+            .take(count)
+            .collect()
+    }
+
+    /// end the game from outside, e.g. puzzle objective is met.
+    /// Running out of bricks can be overridden, the last brick may have met the objective.
+    pub fn end(&mut self, outcome: Outcome) {
+        match self.outcome {
+            None => self.game_over(outcome),
+            Some(Outcome::OutOfBricks) => self.outcome = Some(outcome),
+            Some(_) => {}
+        }
+    }
+
+    /// run one frame
+    pub fn step(&mut self, input: &Input) {
+        if self.is_over() {
+            return;
+        }
+        // keys held since before the game started are not pressed, e.g. space in menu
+        if self.stats.frames == 0 {
+            self.prev_input = *input;
+        }
+        self.stats.frames += 1;
+        self.garbage.tick();
+        let pressed = input.just_pressed(&self.prev_input);
+        self.prev_input = *input;
+
+        match self.phase {
+            Phase::Spawning(n) if n > 1 => self.phase = Phase::Spawning(n - 1),
+            Phase::Spawning(_) => self.spawn(),
+            Phase::Clearing(n) if n > 1 => self.phase = Phase::Clearing(n - 1),
+            Phase::Clearing(_) => {
+                self.board.clean_lines();
+                self.events.push(Event::BoardChanged);
+                self.start_spawning();
+            }
+            _ => {}
+        }
+
+        // DAS is charged in every phase, so a brick may shift at once when spawned.
+        // NES only charges it while a brick is falling.
+        let falling = self.phase == Phase::Falling;
+        let shift = if falling || !self.mode.nes_controls() {
+            let timing = self.mode.timing(self.stats.level);
+            self.das.shift(input, &pressed, &timing)
+        } else {
+            0
+        };
+        if falling {
+            self.falling(input, &pressed, shift);
+        }
+    }
+
+    fn falling(&mut self, input: &Input, pressed: &Input, shift: i8) {
+        if pressed.hold && self.hold_enabled && !self.hold_used && self.hold() {
+            return;
+        }
+        if pressed.rotate {
+            self.try_rotate(self.moving_brick.rotate());
+        }
+        if pressed.rotate_back {
+            self.try_rotate(self.moving_brick.rotate_back());
+        }
+        if shift != 0 {
+            let pos = if shift < 0 {
+                self.moving_pos.left()
+            } else {
+                self.moving_pos.right()
+            };
+            if !self.try_move(pos) && self.mode.nes_controls() {
+                // blocked by wall or bricks, DAS stays charged
+                self.das.charge(&self.mode.timing(self.stats.level));
+            }
+        }
+        if pressed.hard_drop && self.mode.hard_drop() != HardDrop::Disabled {
+            while self.try_move(self.moving_pos.down()) {
+                self.lock_frames = 0;
+            }
+            if self.mode.hard_drop() == HardDrop::Firm && !self.can_fall() {
+                self.lock();
+                return;
+            }
+        }
+
+        let level = self.stats.level;
+        let soft_drop = if self.mode.nes_controls() {
+            // NES: soft drop is pressed again for each brick, and only works alone
+            self.soft_drop_armed = (self.soft_drop_armed || pressed.soft_drop) && input.soft_drop;
+            self.soft_drop_armed && !input.left && !input.right
+        } else {
+            input.soft_drop
+        };
+        if !soft_drop {
+            self.stats.push_down = 0;
+        }
+        let gravity = if soft_drop {
+            self.stats.soft += 1;
+            if self.mode.soft_drop_locks() && !self.can_fall() {
+                self.lock();
+                return;
+            }
+            self.mode.soft_drop(level)
+        } else if self.gravity_paused {
+            return;
+        } else {
+            self.mode.gravity(level)
+        };
+
+        let lock_delay = self.mode.timing(level).lock;
+        // gravity built up at a slower speed, e.g. before soft drop, is at most a tick
+        self.gravity = self.gravity.min(gravity.frames - 1);
+        self.gravity += gravity.rows;
+        while self.gravity >= gravity.frames {
+            self.gravity -= gravity.frames;
+            if self.try_move(self.moving_pos.down()) {
+                self.lock_frames = 0;
+                if soft_drop {
+                    self.stats.push_down += 1;
+                }
+            } else {
+                self.gravity = 0;
+                if lock_delay.is_none() && !self.can_fall() {
+                    //there is no space to fall, so freeze the brick.
+                    self.lock();
+                    return;
+                }
+            }
+        }
+        if let Some(lock_delay) = lock_delay {
+            if !self.can_fall() {
+                self.lock_frames += 1;
+                if self.lock_frames >= lock_delay {
+                    self.lock();
+                }
+            }
+        }
+    }
+
+    fn can_fall(&self) -> bool {
+        self.board
+            .valid_brick(&self.moving_brick, &self.moving_pos.down())
+    }
+
+    /// brick fits the board and doesn't overlap the obstacle
+    fn fits(&self, brick: &Brick, pos: &Dot) -> bool {
+        self.board.valid_brick(brick, pos)
+            && !self
+                .obstacle
+                .is_some_and(|(other, other_pos)| overlap(brick, pos, &other, &other_pos))
+    }
+
+    fn try_move(&mut self, pos: Dot) -> bool {
+        if !self.fits(&self.moving_brick, &pos) {
+            return false;
+        }
+        self.moving_pos = pos;
+        self.rotated = false;
+        self.events.push(Event::Moved);
+        true
+    }
+
+    fn try_rotate(&mut self, rotated: Brick) -> bool {
+        for kick in self.mode.kicks(&rotated) {
+            let pos = kick.with_original_dot(&self.moving_pos);
+            if self.fits(&rotated, &pos) {
+                self.moving_brick = rotated;
+                self.moving_pos = pos;
+                self.rotated = true;
+                self.events.push(Event::Moved);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// swap moving brick with hold, or with next brick when hold is empty
+    fn hold(&mut self) -> bool {
+        let held = match self.hold_brick {
+            Some(brick) => brick,
+            None => match self.next_brick {
+                Some(brick) => {
+                    self.next_brick = self.randomizer.next_brick();
+                    brick
+                }
+                None => return false,
+            },
+        };
+        self.hold_brick = Some(Brick {
+            ty: self.moving_brick.ty,
+            rotation: 0,
+        });
+        self.hold_used = true;
+        self.events.push(Event::Held);
+        self.enter(held);
+        true
+    }
+
+    /// T-spin as 3-corner rule: T brick was rotated into place,
+    /// and 3 corners around its center are occupied or out of board
+    fn is_tspin(&self) -> bool {
+        if self.moving_brick.ty != T || !self.rotated {
+            return false;
+        }
+        let center = T_CENTER.with_original_dot(&self.moving_pos);
+        [Dot(-1, -1), Dot(1, -1), Dot(-1, 1), Dot(1, 1)]
+            .iter()
+            .filter(|corner| {
+                let dot = corner.with_original_dot(&center);
+                !self.board.dot_in_board(&dot) || self.board.occupied_dot(&dot)
+            })
+            .count()
+            >= 3
+    }
+
+    fn lock(&mut self) {
+        let tspin = self.is_tspin();
+        let brick = self.moving_brick;
+        self.board.occupy_brick(&brick, &self.moving_pos);
+        let lines = self.board.get_clean_lines().len() as u32;
+        let height = self.board.height();
+        let mut cleaned = self.board.clone();
+        cleaned.clean_lines();
+        let bravo = lines > 0 && cleaned.is_empty();
+        let level = self.stats.level;
+        let line_clear = self.mode.line_clear_delay(level, self.stats.frames);
+        let bottom = BrickView::from(self.moving_brick)
+            .dots
+            .iter()
+            .map(|dot| dot.1 + self.moving_pos.1)
+            .min()
+            .unwrap();
+        self.are = self.mode.are(level, bottom);
+        self.mode.on_lock(&mut self.stats, lines, bravo);
+        self.stats.soft = 0;
+        self.stats.push_down = 0;
+        self.hold_used = false;
+        self.events.push(Event::Locked {
+            brick,
+            lines,
+            tspin,
+            perfect_clear: bravo,
+            height,
+            holes: cleaned.holes(),
+        });
+        let attack = self.garbage.attack(lines, tspin, bravo);
+        let attack = self.garbage.cancel(attack);
+        if attack > 0 {
+            self.events.push(Event::Attack(attack));
+        }
+        self.events.push(Event::BoardChanged);
+
+        if self.mode.completed(&self.stats) {
+            self.board.clean_lines();
+            self.game_over(Outcome::Completed);
+        } else if lines > 0 && line_clear > 0 {
+            self.phase = Phase::Clearing(line_clear);
+        } else {
+            self.board.clean_lines();
+            if lines == 0 {
+                self.garbage.insert(&mut self.board);
+            }
+            self.start_spawning();
+        }
+    }
+
+    fn start_spawning(&mut self) {
+        if self.are == 0 {
+            self.spawn();
+        } else {
+            self.phase = Phase::Spawning(self.are);
+        }
+    }
+
+    fn spawn(&mut self) {
+        let Some(brick) = self.next_brick else {
+            self.game_over(Outcome::OutOfBricks);
+            return;
+        };
+        if self.board.valid_brick(&brick, &self.start) && !self.fits(&brick, &self.start) {
+            // wait for the obstacle to move away
+            self.phase = Phase::Spawning(1);
+            return;
+        }
+        self.next_brick = self.randomizer.next_brick();
+        self.mode.on_spawn(&mut self.stats);
+        self.enter(brick);
+    }
+
+    /// put brick at start point
+    fn enter(&mut self, brick: Brick) {
+        self.moving_brick = brick;
+        self.moving_pos = self.start;
+        self.gravity = 0;
+        self.lock_frames = 0;
+        self.soft_drop_armed = false;
+        self.rotated = false;
+
+        if self.board.valid_brick(&self.moving_brick, &self.moving_pos) {
+            self.phase = Phase::Falling;
+            self.events.push(Event::Spawned);
+        } else {
+            self.game_over(Outcome::TopOut);
+        }
+    }
+
+    fn game_over(&mut self, outcome: Outcome) {
+        self.phase = Phase::Over;
+        self.outcome = Some(outcome);
+        self.events.push(Event::Over);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{BOARD_X, BOARD_Y};
+
+    const I_UP: Brick = Brick { ty: 1, rotation: 1 };
+    const O: Brick = Brick { ty: 0, rotation: 0 };
+
+    /// puzzle engine on `rows` full lines but the left column
+    fn well(rows: i8) -> Engine {
+        let mut board = Board::default();
+        for y in 0..rows {
+            for x in 1..BOARD_X {
+                board.occupy_dot(&Dot(x, y));
+            }
+        }
+        Engine::with_bricks(GameMode::Puzzle, board, &[O; 4], false)
+    }
+
+    /// hard drop `brick` from `pos`, events until the next brick spawns
+    fn drop_at(engine: &mut Engine, brick: Brick, pos: Dot) -> Vec<Event> {
+        while engine.phase != Phase::Falling {
+            engine.step(&Input::default());
+        }
+        engine.take_events();
+        engine.moving_brick = brick;
+        engine.moving_pos = pos;
+        engine.step(&Input {
+            hard_drop: true,
+            ..Input::default()
+        });
+        let mut events = engine.take_events();
+        while engine.phase != Phase::Falling && !engine.is_over() {
+            engine.step(&Input::default());
+            events.extend(engine.take_events());
+        }
+        events
+    }
+
+    fn attacks(events: &[Event]) -> Vec<u32> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Attack(lines) => Some(*lines),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn garbage_rows(board: &Board) -> usize {
+        (0..BOARD_Y)
+            .map(|y| {
+                (0..BOARD_X)
+                    .filter(|x| board.occupied_dot(&Dot(*x, y)))
+                    .count()
+            })
+            .filter(|dots| *dots == BOARD_X as usize - 1)
+            .count()
+    }
+
+    #[test]
+    fn attack_cancels_garbage_first() {
+        let mut engine = well(4);
+        // left after the tetris, not a perfect clear
+        engine.board.occupy_dot(&Dot(5, 4));
+        engine.garbage.receive(3);
+        let events = drop_at(&mut engine, I_UP, Dot(-2, 10));
+        assert_eq!(attacks(&events), [1]);
+        assert_eq!(engine.garbage.pending(), 0);
+
+        let mut engine = well(4);
+        engine.board.occupy_dot(&Dot(5, 4));
+        engine.garbage.receive(6);
+        let events = drop_at(&mut engine, I_UP, Dot(-2, 10));
+        assert!(attacks(&events).is_empty());
+        assert_eq!(engine.garbage.pending(), 2);
+    }
+
+    #[test]
+    fn garbage_inserted_without_clear() {
+        let mut engine = well(0);
+        engine.garbage.rules.delay = 0;
+        engine.garbage.receive(2);
+        drop_at(&mut engine, O, Dot(3, 10));
+        assert_eq!(garbage_rows(&engine.board), 2);
+        assert_eq!(engine.board.height(), 4);
+        assert_eq!(engine.garbage.pending(), 0);
+    }
+
+    #[test]
+    fn no_garbage_on_clear() {
+        let mut engine = well(1);
+        engine.garbage.rules.delay = 0;
+        engine.garbage.receive(2);
+        let events = drop_at(&mut engine, I_UP, Dot(-2, 10));
+        // a single sends nothing and cancels nothing
+        assert!(attacks(&events).is_empty());
+        assert_eq!(garbage_rows(&engine.board), 0);
+        assert_eq!(engine.garbage.pending(), 2);
+    }
+
+    #[test]
+    fn end_overrides_out_of_bricks() {
+        // the last brick cleans the well
+        let mut engine = Engine::with_bricks(GameMode::Puzzle, well(4).board, &[I_UP], false);
+        let events = drop_at(&mut engine, I_UP, Dot(-2, 10));
+        assert!(events.iter().any(|e| matches!(e, Event::Over)));
+        assert_eq!(engine.outcome, Some(Outcome::OutOfBricks));
+        engine.end(Outcome::Completed);
+        assert_eq!(engine.outcome, Some(Outcome::Completed));
+        // other outcomes stay
+        engine.end(Outcome::Failed);
+        assert_eq!(engine.outcome, Some(Outcome::Completed));
+        assert!(engine.take_events().is_empty());
+    }
+}
@@ -0,0 +1,509 @@
+use crate::bricks::{Brick, Dot};
+use crate::consts::{BOARD_Y, BRICK_START_DOT};
+use crate::engine::{Engine, Input, Outcome, Phase};
+use crate::modes::{GameMode, HardDrop};
+use crate::movegen::{reachable, Move, Reachable};
+
+/// frames a placement may take before the brick is left to fall,
+/// e.g. when gravity got in the way of its moves
+const PLACE_FRAMES: u32 = 600;
+
+/// what an agent does in a step
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    /// keys held for one frame
+    Keys(Input),
+    /// index into [`Observation::placements`], played by keys until the brick locks.
+    /// Out of range drops the brick where it is.
+    Place(usize),
+}
+
+/// where the moving brick, or the brick out of hold, can lock
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    /// hold first
+    pub hold: bool,
+    pub brick: Brick,
+    pub pos: Dot,
+}
+
+/// what an agent sees of the game
+#[derive(Debug, Clone)]
+pub struct Observation {
+    /// `BOARD_Y` rows from the bottom, each as wide as the board
+    pub board: Vec<Vec<bool>>,
+    /// falling brick and where, `None` between bricks
+    pub moving: Option<(Brick, Dot)>,
+    pub next: Option<Brick>,
+    pub hold: Option<Brick>,
+    pub can_hold: bool,
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub pieces: u32,
+    pub frames: u32,
+    pub combo: u32,
+    pub back_to_back: bool,
+    /// choices of [`Action::Place`], empty between bricks
+    pub placements: Vec<Placement>,
+}
+
+/// what a step changed, besides the reward
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Info {
+    pub lines: u32,
+    pub score: u32,
+    /// bricks spawned
+    pub pieces: u32,
+    /// holes made, less when holes were opened
+    pub holes: i64,
+    pub frames: u32,
+    pub outcome: Option<Outcome>,
+}
+
+/// reward of a step, summed with these weights
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rewards {
+    /// by line cleared
+    pub lines: f64,
+    /// by point scored
+    pub score: f64,
+    /// by brick spawned
+    pub survival: f64,
+    /// by hole made
+    pub holes: f64,
+    /// once when the game is over
+    pub game_over: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            lines: 1.0,
+            score: 0.0,
+            survival: 0.01,
+            holes: -0.1,
+            game_over: -1.0,
+        }
+    }
+}
+
+impl Rewards {
+    pub fn reward(&self, info: &Info) -> f64 {
+        let mut reward = self.lines * info.lines as f64
+            + self.score * info.score as f64
+            + self.survival * info.pieces as f64
+            + self.holes * info.holes as f64;
+        if info.outcome.is_some() {
+            reward += self.game_over;
+        }
+        reward
+    }
+}
+
+/// result of [`Env::step`]
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    pub info: Info,
+}
+
+/// gym-style environment for training agents by the rules of the engine.
+///
+/// Agents either press keys frame by frame, or choose a placement each brick,
+/// which is played by keys as the computer player does.
+pub struct Env {
+    pub mode: GameMode,
+    pub level: u32,
+    /// hold is allowed
+    pub hold: bool,
+    pub rewards: Rewards,
+    engine: Engine,
+    /// moves of the placements offered
+    placements: Vec<(bool, Reachable)>,
+}
+
+impl Env {
+    pub fn new(mode: GameMode) -> Self {
+        Self {
+            mode,
+            level: 0,
+            hold: mode == GameMode::Versus,
+            rewards: Rewards::default(),
+            engine: Engine::new(mode, 0, 0),
+            placements: Vec::new(),
+        }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// start a new game, up to the first brick
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.engine = Engine::new(self.mode, self.level, seed);
+        self.engine.hold_enabled = self.hold;
+        self.until_falling();
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Action) -> Step {
+        let before = self.engine.stats.clone();
+        let holes = self.engine.board.holes();
+        let over = self.engine.is_over();
+        match action {
+            Action::Keys(input) => self.frame(input),
+            Action::Place(index) => {
+                let (hold, moves) = match self.placements.get(index) {
+                    Some((hold, target)) => (*hold, target.moves.clone()),
+                    None => (false, vec![Move::HardDrop]),
+                };
+                self.place(hold, &moves);
+                self.until_falling();
+            }
+        }
+        let stats = &self.engine.stats;
+        let info = Info {
+            lines: stats.lines - before.lines,
+            score: stats.score - before.score,
+            pieces: stats.pieces - before.pieces,
+            holes: self.engine.board.holes() as i64 - holes as i64,
+            frames: stats.frames - before.frames,
+            outcome: if over { None } else { self.engine.outcome },
+        };
+        Step {
+            observation: self.observe(),
+            reward: self.rewards.reward(&info),
+            done: self.engine.is_over(),
+            info,
+        }
+    }
+
+    fn frame(&mut self, input: Input) {
+        if !self.engine.is_over() {
+            self.engine.step(&input);
+            self.engine.take_events();
+        }
+    }
+
+    fn until_falling(&mut self) {
+        while !matches!(self.engine.phase, Phase::Falling | Phase::Over) {
+            self.frame(Input::default());
+        }
+    }
+
+    /// press keys of the moves, each released for a frame so it is seen, until the brick locks
+    fn place(&mut self, hold: bool, moves: &[Move]) {
+        let pieces = self.engine.stats.pieces;
+        let start = self.engine.stats.frames;
+        let placing = |engine: &Engine| {
+            !engine.is_over()
+                && engine.stats.pieces == pieces
+                && engine.stats.frames - start < PLACE_FRAMES
+        };
+        let press = |env: &mut Self, input: Input| {
+            env.frame(input);
+            env.frame(Input::default());
+        };
+        if hold {
+            press(
+                self,
+                Input {
+                    hold: true,
+                    ..Input::default()
+                },
+            );
+        }
+        for mv in moves {
+            if !placing(&self.engine) {
+                return;
+            }
+            match mv {
+                Move::SoftDrop => {
+                    // soft drop locks a landed brick in modes with sonic drop
+                    let input = match self.engine.mode.hard_drop() {
+                        HardDrop::Sonic => Move::HardDrop.input(),
+                        _ => Move::SoftDrop.input(),
+                    };
+                    while placing(&self.engine) && self.can_fall() {
+                        self.frame(input);
+                    }
+                    self.frame(Input::default());
+                }
+                Move::HardDrop if self.engine.mode.hard_drop() == HardDrop::Firm => {
+                    press(self, mv.input());
+                }
+                Move::HardDrop => {
+                    while placing(&self.engine) {
+                        self.frame(Move::SoftDrop.input());
+                    }
+                }
+                _ => press(self, mv.input()),
+            }
+        }
+        // moves went elsewhere, let the brick fall and lock
+        while placing(&self.engine) {
+            self.frame(Input::default());
+        }
+        while !self.engine.is_over() && self.engine.stats.pieces == pieces {
+            self.frame(Move::SoftDrop.input());
+        }
+    }
+
+    fn can_fall(&self) -> bool {
+        let engine = &self.engine;
+        engine
+            .board
+            .valid_brick(&engine.moving_brick, &engine.moving_pos.down())
+    }
+
+    fn observe(&mut self) -> Observation {
+        let engine = &self.engine;
+        let falling = engine.phase == Phase::Falling;
+        self.placements.clear();
+        if falling {
+            let board = &engine.board;
+            let current = reachable(
+                board,
+                engine.moving_brick,
+                engine.moving_pos,
+                engine.mode,
+                engine.stats.level,
+            );
+            self.placements
+                .extend(current.into_iter().map(|target| (false, target)));
+            // out of hold comes the held brick, or the next one when hold is empty
+            if let (true, Some(held)) = (engine.can_hold(), engine.hold_brick.or(engine.next_brick))
+            {
+                let held = reachable(
+                    board,
+                    held,
+                    BRICK_START_DOT,
+                    engine.mode,
+                    engine.stats.level,
+                );
+                self.placements
+                    .extend(held.into_iter().map(|target| (true, target)));
+            }
+        }
+        let board = &engine.board;
+        Observation {
+            board: (0..BOARD_Y)
+                .map(|y| {
+                    (0..board.width())
+                        .map(|x| board.occupied_dot(&Dot(x, y)))
+                        .collect()
+                })
+                .collect(),
+            moving: falling.then_some((engine.moving_brick, engine.moving_pos)),
+            next: engine.next_brick,
+            hold: engine.hold_brick,
+            can_hold: engine.can_hold(),
+            score: engine.stats.score,
+            lines: engine.stats.lines,
+            level: engine.stats.level,
+            pieces: engine.stats.pieces,
+            frames: engine.stats.frames,
+            combo: engine.garbage.combo(),
+            back_to_back: engine.garbage.back_to_back(),
+            placements: self
+                .placements
+                .iter()
+                .map(|(hold, target)| Placement {
+                    hold: *hold,
+                    brick: target.brick,
+                    pos: target.pos,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickView;
+
+    /// dots of the placement on the board
+    fn placed(observation: &Observation, placement: &Placement) -> bool {
+        BrickView::from(placement.brick).dots.iter().all(|dot| {
+            let dot = dot.with_original_dot(&placement.pos);
+            observation.board[dot.1 as usize][dot.0 as usize]
+        })
+    }
+
+    #[test]
+    fn reset_to_first_brick() {
+        let mut env = Env::new(GameMode::Classic);
+        let observation = env.reset(7);
+        assert_eq!(observation.pieces, 1);
+        assert!(observation.moving.is_some());
+        assert!(observation.next.is_some());
+        assert!(!observation.placements.is_empty());
+        assert!(observation.board.iter().flatten().all(|dot| !dot));
+        assert!(!env.engine().is_over());
+    }
+
+    #[test]
+    fn seeded_games_repeat() {
+        let play = |seed| {
+            let mut env = Env::new(GameMode::Versus);
+            let first = env.reset(seed);
+            let mut seen = vec![(first.moving, first.next)];
+            for i in 0..30 {
+                let count = env.observe().placements.len();
+                let step = env.step(Action::Place(i * 7 % count.max(1)));
+                seen.push((step.observation.moving, step.observation.next));
+                assert_eq!(step.reward, env.rewards.reward(&step.info));
+            }
+            (seen, env.engine().board.clone(), env.engine().stats.score)
+        };
+        let (seen, board, score) = play(3);
+        let (again, board_again, score_again) = play(3);
+        assert_eq!(format!("{:?}", seen), format!("{:?}", again));
+        assert!(board == board_again);
+        assert_eq!(score, score_again);
+        let (other, _, _) = play(4);
+        assert_ne!(format!("{:?}", seen), format!("{:?}", other));
+    }
+
+    #[test]
+    fn place_locks_at_placement() {
+        let mut env = Env::new(GameMode::Classic);
+        let observation = env.reset(1);
+        for (index, placement) in observation.placements.iter().enumerate() {
+            env.reset(1);
+            let step = env.step(Action::Place(index));
+            assert!(placed(&step.observation, placement), "{:?}", placement);
+            assert_eq!(step.info.pieces, 1);
+            assert_eq!(step.info.lines, 0);
+            let holes = env.engine().board.holes() as i64;
+            assert_eq!(step.info.holes, holes);
+            // a brick spawned and the holes it left
+            assert!((step.reward - (0.01 - 0.1 * holes as f64)).abs() < 1e-9);
+            assert!(!step.done);
+            assert!(step.observation.moving.is_some());
+        }
+    }
+
+    #[test]
+    fn place_out_of_range_drops() {
+        let mut env = Env::new(GameMode::Nes);
+        let observation = env.reset(2);
+        let (brick, mut pos) = observation.moving.unwrap();
+        while env.engine().board.valid_brick(&brick, &pos.down()) {
+            pos = pos.down();
+        }
+        let step = env.step(Action::Place(usize::MAX));
+        let dropped = Placement {
+            hold: false,
+            brick,
+            pos,
+        };
+        assert!(placed(&step.observation, &dropped));
+        assert_eq!(step.info.pieces, 1);
+    }
+
+    #[test]
+    fn place_out_of_hold() {
+        let mut env = Env::new(GameMode::Versus);
+        let observation = env.reset(5);
+        let next = observation.next;
+        let index = observation
+            .placements
+            .iter()
+            .position(|placement| placement.hold)
+            .unwrap();
+        assert_eq!(
+            Some(observation.placements[index].brick.ty),
+            next.map(|b| b.ty)
+        );
+        let step = env.step(Action::Place(index));
+        assert_eq!(
+            step.observation.hold.map(|b| b.ty),
+            observation.moving.map(|(b, _)| b.ty)
+        );
+        assert!(placed(&step.observation, &observation.placements[index]));
+        // the next brick may hold again
+        assert!(step.observation.can_hold);
+    }
+
+    #[test]
+    fn keys_play_a_frame() {
+        let mut env = Env::new(GameMode::Classic);
+        let observation = env.reset(1);
+        let (_, pos) = observation.moving.unwrap();
+        let left = Input {
+            left: true,
+            ..Input::default()
+        };
+        let step = env.step(Action::Keys(left));
+        assert_eq!(step.info.frames, 1);
+        let moved = step.observation.moving.unwrap().1;
+        assert_eq!((moved.0, moved.1), (pos.0 - 1, pos.1));
+        let drop = Input {
+            hard_drop: true,
+            ..Input::default()
+        };
+        let step = env.step(Action::Keys(drop));
+        assert_eq!(step.info.frames, 1);
+        // locked, and classic spawns the next brick without entry delay
+        assert_eq!(step.info.pieces, 1);
+        let (_, spawned) = step.observation.moving.unwrap();
+        assert_eq!(spawned.1, pos.1);
+        assert!(step.observation.board[0].iter().any(|dot| *dot));
+        assert!(!step.observation.placements.is_empty());
+    }
+
+    #[test]
+    fn done_on_top_out() {
+        let mut env = Env::new(GameMode::Classic);
+        env.reset(9);
+        let mut steps = 0;
+        let last = loop {
+            let step = env.step(Action::Place(usize::MAX));
+            steps += 1;
+            assert!(steps < 100, "stacked in the middle without topping out");
+            if step.done {
+                break step;
+            }
+            assert_eq!(step.info.outcome, None);
+        };
+        assert_eq!(last.info.outcome, Some(Outcome::TopOut));
+        assert_eq!(last.reward, env.rewards.reward(&last.info));
+        assert!(last.reward < -0.5);
+        // steps after the end change nothing
+        let after = env.step(Action::Place(0));
+        assert!(after.done);
+        assert_eq!(after.info.outcome, None);
+        assert_eq!(after.info.frames, 0);
+        assert_eq!(after.reward, 0.0);
+    }
+
+    #[test]
+    fn rewards_sum_weights() {
+        let rewards = Rewards {
+            lines: 1.0,
+            score: 0.01,
+            survival: 0.5,
+            holes: -0.25,
+            game_over: -10.0,
+        };
+        let info = Info {
+            lines: 2,
+            score: 300,
+            pieces: 1,
+            holes: 2,
+            frames: 40,
+            outcome: None,
+        };
+        assert_eq!(rewards.reward(&info), 2.0 + 3.0 + 0.5 - 0.5);
+        let over = Info {
+            outcome: Some(Outcome::TopOut),
+            ..info
+        };
+        assert_eq!(rewards.reward(&over), 5.0 - 10.0);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::bricks::Board;
+use crate::consts::BOARD_X;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+/// garbage lines sent for a clear
+#[derive(Debug, Clone)]
+pub struct AttackTable {
+    /// by lines cleaned at once
+    pub clears: [u32; 5],
+    /// by lines cleaned at once with a T-spin
+    pub tspins: [u32; 5],
+    /// added to a tetris or T-spin following another one
+    pub back_to_back: u32,
+    /// added by clears in a row, first clear is 0, the last value repeats
+    pub combos: &'static [u32],
+    /// added when the board is empty after the clear
+    pub perfect_clear: u32,
+}
+
+/// https://tetris.wiki/Garbage#Tetris_Guideline
+pub const GUIDELINE_ATTACK: AttackTable = AttackTable {
+    clears: [0, 0, 1, 2, 4],
+    tspins: [0, 2, 4, 6, 6],
+    back_to_back: 1,
+    combos: &[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+    perfect_clear: 10,
+};
+
+/// how garbage is sent and received
+#[derive(Debug, Clone)]
+pub struct GarbageRules {
+    pub table: AttackTable,
+    /// frames garbage waits in the queue before it can be inserted
+    pub delay: u32,
+    /// chance in percent the hole moves for each attack
+    pub hole_change_per_attack: u32,
+    /// chance in percent the hole moves for each row of an attack
+    pub hole_change_per_row: u32,
+}
+
+impl Default for GarbageRules {
+    fn default() -> Self {
+        Self {
+            table: GUIDELINE_ATTACK,
+            delay: 60,
+            hole_change_per_attack: 100,
+            hole_change_per_row: 0,
+        }
+    }
+}
+
+/// an attack received, waiting to be inserted
+#[derive(Debug, Clone, Copy)]
+struct Incoming {
+    lines: u32,
+    /// frames left before it can be inserted
+    delay: u32,
+}
+
+/// garbage of one board: attack of its clears, and garbage received from opponents.
+///
+/// Holes are rolled from a seeded generator, so boards with the same seed
+/// get the same garbage whether played locally, by bots or online.
+#[derive(Debug, Clone)]
+pub struct Garbage {
+    pub rules: GarbageRules,
+    queue: VecDeque<Incoming>,
+    /// clears in a row before the last brick, `None` if it cleaned nothing
+    combo: Option<u32>,
+    /// last clear was a tetris or T-spin
+    back_to_back: bool,
+    hole: i8,
+    rng: StdRng,
+}
+
+impl Garbage {
+    pub fn new(rules: GarbageRules, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self {
+            rules,
+            queue: VecDeque::new(),
+            combo: None,
+            back_to_back: false,
+            hole: rng.gen_range(0..BOARD_X),
+            rng,
+        }
+    }
+
+    /// lines sent for a brick frozen, keeping track of combo and back-to-back
+    pub fn attack(&mut self, lines: u32, tspin: bool, perfect_clear: bool) -> u32 {
+        if lines == 0 {
+            self.combo = None;
+            return 0;
+        }
+        let table = &self.rules.table;
+        let combo = self.combo.map_or(0, |combo| combo + 1);
+        self.combo = Some(combo);
+        let difficult = lines == 4 || tspin;
+        let mut attack = if tspin {
+            table.tspins[lines as usize]
+        } else {
+            table.clears[lines as usize]
+        };
+        if difficult && self.back_to_back {
+            attack += table.back_to_back;
+        }
+        self.back_to_back = difficult;
+        attack += table
+            .combos
+            .get(combo as usize)
+            .or(table.combos.last())
+            .copied()
+            .unwrap_or(0);
+        if perfect_clear {
+            attack += table.perfect_clear;
+        }
+        attack
+    }
+
+    /// clears in a row up to the last brick
+    pub fn combo(&self) -> u32 {
+        self.combo.map_or(0, |combo| combo + 1)
+    }
+
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    /// queue garbage from an opponent
+    pub fn receive(&mut self, lines: u32) {
+        if lines > 0 {
+            self.queue.push_back(Incoming {
+                lines,
+                delay: self.rules.delay,
+            });
+        }
+    }
+
+    /// take `lines` off garbage waiting, oldest first, returns lines left to send
+    pub fn cancel(&mut self, mut lines: u32) -> u32 {
+        while let Some(incoming) = self.queue.front_mut() {
+            if lines == 0 {
+                break;
+            }
+            let cancelled = lines.min(incoming.lines);
+            incoming.lines -= cancelled;
+            lines -= cancelled;
+            if incoming.lines == 0 {
+                self.queue.pop_front();
+            }
+        }
+        lines
+    }
+
+    /// count down the delay of garbage waiting by a frame
+    pub fn tick(&mut self) {
+        for incoming in &mut self.queue {
+            incoming.delay = incoming.delay.saturating_sub(1);
+        }
+    }
+
+    /// push garbage out of delay under the stack, returns lines inserted
+    pub fn insert(&mut self, board: &mut Board) -> u32 {
+        let mut inserted = 0;
+        while let Some(incoming) = self.queue.front() {
+            if incoming.delay > 0 {
+                break;
+            }
+            let incoming = self.queue.pop_front().unwrap();
+            self.move_hole(self.rules.hole_change_per_attack);
+            for row in 0..incoming.lines {
+                if row > 0 {
+                    self.move_hole(self.rules.hole_change_per_row);
+                }
+                board.push_garbage(self.hole);
+            }
+            inserted += incoming.lines;
+        }
+        inserted
+    }
+
+    /// hole goes to another column with chance `percent`
+    fn move_hole(&mut self, percent: u32) {
+        if self.rng.gen_range(0..100) < percent {
+            self.hole = (self.hole + self.rng.gen_range(1..BOARD_X)) % BOARD_X;
+        }
+    }
+
+    /// lines waiting, for the meter
+    pub fn pending(&self) -> u32 {
+        self.queue.iter().map(|incoming| incoming.lines).sum()
+    }
+
+    /// lines out of delay, inserted when next brick freezes without cleaning lines
+    pub fn ready(&self) -> u32 {
+        self.queue
+            .iter()
+            .filter(|incoming| incoming.delay == 0)
+            .map(|incoming| incoming.lines)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::Dot;
+
+    fn garbage() -> Garbage {
+        Garbage::new(GarbageRules::default(), 0)
+    }
+
+    #[test]
+    fn clears_and_tspins() {
+        for (lines, attack) in [(0, 0), (1, 0), (2, 1), (3, 2), (4, 4)] {
+            assert_eq!(garbage().attack(lines, false, false), attack);
+        }
+        for (lines, attack) in [(1, 2), (2, 4), (3, 6)] {
+            assert_eq!(garbage().attack(lines, true, false), attack);
+        }
+        assert_eq!(garbage().attack(2, false, true), 1 + 10);
+    }
+
+    #[test]
+    fn back_to_back() {
+        let mut garbage = garbage();
+        assert_eq!(garbage.attack(4, false, false), 4);
+        assert!(garbage.back_to_back());
+        garbage.attack(0, false, false);
+        // T-spin double after a tetris
+        assert_eq!(garbage.attack(2, true, false), 4 + 1);
+        garbage.attack(0, false, false);
+        // a double between breaks it
+        assert_eq!(garbage.attack(2, false, false), 1);
+        assert!(!garbage.back_to_back());
+        garbage.attack(0, false, false);
+        assert_eq!(garbage.attack(4, false, false), 4);
+    }
+
+    #[test]
+    fn combos() {
+        let mut garbage = garbage();
+        let attacks: Vec<_> = (0..14).map(|_| garbage.attack(1, false, false)).collect();
+        assert_eq!(attacks, [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5, 5, 5]);
+        assert_eq!(garbage.combo(), 14);
+        assert_eq!(garbage.attack(0, false, false), 0);
+        assert_eq!(garbage.combo(), 0);
+        assert_eq!(garbage.attack(2, false, false), 1);
+    }
+
+    #[test]
+    fn cancel_oldest_first() {
+        let mut garbage = garbage();
+        garbage.receive(3);
+        garbage.receive(2);
+        assert_eq!(garbage.cancel(4), 0);
+        assert_eq!(garbage.pending(), 1);
+        assert_eq!(garbage.cancel(5), 4);
+        assert_eq!(garbage.pending(), 0);
+    }
+
+    #[test]
+    fn insert_after_delay() {
+        let mut garbage = garbage();
+        let mut board = Board::default();
+        garbage.receive(2);
+        assert_eq!(garbage.insert(&mut board), 0);
+        for _ in 0..garbage.rules.delay {
+            garbage.tick();
+        }
+        assert_eq!(garbage.ready(), 2);
+        assert_eq!(garbage.insert(&mut board), 2);
+        assert_eq!(board.height(), 2);
+        // one hole for the whole attack
+        assert_eq!(board.holes(), 0);
+        for x in 0..BOARD_X {
+            assert_eq!(
+                board.occupied_dot(&Dot(x, 0)),
+                board.occupied_dot(&Dot(x, 1))
+            );
+        }
+        assert_eq!(garbage.pending(), 0);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::bricks::{Board, Brick, BrickView, Dot};
+use crate::consts::{BOARD_X, BRICKS_TYPES, BRICK_START_DOT, BRICK_VIEWS};
+use crate::puzzle::{Objective, Puzzle};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// shapes of generated puzzles: (garbage rows, rows to clear, bricks given).
+///
+/// No garbage means a perfect clear, otherwise clear the rows above garbage.
+const RECIPES: [(i8, i8, usize); 7] = [
+    (0, 2, 3),
+    (0, 2, 5),
+    (0, 4, 6),
+    (0, 4, 10),
+    (2, 2, 3),
+    (3, 3, 4),
+    (4, 4, 6),
+];
+
+/// search nodes for one try of carving bricks out of a board
+const CARVE_BUDGET: u32 = 2000;
+/// candidates tried at each step of carving
+const CARVE_BRANCHES: usize = 4;
+/// boards tried before giving up on a seed
+const MAX_ATTEMPTS: u32 = 200;
+
+/// where a brick freezes: brick (with rotation) and its position
+pub type Placement = (Brick, Dot);
+
+/// lowest position of a brick hard dropped from spawn height at column `x`,
+/// `None` if it does not fit there.
+pub fn drop_position(board: &Board, brick: &Brick, x: i8) -> Option<Dot> {
+    let mut pos = Dot(x, BRICK_START_DOT.1);
+    if !board.valid_brick(brick, &pos) {
+        return None;
+    }
+    while board.valid_brick(brick, &pos.down()) {
+        pos = pos.down();
+    }
+    Some(pos)
+}
+
+/// generate a puzzle from a seed, solvable by hard drops alone.
+///
+/// Puzzle is built backwards: fill the rows to clear, then carve bricks out of them
+/// such that each brick dropped back lands where it was. The first carved brick
+/// (the last one to drop) touches every row, so no row is cleared too early.
+pub fn generate(seed: u64) -> Result<Puzzle, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..MAX_ATTEMPTS {
+        let (garbage, rows, bricks) = *RECIPES.choose(&mut rng).unwrap();
+        let mut board = Board::default();
+        for y in 0..garbage {
+            let hole = rng.gen_range(0..BOARD_X);
+            for x in (0..BOARD_X).filter(|x| *x != hole) {
+                board.occupy_dot(&Dot(x, y));
+            }
+        }
+        for y in garbage..garbage + rows {
+            for x in 0..BOARD_X {
+                board.occupy_dot(&Dot(x, y));
+            }
+        }
+
+        let mut carved = Vec::new();
+        let mut budget = CARVE_BUDGET;
+        let rows = garbage..garbage + rows;
+        if !carve(
+            &mut board,
+            &rows,
+            bricks,
+            &mut carved,
+            &mut budget,
+            &mut rng,
+        ) {
+            continue;
+        }
+        carved.reverse();
+
+        let objective = if garbage == 0 {
+            Objective::PerfectClear
+        } else {
+            Objective::Lines(rows.len() as u32)
+        };
+        let puzzle = Puzzle {
+            name: format!("RANDOM {:04}", seed % 10000),
+            board,
+            bricks: carved
+                .iter()
+                .map(|(brick, _)| Brick {
+                    rotation: 0,
+                    ..*brick
+                })
+                .collect(),
+            hold: false,
+            objective,
+        };
+        if solves(&puzzle, &carved) {
+            return Ok(puzzle);
+        }
+    }
+    Err(format!(
+        "no puzzle of seed {} in {} attempts",
+        seed, MAX_ATTEMPTS
+    ))
+}
+
+/// carve `left` bricks out of `rows`, pushing them in carving order
+fn carve(
+    board: &mut Board,
+    rows: &std::ops::Range<i8>,
+    left: usize,
+    carved: &mut Vec<Placement>,
+    budget: &mut u32,
+    rng: &mut StdRng,
+) -> bool {
+    if left == 0 {
+        return true;
+    }
+    if *budget == 0 {
+        return false;
+    }
+    *budget -= 1;
+
+    let mut candidates = carvings(board, rows, carved.is_empty());
+    candidates.shuffle(rng);
+    for (brick, pos) in candidates.into_iter().take(CARVE_BRANCHES) {
+        let view = BrickView::from(brick);
+        for dot in view.dots {
+            board.free_dot(&dot.with_original_dot(&pos));
+        }
+        carved.push((brick, pos));
+        if carve(board, rows, left - 1, carved, budget, rng) {
+            return true;
+        }
+        carved.pop();
+        board.occupy_brick(&brick, &pos);
+    }
+    false
+}
+
+/// bricks which can be taken out of `rows` and dropped back to the same place
+fn carvings(board: &Board, rows: &std::ops::Range<i8>, span_all: bool) -> Vec<Placement> {
+    let mut result = Vec::new();
+    for ty in 0..BRICKS_TYPES {
+        for rotation in 0..BRICK_VIEWS[ty].len() {
+            let brick = Brick { ty, rotation };
+            let view = BrickView::from(brick);
+            for x in -2..BOARD_X {
+                for y in rows.start - 3..rows.end {
+                    let pos = Dot(x, y);
+                    let dots = view.dots.map(|dot| dot.with_original_dot(&pos));
+                    let fits = dots.iter().all(|dot| {
+                        rows.contains(&dot.1) && board.dot_in_board(dot) && board.occupied_dot(dot)
+                    });
+                    if !fits || (span_all && !rows.clone().all(|y| dots.iter().any(|d| d.1 == y))) {
+                        continue;
+                    }
+                    let mut rest = board.clone();
+                    for dot in &dots {
+                        rest.free_dot(dot);
+                    }
+                    if let Some(landed) = drop_position(&rest, &brick, x) {
+                        if landed.1 == y {
+                            result.push((brick, pos));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+/// replay a solution on the puzzle board by hard drops
+fn solves(puzzle: &Puzzle, solution: &[Placement]) -> bool {
+    let mut board = puzzle.board.clone();
+    let mut lines = 0;
+    for (brick, pos) in solution {
+        match drop_position(&board, brick, pos.0) {
+            Some(landed) if landed.1 == pos.1 => board.occupy_brick(brick, pos),
+            _ => return false,
+        };
+        lines += board.clean_lines();
+    }
+    match puzzle.objective {
+        Objective::Lines(n) => lines >= n,
+        Objective::PerfectClear => lines > 0 && board.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Problem;
+    use std::time::Duration;
+
+    /// `goal` lines cleared by hard drops of `bricks` in order, stacking no higher than `top`
+    fn drops_solve(board: &Board, bricks: &[Brick], top: i8, lines: u32, goal: u32) -> bool {
+        if lines >= goal {
+            return true;
+        }
+        let Some((brick, rest)) = bricks.split_first() else {
+            return false;
+        };
+        for rotation in 0..BRICK_VIEWS[brick.ty].len() {
+            let brick = Brick { rotation, ..*brick };
+            for x in -3..BOARD_X {
+                let Some(pos) = drop_position(board, &brick, x) else {
+                    continue;
+                };
+                let mut after = board.clone();
+                after.occupy_brick(&brick, &pos);
+                if after.height() > top {
+                    continue;
+                }
+                let cleared = after.clean_lines();
+                if drops_solve(&after, rest, top - cleared as i8, lines + cleared, goal) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn generated_puzzles_are_solved_with_their_bricks() {
+        for seed in 0..40 {
+            let puzzle = generate(seed).unwrap();
+            let solved = match puzzle.objective {
+                Objective::PerfectClear => {
+                    Problem::new(puzzle.board.clone(), puzzle.bricks.clone(), None, false)
+                        .first(Duration::from_secs(10))
+                        .is_some_and(|solution| solution.len() <= puzzle.bricks.len())
+                }
+                // carving empties at most two whole rows at the top of the ones to clear
+                Objective::Lines(n) => {
+                    let top = puzzle.board.height() + 2;
+                    drops_solve(&puzzle.board, &puzzle.bricks, top, 0, n)
+                }
+                _ => false,
+            };
+            assert!(solved, "seed {}: {}", seed, puzzle);
+        }
+    }
+
+    #[test]
+    fn generate_is_seeded() {
+        for seed in 0..10 {
+            assert_eq!(
+                generate(seed).unwrap().to_string(),
+                generate(seed).unwrap().to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! parts shared by the game, the server and the simulator binaries
+pub mod bot;
+pub mod bricks;
+pub mod consts;
+pub mod engine;
+pub mod env;
+pub mod garbage;
+pub mod generator;
+pub mod modes;
+pub mod movegen;
+pub mod protocol;
+pub mod puzzle;
+pub mod randomizer;
+pub mod replay;
+pub mod search;
+pub mod solver;
+pub mod tbp;
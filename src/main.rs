@@ -1,17 +1,36 @@
 #![windows_subsystem = "windows"]
-mod bricks;
-mod consts;
+mod autoplay;
+mod coop;
+mod mission;
+mod online;
+mod overlay;
+mod player;
+mod tutorial;
+mod versus;
 
 use bevy::prelude::*;
-use bevy_utils::Duration;
-use bricks::{Board, Brick, BrickView, Dot};
+use bevy_tetris::{bot, bricks, consts, engine, generator, modes, puzzle};
+use bot::{Bot, DIFFICULTIES};
+use bricks::{Board, BrickView, Dot};
 use consts::*;
+use engine::{Engine, Outcome};
+use mission::{MissionRun, MISSIONS};
+use modes::{GameMode, GAME_MODES, NES_MAX_START_LEVEL};
+use player::{spawn_player, KeyMap, Player};
+use puzzle::{Puzzle, PuzzlePack};
+use std::path::Path;
+use tutorial::{Progress, Tutorial};
+use versus::{Opponent, Versus};
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 enum GameState {
     #[default]
+    Menu,
+    Puzzles,
     Playing,
     GameOver,
+    Versus,
+    Coop,
 }
 
 fn main() {
@@ -25,53 +44,98 @@ fn main() {
             }),
             ..default()
         }))
-        .insert_resource(GameData::default())
+        .insert_resource(GameData {
+            address: arg("--addr").unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+            server: arg("--server").unwrap_or_else(|| DEFAULT_SERVER.to_string()),
+            room: arg("--room").map(|code| code.to_uppercase()),
+            bot: arg("--bot"),
+            ..default()
+        })
+        .init_resource::<PuzzleBrowser>()
+        .init_resource::<Versus>()
         .add_startup_system(setup_screen.in_base_set(StartupSet::PreStartup))
         .add_state::<GameState>()
-        .add_system(new_game_system.in_schedule(OnEnter(GameState::Playing)))
+        .add_system(menu_setup.in_schedule(OnEnter(GameState::Menu)))
+        .add_system(menu_system.in_set(OnUpdate(GameState::Menu)))
+        .add_system(autoplay::demo_system.in_set(OnUpdate(GameState::Menu)))
+        .add_system(puzzles_setup.in_schedule(OnEnter(GameState::Puzzles)))
+        .add_system(puzzles_system.in_set(OnUpdate(GameState::Puzzles)))
+        .add_system(player::layout_system)
         .add_systems(
             (
-                keyboard_system,
-                move_brick_system,
-                freeze_brick_system,
-                scoreboard_system,
+                autoplay::autoplay_system,
+                player::keyboard_system,
+                player::cpu_system,
+                player::step_system,
+                rules_system,
+                overlay::pc_overlay_system,
+                overlay::hint_overlay_system,
+                autoplay::demo_play_system,
+                player::draw_system,
+                player::text_system,
             )
+                .chain()
                 .in_set(OnUpdate(GameState::Playing)),
         )
         .add_system(game_over_setup.in_schedule(OnEnter(GameState::GameOver)))
         .add_system(game_over_system.in_set(OnUpdate(GameState::GameOver)))
+        .add_system(player::despawn_players.in_schedule(OnExit(GameState::GameOver)))
+        .add_system(versus::versus_setup.in_schedule(OnEnter(GameState::Versus)))
+        .add_systems(
+            (
+                player::keyboard_system,
+                player::cpu_system,
+                player::step_system,
+                versus::garbage_system,
+                versus::versus_system,
+                online::online_system,
+                player::draw_system,
+                player::text_system,
+                versus::versus_over_system,
+            )
+                .chain()
+                .in_set(OnUpdate(GameState::Versus)),
+        )
+        .add_systems(
+            (player::despawn_players, online::online_exit).in_schedule(OnExit(GameState::Versus)),
+        )
+        .add_system(coop::coop_setup.in_schedule(OnEnter(GameState::Coop)))
+        .add_systems(
+            (
+                coop::coop_step_system,
+                coop::coop_draw_system,
+                coop::coop_text_system,
+                coop::coop_over_system,
+            )
+                .chain()
+                .in_set(OnUpdate(GameState::Coop)),
+        )
+        .add_system(coop::coop_exit.in_schedule(OnExit(GameState::Coop)))
         .run();
 }
 
+/// value of a command line option as `--addr HOST:PORT`
+fn arg(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+}
+
 fn setup_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn(Camera2dBundle::default());
-    commands.spawn(SpriteBundle {
-        texture: asset_server.load("screen.png"),
-        ..default()
-    });
-    commands
-        .spawn(init_text(
-            "000000",
-            TEXT_SCORE_X,
-            TEXT_SCORE_Y,
-            &asset_server,
-        ))
-        .insert(ScoreText);
-    commands
-        .spawn(init_text(
-            "000000",
-            TEXT_LINES_X,
-            TEXT_LINES_Y,
-            &asset_server,
-        ))
-        .insert(LinesText);
     commands
-        .spawn(init_text("00", TEXT_LEVEL_X, TEXT_LEVEL_Y, &asset_server))
-        .insert(LevelText);
+        .spawn(SpriteBundle {
+            texture: asset_server.load("screen.png"),
+            ..default()
+        })
+        .insert(Background);
 }
 
+/// screen image with labels
 #[derive(Component)]
-struct DotBundle;
+struct Background;
+
 #[derive(Component)]
 struct BoardBundle;
 
@@ -82,248 +146,395 @@ struct BrickBoardBundle;
 struct BrickNextBundle;
 
 #[derive(Component)]
-struct ScoreText;
-
+struct GameOverText;
 #[derive(Component)]
-struct LinesText;
+struct MenuText;
 
-#[derive(Component)]
-struct LevelText;
-#[derive(Component)]
-struct GameOverText;
+fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, game: Res<GameData>) {
+    commands
+        .spawn(init_text(
+            &menu_text(&game),
+            TEXT_GAME_X,
+            TEXT_GAME_Y,
+            &asset_server,
+        ))
+        .insert(MenuText);
+}
 
-/// keyboard_system only handle keyboard input
-/// won't handle tick-tick falling
-fn keyboard_system(
+fn menu_system(
     mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<NextState<GameState>>,
     mut game: ResMut<GameData>,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut Transform), With<BrickBoardBundle>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<(Entity, &mut Text), With<MenuText>>,
 ) {
-    let Ok((moving_entity, mut transform)) = query.get_single_mut() else {
+    let Ok((entity, mut text)) = query.get_single_mut() else {
         return;
     };
-
+    let selected = GAME_MODES.iter().position(|m| *m == game.mode).unwrap();
     if keyboard_input.just_pressed(KeyCode::Up) {
-        let rotated = game.moving_brick.rotate();
-        if game.board.valid_brick(&rotated, &game.moving_pos) {
-            spawn_brick_board(&mut commands, rotated.into(), game.moving_pos);
-            game.moving_brick = rotated;
-            commands.entity(moving_entity).despawn_recursive();
+        game.mode = GAME_MODES[(selected + GAME_MODES.len() - 1) % GAME_MODES.len()];
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        game.mode = GAME_MODES[(selected + 1) % GAME_MODES.len()];
+    }
+    if game.mode == GameMode::Nes {
+        if keyboard_input.just_pressed(KeyCode::Left) {
+            game.start_level = game.start_level.saturating_sub(1);
+        }
+        if keyboard_input.just_pressed(KeyCode::Right) {
+            game.start_level = (game.start_level + 1).min(NES_MAX_START_LEVEL);
         }
     }
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        let mut valid = false;
-        while game
-            .board
-            .valid_brick(&game.moving_brick, &game.moving_pos.down())
-        {
-            game.moving_pos.move_down();
-            transform.translation.y -= consts::DOT_WIDTH_PX;
-            valid = true;
+    if game.mode == GameMode::Versus {
+        let opponents = Opponent::all(game.bot.is_some());
+        let count = opponents.len();
+        let selected = opponents.iter().position(|o| *o == game.opponent).unwrap();
+        if keyboard_input.just_pressed(KeyCode::Left) {
+            game.opponent = opponents[(selected + count - 1) % count];
         }
-        if valid {
-            // Trigger the falling timer immediately to spawn a new brick right away
-            let elapse = game.falling_timer.duration() - Duration::from_nanos(1);
-            game.falling_timer.set_elapsed(elapse);
+        if keyboard_input.just_pressed(KeyCode::Right) {
+            game.opponent = opponents[(selected + 1) % count];
         }
     }
+    text.sections[0].value = menu_text(&game);
 
-    // Speed up the falling timer when the down key is pressed
-    if keyboard_input.just_pressed(KeyCode::Down) {
-        let level = game.level;
-        game.falling_timer
-            .set_duration(Duration::from_secs_f32(get_speed(level) / 10.));
-    }
-    if keyboard_input.just_released(KeyCode::Down) {
-        let level = game.level;
-        game.falling_timer
-            .set_duration(Duration::from_secs_f32(get_speed(level)));
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        commands.entity(entity).despawn_recursive();
+        if game.mode == GameMode::Puzzle {
+            state.set(GameState::Puzzles);
+        } else if game.mode == GameMode::Versus {
+            state.set(GameState::Versus);
+        } else if game.mode == GameMode::Coop {
+            state.set(GameState::Coop);
+        } else {
+            let engine = game.reset();
+            spawn_player(&mut commands, &asset_server, 0, engine, Some(&SINGLE_KEYS));
+            state.set(GameState::Playing);
+        }
     }
+}
 
-    let just_pressed = keyboard_input.any_just_pressed(vec![KeyCode::Left, KeyCode::Right]);
-    if just_pressed {
-        game.keyboard_timer.reset();
+fn menu_text(game: &GameData) -> String {
+    let mut text = format!("{}\n\n", STRING_SELECT_MODE);
+    for mode in GAME_MODES {
+        let cursor = if mode == game.mode { "> " } else { "  " };
+        text += &format!("{}{}\n", cursor, mode.name());
     }
-    let ticked = game.keyboard_timer.tick(time.delta()).finished();
-    if !ticked && !just_pressed {
-        return;
+    if game.mode == GameMode::Nes {
+        text += &format!("\n  LEVEL < {:02} >\n", game.start_level);
     }
-
-    if keyboard_input.pressed(KeyCode::Left)
-        && game
-            .board
-            .valid_brick(&game.moving_brick, &game.moving_pos.left())
-    {
-        game.moving_pos.move_left();
-        transform.translation.x -= consts::DOT_WIDTH_PX;
+    if game.mode == GameMode::Versus {
+        text += &format!("\n  VS < {} >\n", game.opponent.name());
+        if matches!(game.opponent, Opponent::Host | Opponent::Join) {
+            text += &format!("  {}\n", game.address);
+        }
+        if game.opponent == Opponent::Server {
+            let room = game.room.as_deref().unwrap_or("NEW");
+            text += &format!("  {}\n  ROOM {}\n", game.server, room);
+        }
     }
+    text + "\nPRESS SPACE"
+}
 
-    if keyboard_input.pressed(KeyCode::Right)
-        && game
-            .board
-            .valid_brick(&game.moving_brick, &game.moving_pos.right())
-    {
-        game.moving_pos.move_right();
-        transform.translation.x += consts::DOT_WIDTH_PX;
+/// puzzle pack browser
+#[derive(Resource, Default)]
+struct PuzzleBrowser {
+    pack: PuzzlePack,
+    selected: usize,
+}
+
+/// puzzles shown at once in browser
+const PUZZLES_PER_PAGE: usize = 10;
+
+fn puzzles_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut browser: ResMut<PuzzleBrowser>,
+) {
+    browser.pack = PuzzlePack::load(PUZZLES_DIR, PUZZLES_PROGRESS_FILE);
+    for error in &browser.pack.errors {
+        warn!("puzzle {}", error);
     }
+    browser.selected = browser
+        .selected
+        .min(browser.pack.puzzles.len().saturating_sub(1));
+    commands
+        .spawn(init_text(
+            &puzzles_text(&browser),
+            TEXT_GAME_X,
+            TEXT_GAME_Y,
+            &asset_server,
+        ))
+        .insert(MenuText);
 }
 
-/// move_brick_system only handle tick-tick falling
-/// won't handle keyboard input
-fn move_brick_system(
-    //commands: Commands,
+fn puzzles_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<NextState<GameState>>,
     mut game: ResMut<GameData>,
-    time: Res<Time>,
-    mut query: Query<&mut Transform, With<BrickBoardBundle>>,
+    mut browser: ResMut<PuzzleBrowser>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<(Entity, &mut Text), With<MenuText>>,
 ) {
-    let ticked = game.falling_timer.tick(time.delta()).finished();
-    let Ok(mut transform) = query.get_single_mut() else {
+    let Ok((entity, mut text)) = query.get_single_mut() else {
         return;
     };
-    if !ticked {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        commands.entity(entity).despawn_recursive();
+        state.set(GameState::Menu);
         return;
     }
-
-    if game
-        .board
-        .valid_brick(&game.moving_brick, &game.moving_pos.down())
-    {
-        //after ticking, brick falling one line.
-        game.moving_pos.move_down();
-        transform.translation.y -= consts::DOT_WIDTH_PX;
-    } else {
-        //there is no space to fall, so freeze the brick.
-        let frozen_brick = game.moving_brick;
-        let frozen_pos = game.moving_pos;
-        game.board.occupy_brick(&frozen_brick, &frozen_pos);
-        game.freeze = true;
-        //if we destroy moving brick here.
-        //there is flash, when destroy brick ,and re-draw board.
-        //commands.entity(entity).despawn_recursive();
+    if keyboard_input.just_pressed(KeyCode::G) {
+        generate_puzzle(&mut browser);
     }
-}
-fn freeze_brick_system(
-    mut commands: Commands,
-    mut game: ResMut<GameData>,
-    mut brick: Query<Entity, With<BrickBoardBundle>>,
-    mut board: Query<Entity, With<BoardBundle>>,
-) {
-    if !game.freeze {
+    let count = browser.pack.puzzles.len();
+    if count == 0 {
         return;
     }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        browser.selected = (browser.selected + count - 1) % count;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        browser.selected = (browser.selected + 1) % count;
+    }
+    text.sections[0].value = puzzles_text(&browser);
 
-    //step 1. check: we can clean one line?
-    game.deleted_lines = game.board.clean_lines();
-
-    //destroy moving brick
-    if let Ok(entity) = brick.get_single_mut() {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        let (file, puzzle) = browser.pack.puzzles[browser.selected].clone();
+        game.reset();
+        let engine = puzzle.engine();
+        game.puzzle = Some((file, puzzle));
+        spawn_player(&mut commands, &asset_server, 0, engine, Some(&SINGLE_KEYS));
         commands.entity(entity).despawn_recursive();
+        state.set(GameState::Playing);
     }
-    //destroy board
-    if let Ok(entity) = board.get_single_mut() {
-        commands.entity(entity).despawn_recursive();
+}
+
+/// generate a random puzzle, export it into the puzzle folder and select it
+fn generate_puzzle(browser: &mut PuzzleBrowser) {
+    let seed = rand::random::<u64>();
+    let puzzle = match generator::generate(seed) {
+        Ok(puzzle) => puzzle,
+        Err(e) => {
+            warn!("generate puzzle: {}", e);
+            return;
+        }
+    };
+    let file = format!("random-{:016x}.txt", seed);
+    if let Err(e) = std::fs::write(Path::new(PUZZLES_DIR).join(&file), puzzle.to_string()) {
+        warn!("puzzle {}: {}", file, e);
+        return;
+    }
+    browser.pack = PuzzlePack::load(PUZZLES_DIR, PUZZLES_PROGRESS_FILE);
+    if let Some(i) = browser.pack.puzzles.iter().position(|(f, _)| *f == file) {
+        browser.selected = i;
     }
-    //redraw board
-    spawn_board(&mut commands, &game.board);
 }
 
-#[allow(clippy::type_complexity)]
-fn scoreboard_system(
-    mut commands: Commands,
+fn puzzles_text(browser: &PuzzleBrowser) -> String {
+    let pack = &browser.pack;
+    let mut text = format!(
+        "PUZZLES {}/{}\n\n",
+        pack.puzzles
+            .iter()
+            .filter(|(file, _)| pack.solved.contains(file))
+            .count(),
+        pack.puzzles.len()
+    );
+    let page = browser.selected / PUZZLES_PER_PAGE * PUZZLES_PER_PAGE;
+    for (i, (file, puzzle)) in pack
+        .puzzles
+        .iter()
+        .enumerate()
+        .skip(page)
+        .take(PUZZLES_PER_PAGE)
+    {
+        let cursor = if i == browser.selected { ">" } else { " " };
+        let solved = if pack.solved.contains(file) { "x" } else { " " };
+        text += &format!("{}[{}] {}\n", cursor, solved, puzzle.name);
+    }
+    text + "\nSPACE PLAY  G RANDOM\nESC BACK"
+}
+
+const SINGLE_KEYS: KeyMap = KeyMap {
+    left: &[KeyCode::Left],
+    right: &[KeyCode::Right],
+    soft_drop: &[KeyCode::Down],
+    hard_drop: &[KeyCode::Space],
+    rotate: &[KeyCode::Up],
+    rotate_back: &[KeyCode::Z],
+    hold: &[KeyCode::C, KeyCode::LShift],
+};
+
+/// rules of single player modes on top of the engine, and info beside the board
+fn rules_system(
     mut state: ResMut<NextState<GameState>>,
     mut game: ResMut<GameData>,
-    mut next_brick: Query<Entity, With<BrickNextBundle>>,
-    mut query: ParamSet<(
-        Query<&mut Text, With<ScoreText>>,
-        Query<&mut Text, With<LinesText>>,
-        Query<&mut Text, With<LevelText>>,
-    )>,
+    mut browser: ResMut<PuzzleBrowser>,
+    mut query: Query<&mut Player>,
 ) {
-    if game.deleted_lines > 0 {
-        game.score += get_score(game.level, game.deleted_lines);
-        game.lines += game.deleted_lines;
-        game.deleted_lines = 0;
-
-        let level = get_level(game.lines);
-        if game.level != level {
-            game.level = level;
-            game.falling_timer
-                .set_duration(Duration::from_secs_f32(get_speed(level)));
-        }
-        if let Ok(mut text) = query.p0().get_single_mut() {
-            text.sections[0].value = format!("{:06}", game.score);
-        }
-        if let Ok(mut text) = query.p1().get_single_mut() {
-            text.sections[0].value = format!("{:06}", game.lines);
-        }
-        if let Ok(mut text) = query.p2().get_single_mut() {
-            text.sections[0].value = format!("{:02}", game.level);
+    let Ok(mut player) = query.get_single_mut() else {
+        return;
+    };
+    let player = &mut *player;
+    for event in std::mem::take(&mut player.events) {
+        check_puzzle(&mut game, &mut player.engine, &mut browser, &event);
+        check_mission(&mut game, &mut player.engine, &event);
+        if check_tutorial(&mut game, player, &event) {
+            // events left are from the board before
+            break;
         }
     }
+    if game
+        .mission
+        .as_ref()
+        .is_some_and(|run| run.expired(&player.engine))
+    {
+        player.engine.end(Outcome::Failed);
+    }
+    player.info = info_text(&game, &player.engine);
 
-    //next moving brick
-    //step 1. generate new brick(using next_brick, and rand generate new next_brick)
-    //game.freeze = false;
-    if !game.freeze {
-        return;
+    if player.engine.is_over() {
+        //game over!
+        state.set(GameState::GameOver);
     }
+}
 
-    game.freeze = false;
-    game.score += SCORE_PER_DROP;
-    if let Ok(mut text) = query.p0().get_single_mut() {
-        text.sections[0].value = format!("{:06}", game.score);
+/// end the puzzle when its objective is met or failed
+fn check_puzzle(
+    game: &mut GameData,
+    engine: &mut Engine,
+    browser: &mut PuzzleBrowser,
+    event: &engine::Event,
+) {
+    let Some((file, puzzle)) = &game.puzzle else {
+        return;
+    };
+    match puzzle.objective.check(engine, event) {
+        Some(true) => {
+            if let Err(e) = browser.pack.mark_solved(file) {
+                warn!("save puzzle progress: {}", e);
+            }
+            engine.end(Outcome::Completed);
+        }
+        Some(false) => engine.end(Outcome::Failed),
+        None => {}
     }
+}
 
-    game.moving_pos = consts::BRICK_START_DOT;
-    game.moving_brick = game.next_brick;
-    game.next_brick = Brick::rand();
+/// go on to next mission, or end the game when all are done or one fails
+fn check_mission(game: &mut GameData, engine: &mut Engine, event: &engine::Event) {
+    let Some(run) = &mut game.mission else {
+        return;
+    };
+    match run.check(engine, event) {
+        Some(true) => engine.end(Outcome::Completed),
+        Some(false) => engine.end(Outcome::Failed),
+        None => {}
+    }
+}
 
-    if game.board.valid_brick(&game.moving_brick, &BRICK_START_DOT) {
-        //step 2.2 destroy next_brick
-        if let Ok(entity) = next_brick.get_single_mut() {
-            commands.entity(entity).despawn_recursive();
+/// go on with the tutorial, returns true when a new board is set up
+fn check_tutorial(game: &mut GameData, player: &mut Player, event: &engine::Event) -> bool {
+    let Some(tutorial) = &mut game.tutorial else {
+        return false;
+    };
+    match tutorial.check(&player.input, event) {
+        Progress::Playing => false,
+        Progress::NewBoard(engine) => {
+            player.set_engine(*engine);
+            true
         }
+        Progress::Finished => {
+            player.engine.end(Outcome::Completed);
+            false
+        }
+    }
+}
 
-        //step 3.1 draw new one in start point
-        spawn_brick_board(
-            &mut commands,
-            game.moving_brick.into(),
-            consts::BRICK_START_DOT,
-        );
-        //step 3.3 draw new next_brick
-        spawn_brick_next(&mut commands, game.next_brick.into());
-        // Reset the timer
-        game.falling_timer.reset();
+/// grade, puzzle objective, mission or tutorial step
+fn info_text(game: &GameData, engine: &Engine) -> String {
+    let mode = engine.mode;
+    if let Some(grade) = mode.grade(&engine.stats) {
+        format!(
+            "GRADE {}\nTIME {}",
+            grade,
+            format_time(engine.stats.frames, mode.fps())
+        )
+    } else if let Some((_, puzzle)) = &game.puzzle {
+        let hold = match (puzzle.hold, engine.hold_brick) {
+            (false, _) => String::new(),
+            (true, None) => "HOLD -".to_string(),
+            (true, Some(brick)) => format!("HOLD {}", brick.name()),
+        };
+        format!("{}\n{}", puzzle.objective.describe(), hold)
+    } else if let Some(run) = &game.mission {
+        run.describe(engine)
+    } else if let Some(tutorial) = &game.tutorial {
+        tutorial.text()
     } else {
-        //game over!
-        state.set(GameState::GameOver);
+        String::new()
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn game_over_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut board: Query<Entity, With<BoardBundle>>,
-    mut next_brick: Query<Entity, With<BrickNextBundle>>,
+    game: Res<GameData>,
+    player: Query<&Player>,
+    drawn: Query<
+        Entity,
+        Or<(
+            With<BoardBundle>,
+            With<BrickBoardBundle>,
+            With<BrickNextBundle>,
+            With<overlay::PcDots>,
+            With<overlay::HintDots>,
+        )>,
+    >,
 ) {
-    //destroy board
-    if let Ok(entity) = board.get_single_mut() {
-        commands.entity(entity).despawn_recursive();
-    }
-    //destroy next brick
-    if let Ok(entity) = next_brick.get_single_mut() {
+    let Ok(player) = player.get_single() else {
+        return;
+    };
+    let engine = &player.engine;
+    //destroy board, moving brick, next brick, perfect clear and hint shown
+    for entity in &drawn {
         commands.entity(entity).despawn_recursive();
     }
     //show GameOver
+    let mut msg = match (engine.outcome, &game.puzzle) {
+        (Some(Outcome::Completed), Some(_)) => STRING_PUZZLE_SOLVED.to_string(),
+        (_, Some(_)) => STRING_PUZZLE_FAILED.to_string(),
+        (Some(Outcome::Completed), None) => STRING_GAME_COMPLETED.to_string(),
+        _ => STRING_GAME_OVER.to_string(),
+    };
+    let stats = &engine.stats;
+    if let Some(grade) = engine.mode.grade(stats) {
+        //section times, as arcade shows after the game
+        let sections: String = stats
+            .section_frames
+            .iter()
+            .enumerate()
+            .map(|(i, frames)| {
+                format!(
+                    " {:03}-{:03} {}\n",
+                    i * 100,
+                    i * 100 + 99,
+                    format_time(*frames, engine.mode.fps())
+                )
+            })
+            .collect();
+        msg = format!(" GRADE {}\n\n{}\n{}", grade, sections, msg);
+    }
+    if let Some(run) = &game.mission {
+        msg = format!(" MISSIONS {}/{}\n\n{}", run.index, MISSIONS.len(), msg);
+    }
     commands
-        .spawn(init_text(
-            STRING_GAME_OVER,
-            TEXT_GAME_X,
-            TEXT_GAME_Y,
-            &asset_server,
-        ))
+        .spawn(init_text(&msg, TEXT_GAME_X, TEXT_GAME_Y, &asset_server))
         .insert(GameOverText);
 }
 fn game_over_system(
@@ -333,50 +544,27 @@ fn game_over_system(
     mut game_over: Query<Entity, With<GameOverText>>,
     keyboard_input: Res<Input<KeyCode>>,
 ) {
-    if !keyboard_input.pressed(KeyCode::Space) {
+    if !keyboard_input.just_pressed(KeyCode::Space) {
         return;
     }
 
-    game.reset();
-
     if let Ok(entity) = game_over.get_single_mut() {
         commands.entity(entity).despawn_recursive();
     }
-    state.set(GameState::Playing);
-}
-
-#[allow(clippy::type_complexity)]
-fn new_game_system(
-    mut commands: Commands,
-    game: ResMut<GameData>,
-    mut query: ParamSet<(
-        Query<&mut Text, With<ScoreText>>,
-        Query<&mut Text, With<LinesText>>,
-        Query<&mut Text, With<LevelText>>,
-    )>,
-) {
-    let moving_brick = game.moving_brick;
-    let next_brick = game.next_brick;
-    spawn_brick_board(&mut commands, moving_brick.into(), BRICK_START_DOT);
-    spawn_brick_next(&mut commands, next_brick.into());
-
-    if let Ok(mut text) = query.p0().get_single_mut() {
-        text.sections[0].value = format!("{:06}", game.score);
-    }
-    if let Ok(mut text) = query.p1().get_single_mut() {
-        text.sections[0].value = format!("{:06}", game.lines);
-    }
-    if let Ok(mut text) = query.p2().get_single_mut() {
-        text.sections[0].value = format!("{:02}", game.level);
+    //back to puzzle browser after a puzzle
+    if game.puzzle.take().is_some() {
+        state.set(GameState::Puzzles);
+    } else {
+        state.set(GameState::Menu);
     }
 }
 
-fn spawn_brick_next(commands: &mut Commands, brick: BrickView) {
+fn spawn_brick_next(commands: &mut Commands, brick: BrickView) -> Entity {
     commands
         .spawn(SpriteBundle {
             transform: Transform::from_xyz(
-                consts::NEXT_BRICK_LEFT_PX - consts::WINDOWS_WIDTH / 2.0,
-                consts::NEXT_BRICK_BOTTOM_PX - consts::WINDOWS_HEIGHT / 2.0,
+                consts::NEXT_BRICK_LEFT_PX,
+                consts::NEXT_BRICK_BOTTOM_PX,
                 0.0,
             ),
             ..default()
@@ -386,38 +574,38 @@ fn spawn_brick_next(commands: &mut Commands, brick: BrickView) {
             (0..4).for_each(|i| {
                 spawn_dot_as_child(parent, dot_to_vec2(&brick.dots[i]));
             });
-        });
+        })
+        .id()
 }
 
-fn spawn_board(commands: &mut Commands, board: &Board) {
+fn spawn_board(commands: &mut Commands, board: &Board) -> Entity {
     commands
         .spawn(SpriteBundle {
             //from middle pixel to pixel of (left,bottom)
             transform: Transform::from_xyz(
-                10.0 - consts::WINDOWS_WIDTH / 2.0 + consts::BOARD_LEFT_PX,
-                10.0 - consts::WINDOWS_HEIGHT / 2.0 + consts::BOARD_BOTTOM_PX,
+                10.0 + consts::BOARD_LEFT_PX,
+                10.0 + consts::BOARD_BOTTOM_PX,
                 0.0, //zero,which one pixel behind the UI-screen png; cannot be seen in screen
             ),
             ..default()
         })
         .insert(BoardBundle)
         .with_children(|parent| {
-            (0..consts::BOARD_X)
+            (0..board.width())
                 .flat_map(|a| (0..consts::BOARD_Y).map(move |b| Dot(a, b)))
                 .filter(|dot| board.occupied_dot(dot))
                 .for_each(|dot| spawn_dot_as_child(parent, dot_to_vec2(&dot)));
-        });
+        })
+        .id()
 }
 
-fn spawn_brick_board(commands: &mut Commands, brick: BrickView, dot_in_board: Dot) {
+fn spawn_brick_board(commands: &mut Commands, brick: BrickView, dot_in_board: Dot) -> Entity {
     commands
         .spawn(SpriteBundle {
             //from middle pixel to pixel of (left,bottom)
             transform: Transform::from_xyz(
-                dot_in_board.0 as f32 * consts::DOT_WIDTH_PX + 10.0 - consts::WINDOWS_WIDTH / 2.0
-                    + consts::BOARD_LEFT_PX,
-                dot_in_board.1 as f32 * consts::DOT_WIDTH_PX + 10.0 - consts::WINDOWS_HEIGHT / 2.0
-                    + consts::BOARD_BOTTOM_PX,
+                dot_in_board.0 as f32 * consts::DOT_WIDTH_PX + 10.0 + consts::BOARD_LEFT_PX,
+                dot_in_board.1 as f32 * consts::DOT_WIDTH_PX + 10.0 + consts::BOARD_BOTTOM_PX,
                 0.0, //zero,which one pixel behind the UI-screen png; cannot be seen in screen
             ),
             ..default()
@@ -427,7 +615,8 @@ fn spawn_brick_board(commands: &mut Commands, brick: BrickView, dot_in_board: Do
             (0..4).for_each(|i| {
                 spawn_dot_as_child(parent, dot_to_vec2(&brick.dots[i]));
             });
-        });
+        })
+        .id()
 }
 fn spawn_dot_as_child(commands: &mut ChildBuilder, trans: Vec2) {
     commands
@@ -482,48 +671,60 @@ fn init_text(msg: &str, x: f32, y: f32, asset_server: &Res<AssetServer>) -> Text
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct GameData {
-    board: Board,
-    moving_brick: Brick,
-    moving_pos: Dot,
-    next_brick: Brick,
-    freeze: bool,
-    deleted_lines: u32,
-    score: u32,
-    lines: u32,
-    level: u32,
-    keyboard_timer: Timer,
-    falling_timer: Timer,
+    /// mode selected in menu
+    mode: GameMode,
+    start_level: u32,
+    /// who plays against player 1 in versus
+    opponent: Opponent,
+    /// host:port to host or join online versus
+    address: String,
+    /// host:port of the server, src/bin/server.rs
+    server: String,
+    /// room code to join on the server, a new room when `None`
+    room: Option<String>,
+    /// command line of an external bot, src/tbp.rs
+    bot: Option<String>,
+    /// (file name, puzzle) being played
+    puzzle: Option<(String, Puzzle)>,
+    mission: Option<MissionRun>,
+    tutorial: Option<Tutorial>,
 }
 
 impl GameData {
-    fn reset(&mut self) {
-        self.board.clear();
-        self.freeze = false;
-        self.deleted_lines = 0;
-        self.score = 0;
-        self.lines = 0;
-        self.level = 0;
-        self.keyboard_timer = Timer::from_seconds(consts::TIMER_KEY_SECS, TimerMode::Repeating);
-        self.falling_timer = Timer::from_seconds(consts::TIMER_FALLING_SECS, TimerMode::Repeating);
+    /// the external bot, or the best computer player without one or when it doesn't start
+    fn computer(&self, seed: u64) -> Bot {
+        if let Some(command) = &self.bot {
+            match Bot::external(command, seed) {
+                Ok(bot) => return bot,
+                Err(e) => warn!("bot {}: {}", command, e),
+            }
+        }
+        Bot::new(&DIFFICULTIES[DIFFICULTIES.len() - 1], seed)
     }
-}
-impl Default for GameData {
-    fn default() -> Self {
-        Self {
-            board: Board::default(),
-            moving_brick: Brick::rand(),
-            moving_pos: consts::BRICK_START_DOT,
-            next_brick: Brick::rand(),
-            freeze: false,
-            keyboard_timer: Timer::from_seconds(consts::TIMER_KEY_SECS, TimerMode::Repeating),
-            falling_timer: Timer::from_seconds(consts::TIMER_FALLING_SECS, TimerMode::Repeating),
-            deleted_lines: 0,
-            score: 0,
-            lines: 0,
-            level: 0,
+
+    /// start a new game of the selected mode
+    fn reset(&mut self) -> Engine {
+        // start level is only selectable for NES
+        let start_level = if self.mode == GameMode::Nes {
+            self.start_level
+        } else {
+            0
+        };
+        let mut engine = Engine::new(self.mode, start_level, rand::random());
+        self.puzzle = None;
+        self.mission = (self.mode == GameMode::Mission).then(MissionRun::default);
+        self.tutorial = (self.mode == GameMode::Tutorial).then(|| Tutorial::load(TUTORIAL_DIR));
+        if let Some(tutorial) = &self.tutorial {
+            for error in &tutorial.errors {
+                warn!("tutorial {}", error);
+            }
+            if let Some(tutorial_engine) = tutorial.engine() {
+                engine = tutorial_engine;
+            }
         }
+        engine
     }
 }
 
@@ -532,26 +733,13 @@ fn dot_to_vec2(dot: &Dot) -> Vec2 {
     Vec2::new(DOT_WIDTH_PX * dot.0 as f32, DOT_WIDTH_PX * dot.1 as f32)
 }
 
-/// delay = 725 * .85 ^ level + level (ms)
-///
-/// use formula from dwhacks, http://gist.github.com/dwhacks/8644250
-#[inline]
-pub fn get_speed(level: u32) -> f32 {
-    consts::TIMER_FALLING_SECS * (0.85_f32).powi(level as i32) + level as f32 / 1000.0
-}
-
-/// use as [Original Nintendo Scoring System]
-///
-/// https://tetris.fandom.com/wiki/Scoring
-#[inline]
-pub fn get_score(level: u32, erase_lines: u32) -> u32 {
-    assert!(0 < erase_lines);
-    assert!(erase_lines <= 4);
-    vec![40, 100, 300, 1200][(erase_lines - 1) as usize] * (level + 1)
-}
-
-/// increase level every 10 lines.
-#[inline]
-pub fn get_level(total_lines: u32) -> u32 {
-    (total_lines / 10).min(99)
+/// mm:ss.cc as arcade games show
+fn format_time(frames: u32, fps: f64) -> String {
+    let centis = (frames as f64 / fps * 100.0) as u32;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
 }
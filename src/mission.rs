@@ -0,0 +1,340 @@
+use crate::engine::{Engine, Event};
+
+/// what a mission asks for, checked every time a brick freezes
+#[derive(Debug, Clone, Copy)]
+pub enum Goal {
+    /// clean exactly `lines` lines at once, by `brick` if given,
+    /// with the stack higher than `above` lines when the brick freezes
+    Clear {
+        lines: u32,
+        brick: Option<char>,
+        tspin: bool,
+        above: i8,
+    },
+    /// freeze bricks in a row without creating a hole
+    NoHoles,
+}
+
+/// when a mission fails
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    /// bricks frozen
+    Pieces(u32),
+    /// seconds of play
+    Seconds(u32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Mission {
+    pub goal: Goal,
+    /// goal met this many times
+    pub times: u32,
+    pub limit: Limit,
+}
+
+const fn clear(lines: u32, brick: Option<char>, above: i8, times: u32, limit: Limit) -> Mission {
+    Mission {
+        goal: Goal::Clear {
+            lines,
+            brick,
+            tspin: false,
+            above,
+        },
+        times,
+        limit,
+    }
+}
+
+/// missions played in order, getting harder, level goes up by one for each
+pub const MISSIONS: [Mission; 10] = [
+    clear(1, None, 0, 2, Limit::Pieces(15)),
+    Mission {
+        goal: Goal::NoHoles,
+        times: 5,
+        limit: Limit::Pieces(10),
+    },
+    clear(2, None, 0, 1, Limit::Seconds(60)),
+    clear(3, Some('L'), 0, 1, Limit::Pieces(25)),
+    Mission {
+        goal: Goal::NoHoles,
+        times: 10,
+        limit: Limit::Pieces(15),
+    },
+    clear(2, Some('I'), 12, 1, Limit::Pieces(50)),
+    clear(4, Some('I'), 0, 1, Limit::Seconds(90)),
+    Mission {
+        goal: Goal::Clear {
+            lines: 1,
+            brick: Some('T'),
+            tspin: true,
+            above: 0,
+        },
+        times: 1,
+        limit: Limit::Pieces(30),
+    },
+    clear(3, Some('J'), 6, 1, Limit::Pieces(25)),
+    clear(4, Some('I'), 0, 2, Limit::Seconds(150)),
+];
+
+const CLEAR_NAMES: [&str; 4] = ["SINGLE", "DOUBLE", "TRIPLE", "TETRIS"];
+
+impl Mission {
+    /// short text shown beside the board
+    pub fn describe(&self) -> String {
+        match self.goal {
+            Goal::Clear {
+                lines,
+                brick,
+                tspin,
+                above,
+            } => {
+                let mut text = CLEAR_NAMES[lines as usize - 1].to_string();
+                if tspin {
+                    text = format!("T-SPIN {}", text);
+                } else if let Some(brick) = brick {
+                    text += &format!(" WITH {}", brick);
+                }
+                if self.times > 1 {
+                    text += &format!(" x{}", self.times);
+                }
+                if above > 0 {
+                    text += &format!("\nSTACK ABOVE {}", above);
+                }
+                text
+            }
+            Goal::NoHoles => format!("{} BRICKS\nWITHOUT HOLES", self.times),
+        }
+    }
+}
+
+/// progress through [`MISSIONS`]
+#[derive(Debug, Default)]
+pub struct MissionRun {
+    /// current mission
+    pub index: usize,
+    /// times the goal of current mission is met
+    pub done: u32,
+    /// bricks frozen in current mission
+    pieces: u32,
+    /// frame current mission started
+    start: u32,
+    /// holes after the last brick froze
+    holes: u32,
+}
+
+impl MissionRun {
+    pub fn mission(&self) -> &'static Mission {
+        &MISSIONS[self.index.min(MISSIONS.len() - 1)]
+    }
+
+    /// mission, progress and what is left of the limit
+    pub fn describe(&self, engine: &Engine) -> String {
+        let mission = self.mission();
+        let left = match mission.limit {
+            Limit::Pieces(n) => format!("{} BRICKS", n.saturating_sub(self.pieces)),
+            Limit::Seconds(n) => {
+                let secs = (engine.stats.frames - self.start) as f64 / engine.mode.fps();
+                format!("{} SECS", (n as f64 - secs).ceil().max(0.0))
+            }
+        };
+        format!(
+            "MISSION {}/{}\n{}\n{}/{} {}",
+            self.index + 1,
+            MISSIONS.len(),
+            mission.describe(),
+            self.done,
+            mission.times,
+            left
+        )
+    }
+
+    /// `Some(true)` when all missions are done, `Some(false)` when current one fails.
+    /// Going to next mission raises engine level.
+    pub fn check(&mut self, engine: &mut Engine, event: &Event) -> Option<bool> {
+        let (brick, lines, tspin, height, holes) = match *event {
+            Event::Locked {
+                brick,
+                lines,
+                tspin,
+                height,
+                holes,
+                ..
+            } => (brick, lines, tspin, height, holes),
+            Event::Over => return Some(false),
+            _ => return None,
+        };
+        let mission = self.mission();
+        self.pieces += 1;
+        match mission.goal {
+            Goal::Clear {
+                lines: need,
+                brick: need_brick,
+                tspin: need_tspin,
+                above,
+            } => {
+                if lines == need
+                    && (need_brick.is_none() || need_brick == Some(brick.name()))
+                    && (tspin || !need_tspin)
+                    && height > above
+                {
+                    self.done += 1;
+                }
+            }
+            Goal::NoHoles if holes > self.holes => self.done = 0,
+            Goal::NoHoles => self.done += 1,
+        }
+        self.holes = holes;
+
+        if self.done >= mission.times {
+            self.index += 1;
+            if self.index == MISSIONS.len() {
+                return Some(true);
+            }
+            self.done = 0;
+            self.pieces = 0;
+            self.start = engine.stats.frames;
+            engine.stats.level = self.index as u32;
+            return None;
+        }
+        match mission.limit {
+            Limit::Pieces(n) if self.pieces >= n => Some(false),
+            _ => None,
+        }
+    }
+
+    /// time limit of current mission is over
+    pub fn expired(&self, engine: &Engine) -> bool {
+        match self.mission().limit {
+            Limit::Seconds(n) => {
+                (engine.stats.frames - self.start) as f64 >= n as f64 * engine.mode.fps()
+            }
+            Limit::Pieces(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::Brick;
+    use crate::modes::GameMode;
+
+    fn locked(name: char, lines: u32, height: i8, holes: u32) -> Event {
+        Event::Locked {
+            brick: Brick::from_name(name).unwrap(),
+            lines,
+            tspin: false,
+            perfect_clear: false,
+            height,
+            holes,
+        }
+    }
+
+    fn at(index: usize) -> (MissionRun, Engine) {
+        let run = MissionRun {
+            index,
+            ..MissionRun::default()
+        };
+        (run, Engine::new(GameMode::Mission, 0, 0))
+    }
+
+    #[test]
+    fn next_mission() {
+        let (mut run, mut engine) = at(0);
+        assert_eq!(run.check(&mut engine, &locked('O', 0, 2, 0)), None);
+        assert_eq!(run.check(&mut engine, &locked('O', 1, 2, 0)), None);
+        assert_eq!(run.done, 1);
+        assert_eq!(run.check(&mut engine, &locked('I', 1, 2, 0)), None);
+        assert_eq!((run.index, run.done, engine.stats.level), (1, 0, 1));
+        assert_eq!(run.check(&mut engine, &Event::Over), Some(false));
+    }
+
+    #[test]
+    fn holes_reset() {
+        let (mut run, mut engine) = at(1);
+        for _ in 0..3 {
+            run.check(&mut engine, &locked('O', 0, 2, 0));
+        }
+        assert_eq!(run.done, 3);
+        // a new hole starts over
+        run.check(&mut engine, &locked('S', 0, 3, 1));
+        assert_eq!(run.done, 0);
+        // holes already there don't count, fewer holes neither
+        run.check(&mut engine, &locked('O', 0, 3, 1));
+        run.check(&mut engine, &locked('I', 1, 2, 0));
+        assert_eq!(run.done, 2);
+    }
+
+    #[test]
+    fn brick_and_height() {
+        // triple with L
+        let (mut run, mut engine) = at(3);
+        run.check(&mut engine, &locked('J', 3, 4, 0));
+        run.check(&mut engine, &locked('L', 2, 4, 0));
+        assert_eq!(run.done, 0);
+        run.check(&mut engine, &locked('L', 3, 4, 0));
+        assert_eq!(run.index, 4);
+
+        // double with I above 12 lines
+        let (mut run, mut engine) = at(5);
+        run.check(&mut engine, &locked('I', 2, 12, 0));
+        assert_eq!(run.done, 0);
+        run.check(&mut engine, &locked('I', 2, 13, 0));
+        assert_eq!(run.index, 6);
+    }
+
+    #[test]
+    fn limits() {
+        let Limit::Pieces(pieces) = MISSIONS[0].limit else {
+            unreachable!()
+        };
+        let (mut run, mut engine) = at(0);
+        for _ in 1..pieces {
+            assert_eq!(run.check(&mut engine, &locked('O', 0, 2, 0)), None);
+        }
+        assert_eq!(run.check(&mut engine, &locked('O', 0, 2, 0)), Some(false));
+
+        let Limit::Seconds(secs) = MISSIONS[2].limit else {
+            unreachable!()
+        };
+        let (run, mut engine) = at(2);
+        engine.stats.frames = secs * 60 - 1;
+        assert!(!run.expired(&engine));
+        engine.stats.frames += 1;
+        assert!(run.expired(&engine));
+        // time limited missions don't count bricks
+        let (mut run, mut engine) = at(2);
+        for _ in 0..100 {
+            assert_eq!(run.check(&mut engine, &locked('O', 0, 2, 0)), None);
+        }
+    }
+
+    #[test]
+    fn all_missions() {
+        let mut run = MissionRun::default();
+        let mut engine = Engine::new(GameMode::Mission, 0, 0);
+        let mut result = None;
+        while result.is_none() {
+            let mission = *run.mission();
+            let event = match mission.goal {
+                Goal::Clear {
+                    lines,
+                    brick,
+                    tspin,
+                    above,
+                } => Event::Locked {
+                    brick: Brick::from_name(brick.unwrap_or('I')).unwrap(),
+                    lines,
+                    tspin,
+                    perfect_clear: false,
+                    height: above + 1,
+                    holes: 0,
+                },
+                Goal::NoHoles => locked('O', 0, 2, 0),
+            };
+            result = run.check(&mut engine, &event);
+        }
+        assert_eq!(result, Some(true));
+        assert_eq!(run.index, MISSIONS.len());
+    }
+}
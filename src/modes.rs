@@ -0,0 +1,755 @@
+use crate::bricks::{Brick, Dot};
+use crate::consts;
+use crate::randomizer::RandomizerKind;
+
+/// brick falls `rows` lines every `frames` frames
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Gravity {
+    pub rows: u32,
+    pub frames: u32,
+}
+
+impl Gravity {
+    /// 1G, one line every frame
+    pub const ONE_G: Gravity = Gravity { rows: 1, frames: 1 };
+
+    pub fn max(self, other: Gravity) -> Gravity {
+        if self.rows * other.frames >= other.rows * self.frames {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+/// delays of one level, in frames
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    /// entry delay between freezing a brick and spawning the next one
+    pub are: u32,
+    /// delay while cleaned lines are still shown on board
+    pub line_clear: u32,
+    /// delayed auto shift, frames holding left/right before auto repeat
+    pub das: u32,
+    /// auto repeat rate, frames between two auto repeated moves
+    pub arr: u32,
+    /// frames a landed brick waits before freezing.
+    /// `None` as original game: brick freezes when it fails to fall.
+    pub lock: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HardDrop {
+    /// drop and freeze at once
+    Firm,
+    /// drop only, brick still can move until it locks
+    Sonic,
+    /// no hard drop, as NES
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum GameMode {
+    /// the original game: dwhacks speed and Nintendo scoring
+    #[default]
+    Classic,
+    /// arcade master mode: gravity up to 20G, TGM grading
+    Master,
+    /// NES Tetris (1989) frame by frame
+    Nes,
+    /// puzzles loaded from files, slow gravity with lock delay and kicks
+    Puzzle,
+    /// missions in a row, as puzzle rules but level goes up by mission
+    Mission,
+    /// scripted lessons, as puzzle rules
+    Tutorial,
+    /// two players on one screen sending garbage, as puzzle rules
+    Versus,
+    /// two players on one wide board, as puzzle rules
+    Coop,
+}
+
+pub const GAME_MODES: [GameMode; 8] = [
+    GameMode::Classic,
+    GameMode::Master,
+    GameMode::Nes,
+    GameMode::Puzzle,
+    GameMode::Mission,
+    GameMode::Tutorial,
+    GameMode::Versus,
+    GameMode::Coop,
+];
+
+/// counters updated by game mode while playing
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub start_level: u32,
+    /// bricks spawned
+    pub pieces: u32,
+    /// frames since game start
+    pub frames: u32,
+    /// frames soft drop is held for current brick
+    pub soft: u32,
+    /// lines the current brick is pushed down by soft drop without a break
+    pub push_down: u32,
+    pub combo: u32,
+    /// frames used by each finished section (100 levels)
+    pub section_frames: Vec<u32>,
+    section_start: u32,
+    gm_checkpoints: u32,
+    gm_failed: bool,
+}
+
+impl Stats {
+    pub fn new(start_level: u32) -> Self {
+        Self {
+            level: start_level,
+            start_level,
+            ..Self::default()
+        }
+    }
+}
+
+// Master mode is modeled after TGM, see https://tetris.wiki/Tetris_The_Grand_Master
+
+/// (level, gravity in 1/256 G)
+const MASTER_GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// (level, ARE, line clear, DAS, lock), shrinking by section as TGM2 master
+const MASTER_TIMING: [(u32, u32, u32, u32, u32); 6] = [
+    (0, 25, 40, 14, 30),
+    (500, 25, 25, 8, 30),
+    (600, 25, 16, 8, 30),
+    (700, 16, 12, 8, 30),
+    (800, 12, 6, 8, 30),
+    (900, 12, 6, 6, 17),
+];
+
+pub const MASTER_MAX_LEVEL: u32 = 999;
+
+/// grades and the score needed
+const MASTER_GRADES: [(&str, u32); 18] = [
+    ("9", 0),
+    ("8", 400),
+    ("7", 800),
+    ("6", 1400),
+    ("5", 2000),
+    ("4", 3500),
+    ("3", 5500),
+    ("2", 8000),
+    ("1", 12000),
+    ("S1", 16000),
+    ("S2", 22000),
+    ("S3", 30000),
+    ("S4", 40000),
+    ("S5", 52000),
+    ("S6", 66000),
+    ("S7", 82000),
+    ("S8", 100000),
+    ("S9", 120000),
+];
+
+/// (level, score at least, frames at most) to keep the chance of GM
+const MASTER_GM_CHECKPOINTS: [(u32, u32, u32); 3] = [
+    (300, 12000, (4 * 60 + 15) * 60),
+    (500, 40000, (7 * 60 + 30) * 60),
+    (999, 126000, (13 * 60 + 30) * 60),
+];
+
+// NES mode, see https://tetris.wiki/Tetris_(NES,_Nintendo)
+
+pub const NES_FPS: f64 = 60.0988;
+
+/// frames per line falling, by level 0 - 29
+const NES_GRAVITY: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+
+/// highest level selectable in menu, A + 9 on NES
+pub const NES_MAX_START_LEVEL: u32 = 19;
+
+const NES_MAX_SCORE: u32 = 999_999;
+
+// Arika rotation system kicks one dot right, then one dot left, except I brick.
+const NO_KICKS: [Dot; 1] = [Dot(0, 0)];
+const ARS_KICKS: [Dot; 3] = [Dot(0, 0), Dot(1, 0), Dot(-1, 0)];
+const I: usize = 1;
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "CLASSIC",
+            GameMode::Master => "MASTER",
+            GameMode::Nes => "NES",
+            GameMode::Puzzle => "PUZZLE",
+            GameMode::Mission => "MISSION",
+            GameMode::Tutorial => "TUTORIAL",
+            GameMode::Versus => "VERSUS",
+            GameMode::Coop => "CO-OP",
+        }
+    }
+
+    /// frames per second the game runs at
+    pub fn fps(&self) -> f64 {
+        match self {
+            GameMode::Nes => NES_FPS,
+            _ => 60.0,
+        }
+    }
+
+    pub fn randomizer(&self) -> RandomizerKind {
+        match self {
+            GameMode::Classic => RandomizerKind::Uniform,
+            GameMode::Master | GameMode::Mission | GameMode::Versus | GameMode::Coop => {
+                RandomizerKind::History { rolls: 4 }
+            }
+            GameMode::Nes => RandomizerKind::Nes,
+            GameMode::Puzzle | GameMode::Tutorial => RandomizerKind::Fixed,
+        }
+    }
+
+    pub fn gravity(&self, level: u32) -> Gravity {
+        match self {
+            GameMode::Classic
+            | GameMode::Puzzle
+            | GameMode::Mission
+            | GameMode::Tutorial
+            | GameMode::Versus
+            | GameMode::Coop => Gravity {
+                rows: 1000,
+                frames: (get_speed(level) * 60_000.0).round() as u32,
+            },
+            GameMode::Master => {
+                let (_, g) = MASTER_GRAVITY
+                    .iter()
+                    .rev()
+                    .find(|(l, _)| *l <= level)
+                    .unwrap();
+                Gravity {
+                    rows: *g,
+                    frames: 256,
+                }
+            }
+            GameMode::Nes => Gravity {
+                rows: 1,
+                frames: NES_GRAVITY[(level as usize).min(NES_GRAVITY.len() - 1)],
+            },
+        }
+    }
+
+    pub fn soft_drop(&self, level: u32) -> Gravity {
+        let gravity = self.gravity(level);
+        match self {
+            GameMode::Classic
+            | GameMode::Puzzle
+            | GameMode::Mission
+            | GameMode::Tutorial
+            | GameMode::Versus
+            | GameMode::Coop => Gravity {
+                rows: gravity.rows * 10,
+                frames: gravity.frames,
+            },
+            GameMode::Master => gravity.max(Gravity::ONE_G),
+            GameMode::Nes => gravity.max(Gravity { rows: 1, frames: 2 }),
+        }
+    }
+
+    /// soft drop on a landed brick freezes it at once
+    pub fn soft_drop_locks(&self) -> bool {
+        matches!(self, GameMode::Master)
+    }
+
+    /// played alone to learn, where hints of the computer player may be shown
+    pub fn practice(&self) -> bool {
+        matches!(
+            self,
+            GameMode::Puzzle | GameMode::Mission | GameMode::Tutorial
+        )
+    }
+
+    pub fn hard_drop(&self) -> HardDrop {
+        match self {
+            GameMode::Classic
+            | GameMode::Puzzle
+            | GameMode::Mission
+            | GameMode::Tutorial
+            | GameMode::Versus
+            | GameMode::Coop => HardDrop::Firm,
+            GameMode::Master => HardDrop::Sonic,
+            GameMode::Nes => HardDrop::Disabled,
+        }
+    }
+
+    /// NES controller handling:
+    /// DAS only charges while a brick falls, a blocked shift keeps DAS charged,
+    /// soft drop must be pressed again for every brick and doesn't work with left/right.
+    pub fn nes_controls(&self) -> bool {
+        matches!(self, GameMode::Nes)
+    }
+
+    pub fn timing(&self, level: u32) -> Timing {
+        match self {
+            GameMode::Classic => Timing {
+                are: 0,
+                line_clear: 0,
+                das: consts::TIMER_KEY_FRAMES,
+                arr: consts::TIMER_KEY_FRAMES,
+                lock: None,
+            },
+            GameMode::Master => {
+                let (_, are, line_clear, das, lock) =
+                    MASTER_TIMING.iter().rev().find(|t| t.0 <= level).unwrap();
+                Timing {
+                    are: *are,
+                    line_clear: *line_clear,
+                    das: *das,
+                    arr: 1,
+                    lock: Some(*lock),
+                }
+            }
+            GameMode::Nes => Timing {
+                are: 10,
+                line_clear: 17,
+                das: 16,
+                arr: 6,
+                lock: None,
+            },
+            GameMode::Puzzle
+            | GameMode::Mission
+            | GameMode::Tutorial
+            | GameMode::Versus
+            | GameMode::Coop => Timing {
+                are: 0,
+                line_clear: 0,
+                das: 10,
+                arr: 2,
+                lock: Some(30),
+            },
+        }
+    }
+
+    /// ARE after a brick freezes with its lowest dot at line `bottom`
+    pub fn are(&self, level: u32, bottom: i8) -> u32 {
+        let are = self.timing(level).are;
+        match self {
+            // 10 frames in the bottom 2 lines, 2 more for every 4 lines above, up to 18
+            GameMode::Nes => are + 2 * ((bottom.max(0) as u32 + 2) / 4).min(4),
+            _ => are,
+        }
+    }
+
+    /// line clear delay of a brick freezes at `frame`
+    pub fn line_clear_delay(&self, level: u32, frame: u32) -> u32 {
+        let line_clear = self.timing(level).line_clear;
+        match self {
+            // the clear animation runs every 4 frames of the global counter, 17 - 20 frames
+            GameMode::Nes => line_clear + 3 - frame % 4,
+            _ => line_clear,
+        }
+    }
+
+    /// offsets tried in order when rotating brick
+    pub fn kicks(&self, brick: &Brick) -> &'static [Dot] {
+        match self {
+            GameMode::Master
+            | GameMode::Puzzle
+            | GameMode::Mission
+            | GameMode::Tutorial
+            | GameMode::Versus
+            | GameMode::Coop
+                if brick.ty != I =>
+            {
+                &ARS_KICKS
+            }
+            _ => &NO_KICKS,
+        }
+    }
+
+    pub fn on_spawn(&self, stats: &mut Stats) {
+        if *self == GameMode::Master
+            && stats.pieces > 0
+            && stats.level % 100 != 99
+            && stats.level != MASTER_MAX_LEVEL - 1
+        {
+            self.set_level(stats, stats.level + 1);
+        }
+        stats.pieces += 1;
+    }
+
+    /// `bravo` is true when the board is empty after cleaning lines
+    pub fn on_lock(&self, stats: &mut Stats, lines: u32, bravo: bool) {
+        match self {
+            GameMode::Classic
+            | GameMode::Puzzle
+            | GameMode::Mission
+            | GameMode::Tutorial
+            | GameMode::Versus
+            | GameMode::Coop => {
+                stats.score += consts::SCORE_PER_DROP;
+                if lines > 0 {
+                    stats.score += get_score(stats.level, lines);
+                    stats.lines += lines;
+                    // mission level goes up by mission instead
+                    if *self != GameMode::Mission {
+                        stats.level = get_level(stats.lines);
+                    }
+                }
+            }
+            GameMode::Master => {
+                if lines == 0 {
+                    stats.combo = 1;
+                    return;
+                }
+                // https://tetris.wiki/Scoring#Tetris_The_Grand_Master
+                stats.combo = stats.combo.max(1) + 2 * lines - 2;
+                let bravo = if bravo { 4 } else { 1 };
+                stats.score +=
+                    ((stats.level + lines).div_ceil(4) + stats.soft) * lines * stats.combo * bravo;
+                stats.lines += lines;
+                self.set_level(stats, (stats.level + lines).min(MASTER_MAX_LEVEL));
+            }
+            GameMode::Nes => {
+                // push down points, pushing one line gives nothing
+                if stats.push_down >= 2 {
+                    stats.score += stats.push_down - 1;
+                }
+                if lines > 0 {
+                    stats.score += get_score(stats.level, lines);
+                    stats.lines += lines;
+                    stats.level = get_nes_level(stats.start_level, stats.lines);
+                }
+                stats.score = stats.score.min(NES_MAX_SCORE);
+            }
+        }
+    }
+
+    fn set_level(&self, stats: &mut Stats, level: u32) {
+        if level / 100 != stats.level / 100 || level == MASTER_MAX_LEVEL {
+            stats
+                .section_frames
+                .push(stats.frames - stats.section_start);
+            stats.section_start = stats.frames;
+        }
+        for (checkpoint, score, frames) in MASTER_GM_CHECKPOINTS {
+            if stats.level < checkpoint && checkpoint <= level {
+                if stats.score >= score && stats.frames <= frames {
+                    stats.gm_checkpoints += 1;
+                } else {
+                    stats.gm_failed = true;
+                }
+            }
+        }
+        stats.level = level;
+    }
+
+    /// game ends without topping out
+    pub fn completed(&self, stats: &Stats) -> bool {
+        *self == GameMode::Master && stats.level >= MASTER_MAX_LEVEL
+    }
+
+    pub fn grade(&self, stats: &Stats) -> Option<&'static str> {
+        if *self != GameMode::Master {
+            return None;
+        }
+        if !stats.gm_failed && stats.gm_checkpoints == MASTER_GM_CHECKPOINTS.len() as u32 {
+            return Some("GM");
+        }
+        MASTER_GRADES
+            .iter()
+            .rev()
+            .find(|(_, score)| *score <= stats.score)
+            .map(|(grade, _)| *grade)
+    }
+}
+
+/// delay = 725 * .85 ^ level + level (ms)
+///
+/// use formula from dwhacks, http://gist.github.com/dwhacks/8644250
+#[inline]
+pub fn get_speed(level: u32) -> f32 {
+    consts::TIMER_FALLING_SECS * (0.85_f32).powi(level as i32) + level as f32 / 1000.0
+}
+
+/// use as [Original Nintendo Scoring System]
+///
+/// https://tetris.fandom.com/wiki/Scoring
+#[inline]
+pub fn get_score(level: u32, erase_lines: u32) -> u32 {
+    assert!(0 < erase_lines);
+    assert!(erase_lines <= 4);
+    [40, 100, 300, 1200][(erase_lines - 1) as usize] * (level + 1)
+}
+
+/// increase level every 10 lines.
+#[inline]
+pub fn get_level(total_lines: u32) -> u32 {
+    (total_lines / 10).min(99)
+}
+
+/// NES increases level first at `min(start * 10 + 10, max(100, start * 10 - 50))` lines,
+/// then every 10 lines.
+///
+/// https://tetris.wiki/Tetris_(NES,_Nintendo)#Level_transitions
+#[inline]
+pub fn get_nes_level(start_level: u32, total_lines: u32) -> u32 {
+    let first = (start_level * 10 + 10).min((start_level * 10).saturating_sub(50).max(100));
+    if total_lines < first {
+        start_level
+    } else {
+        start_level + 1 + (total_lines - first) / 10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: GameMode = GameMode::Master;
+
+    /// master game at `level` with a brick already spawned
+    fn master(level: u32) -> Stats {
+        Stats {
+            pieces: 1,
+            ..Stats::new(level)
+        }
+    }
+
+    #[test]
+    fn master_level_stops() {
+        for (level, after) in [
+            (0, 1),
+            (98, 99),
+            (99, 99),
+            (199, 199),
+            (997, 998),
+            (998, 998),
+        ] {
+            let mut stats = master(level);
+            MASTER.on_spawn(&mut stats);
+            assert_eq!(stats.level, after, "spawn at {}", level);
+        }
+        // the first brick doesn't count
+        let mut stats = Stats::new(0);
+        MASTER.on_spawn(&mut stats);
+        assert_eq!(stats.level, 0);
+        // only clears pass a stop
+        let mut stats = master(99);
+        MASTER.on_lock(&mut stats, 2, false);
+        assert_eq!(stats.level, 101);
+        let mut stats = master(998);
+        MASTER.on_lock(&mut stats, 4, false);
+        assert_eq!(stats.level, MASTER_MAX_LEVEL);
+        assert!(MASTER.completed(&stats));
+    }
+
+    #[test]
+    fn master_grades() {
+        for (score, grade) in [
+            (0, "9"),
+            (399, "9"),
+            (400, "8"),
+            (11999, "2"),
+            (12000, "1"),
+            (16000, "S1"),
+            (119_999, "S8"),
+            (120_000, "S9"),
+        ] {
+            let stats = Stats { score, ..master(0) };
+            assert_eq!(MASTER.grade(&stats), Some(grade), "score {}", score);
+        }
+        assert_eq!(GameMode::Classic.grade(&master(0)), None);
+    }
+
+    /// reach every GM checkpoint with `score` and `frames`, by clearing lines below it
+    fn checkpoints(scores: [u32; 3], frames: [u32; 3]) -> Stats {
+        let mut stats = master(0);
+        for (i, (level, _, _)) in MASTER_GM_CHECKPOINTS.iter().enumerate() {
+            stats.level = level - 1;
+            stats.score = scores[i];
+            stats.frames = frames[i];
+            MASTER.on_lock(&mut stats, 1, false);
+            assert_eq!(stats.level, *level);
+        }
+        stats
+    }
+
+    #[test]
+    fn master_gm_checkpoints() {
+        let scores = MASTER_GM_CHECKPOINTS.map(|(_, score, _)| score);
+        let frames = MASTER_GM_CHECKPOINTS.map(|(_, _, frames)| frames - 600);
+        let stats = checkpoints(scores, frames);
+        assert_eq!(stats.gm_checkpoints, 3);
+        assert_eq!(MASTER.grade(&stats), Some("GM"));
+
+        // too low a score at 300
+        let stats = checkpoints([scores[0] - 1000, scores[1], scores[2]], frames);
+        assert!(stats.gm_failed);
+        assert_ne!(MASTER.grade(&stats), Some("GM"));
+
+        // too slow at 500
+        let late = [frames[0], MASTER_GM_CHECKPOINTS[1].2 + 1, frames[2]];
+        let stats = checkpoints(scores, late);
+        assert!(stats.gm_failed);
+        assert_eq!(stats.gm_checkpoints, 2);
+        assert_ne!(MASTER.grade(&stats), Some("GM"));
+    }
+
+    #[test]
+    fn master_timing() {
+        let gravity = |level| MASTER.gravity(level);
+        assert_eq!(
+            gravity(0),
+            Gravity {
+                rows: 4,
+                frames: 256
+            }
+        );
+        assert_eq!(
+            gravity(199),
+            Gravity {
+                rows: 144,
+                frames: 256
+            }
+        );
+        // falls back at 200
+        assert_eq!(
+            gravity(200),
+            Gravity {
+                rows: 4,
+                frames: 256
+            }
+        );
+        // 1G at 251
+        assert_eq!(
+            gravity(251),
+            Gravity {
+                rows: 256,
+                frames: 256
+            }
+        );
+        // 20G from 500 on
+        for level in [500, 700, 999] {
+            assert_eq!(
+                gravity(level),
+                Gravity {
+                    rows: 20 * 256,
+                    frames: 256
+                }
+            );
+        }
+        for (level, are, line_clear, das, lock) in [
+            (0, 25, 40, 14, 30),
+            (499, 25, 40, 14, 30),
+            (500, 25, 25, 8, 30),
+            (700, 16, 12, 8, 30),
+            (899, 12, 6, 8, 30),
+            (900, 12, 6, 6, 17),
+        ] {
+            let timing = MASTER.timing(level);
+            assert_eq!(
+                (timing.are, timing.line_clear, timing.das, timing.lock),
+                (are, line_clear, das, Some(lock)),
+                "level {}",
+                level
+            );
+            assert_eq!(MASTER.are(level, 10), are);
+        }
+    }
+
+    #[test]
+    fn nes_levels() {
+        // first level-up after start*10+10 lines, then every 10
+        for start in [0, 5, 9] {
+            let first = start * 10 + 10;
+            assert_eq!(get_nes_level(start, 0), start);
+            assert_eq!(get_nes_level(start, first - 1), start);
+            assert_eq!(get_nes_level(start, first), start + 1);
+            assert_eq!(get_nes_level(start, first + 9), start + 1);
+            assert_eq!(get_nes_level(start, first + 10), start + 2);
+            assert_eq!(get_nes_level(start, first + 95), start + 10);
+        }
+        // from 10 on the first level-up is at max(100, start*10-50) lines
+        assert_eq!(get_nes_level(15, 99), 15);
+        assert_eq!(get_nes_level(15, 100), 16);
+        assert_eq!(get_nes_level(19, 139), 19);
+        assert_eq!(get_nes_level(19, 140), 20);
+        assert_eq!(get_nes_level(19, 150), 21);
+    }
+
+    #[test]
+    fn nes_gravity() {
+        let nes = GameMode::Nes;
+        for (level, frames) in [(0, 48), (9, 6), (10, 5), (19, 2), (28, 2), (29, 1), (40, 1)] {
+            assert_eq!(
+                nes.gravity(level),
+                Gravity { rows: 1, frames },
+                "level {}",
+                level
+            );
+        }
+        // soft drop is never slower than every other frame
+        assert_eq!(nes.soft_drop(0), Gravity { rows: 1, frames: 2 });
+        assert_eq!(nes.soft_drop(29), Gravity::ONE_G);
+    }
+
+    #[test]
+    fn nes_are() {
+        let nes = GameMode::Nes;
+        let timing = nes.timing(0);
+        assert_eq!((timing.are, timing.line_clear, timing.lock), (10, 17, None));
+        for (bottom, are) in [
+            (-1, 10),
+            (0, 10),
+            (1, 10),
+            (2, 12),
+            (5, 12),
+            (6, 14),
+            (13, 16),
+            (14, 18),
+            (19, 18),
+        ] {
+            assert_eq!(nes.are(0, bottom), are, "bottom {}", bottom);
+            assert_eq!(are, 10 + 2 * ((bottom.max(0) as u32 + 2) / 4).min(4));
+        }
+        // other modes ignore the height
+        assert_eq!(
+            GameMode::Classic.are(0, 19),
+            GameMode::Classic.timing(0).are
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use crate::bricks::{Board, Brick, Dot};
+use crate::engine;
+use crate::modes::GameMode;
+use std::collections::{HashSet, VecDeque};
+
+/// a key press moving a brick
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Move {
+    Left,
+    Right,
+    Rotate,
+    RotateBack,
+    /// soft drop held until the brick lands
+    SoftDrop,
+    /// drop and lock
+    HardDrop,
+}
+
+impl Move {
+    /// keys held for the move
+    pub fn input(self) -> engine::Input {
+        let mut input = engine::Input::default();
+        match self {
+            Move::Left => input.left = true,
+            Move::Right => input.right = true,
+            Move::Rotate => input.rotate = true,
+            Move::RotateBack => input.rotate_back = true,
+            Move::SoftDrop => input.soft_drop = true,
+            Move::HardDrop => input.hard_drop = true,
+        }
+        input
+    }
+}
+
+/// where a brick can lock, with the fewest moves from spawn to get there
+#[derive(Debug, Clone)]
+pub struct Reachable {
+    pub brick: Brick,
+    pub pos: Dot,
+    /// ends with [`Move::HardDrop`]
+    pub moves: Vec<Move>,
+}
+
+type State = (Brick, Dot);
+
+fn key((brick, pos): &State) -> (usize, i8, i8) {
+    (brick.rotation, pos.0, pos.1)
+}
+
+/// lowest position `brick` falls to from `pos`
+fn drop(board: &Board, brick: &Brick, mut pos: Dot) -> Dot {
+    while board.valid_brick(brick, &pos.down()) {
+        pos = pos.down();
+    }
+    pos
+}
+
+/// key presses a brick landed by soft drop gets before gravity locks it, `None` with lock delay.
+///
+/// Soft drop leaves less than a row of its own gravity to the next gravity tick.
+/// A frame goes to releasing soft drop, then every press needs a frame and a release,
+/// a press in the frame gravity ticks is still seen.
+fn tuck_presses(mode: GameMode, level: u32) -> Option<u32> {
+    if mode.timing(level).lock.is_some() {
+        return None;
+    }
+    let (soft, gravity) = (mode.soft_drop(level), mode.gravity(level));
+    if soft.rows >= soft.frames {
+        // may fall more than a row in the frame it lands, and lock at once
+        return Some(0);
+    }
+    let frames = (gravity.frames + 1)
+        .saturating_sub(soft.rows)
+        .div_ceil(gravity.rows);
+    Some(frames / 2)
+}
+
+/// moves from `state` and where they go, rotations use kicks of `mode`
+fn neighbours(board: &Board, state: &State, mode: GameMode, tucks: bool) -> Vec<(Move, State)> {
+    let (brick, pos) = *state;
+    let mut result = Vec::new();
+    for (mv, to) in [(Move::Left, pos.left()), (Move::Right, pos.right())] {
+        if board.valid_brick(&brick, &to) {
+            result.push((mv, (brick, to)));
+        }
+    }
+    for (mv, rotated) in [
+        (Move::Rotate, brick.rotate()),
+        (Move::RotateBack, brick.rotate_back()),
+    ] {
+        let kicked = mode
+            .kicks(&rotated)
+            .iter()
+            .map(|kick| kick.with_original_dot(&pos))
+            .find(|to| board.valid_brick(&rotated, to));
+        if let Some(to) = kicked {
+            result.push((mv, (rotated, to)));
+        }
+    }
+    if tucks {
+        let to = drop(board, &brick, pos);
+        if to.1 != pos.1 {
+            result.push((Move::SoftDrop, (brick, to)));
+        }
+    }
+    result
+}
+
+/// every placement `brick` entering at `start` can lock at, with the shortest moves to it.
+///
+/// Searched breadth first over moves with the rotation system of `mode`, including soft drop
+/// tucks and spins under overhangs. Without lock delay a brick landed by soft drop locks at
+/// the next gravity tick, so it only gets the presses there is time for at `level`.
+pub fn reachable(
+    board: &Board,
+    brick: Brick,
+    start: Dot,
+    mode: GameMode,
+    level: u32,
+) -> Vec<Reachable> {
+    let mut result = Vec::new();
+    let first = (brick, start);
+    if !board.valid_brick(&brick, &start) {
+        return result;
+    }
+    let landed = tuck_presses(mode, level);
+    let tucks = landed != Some(0);
+    // presses left before the brick locks, `None` while it falls or with lock delay
+    let mut seen = HashSet::from([(key(&first), None)]);
+    let mut placed = HashSet::new();
+    let mut queue = VecDeque::from([(first, None::<u32>, Vec::new())]);
+    while let Some((state, left, moves)) = queue.pop_front() {
+        // hard drop from here
+        let (brick, pos) = state;
+        let to = drop(board, &brick, pos);
+        if placed.insert(key(&(brick, to))) {
+            let mut moves: Vec<Move> = moves.clone();
+            if moves.last() == Some(&Move::SoftDrop) {
+                moves.pop();
+            }
+            moves.push(Move::HardDrop);
+            result.push(Reachable {
+                brick,
+                pos: to,
+                moves,
+            });
+        }
+        for (mv, next) in neighbours(board, &state, mode, tucks) {
+            let left = match (mv, left) {
+                (Move::SoftDrop, None) => landed,
+                (_, None) => None,
+                // gravity may have landed it again before a second soft drop
+                (Move::SoftDrop, Some(_)) | (_, Some(0)) => continue,
+                // off a ledge gravity takes it down, only dropping keeps the moves in step
+                (_, Some(_)) if board.valid_brick(&next.0, &next.1.down()) => Some(0),
+                (_, Some(n)) => Some(n - 1),
+            };
+            if seen.insert((key(&next), left)) {
+                let mut path = moves.clone();
+                path.push(mv);
+                queue.push_back((next, left, path));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickView;
+    use crate::consts::BRICK_START_DOT;
+    use crate::engine::{Engine, Phase};
+    use crate::modes::HardDrop;
+
+    const O: Brick = Brick { ty: 0, rotation: 0 };
+    const T: Brick = Brick { ty: 6, rotation: 0 };
+
+    fn board(dots: &[(i8, i8)]) -> Board {
+        let mut board = Board::default();
+        for (x, y) in dots {
+            board.occupy_dot(&Dot(*x, *y));
+        }
+        board
+    }
+
+    /// roof over columns 0 to 5 in line 2, an O fits under it
+    fn overhang() -> Board {
+        board(&(0..6).map(|x| (x, 2)).collect::<Vec<_>>())
+    }
+
+    /// T slot of a T-spin double in lines 0 and 1 at column 4, roofed at column 3
+    fn tslot() -> Board {
+        let mut dots = Vec::new();
+        for x in (0..10).filter(|x| *x != 4) {
+            dots.push((x, 0));
+        }
+        for x in (0..10).filter(|x| !(3..=5).contains(x)) {
+            dots.push((x, 1));
+        }
+        dots.extend([(0, 2), (1, 2), (2, 2), (3, 2)]);
+        board(&dots)
+    }
+
+    fn dots(placement: &Reachable) -> Vec<Dot> {
+        BrickView::from(placement.brick)
+            .dots
+            .iter()
+            .map(|dot| dot.with_original_dot(&placement.pos))
+            .collect()
+    }
+
+    /// some placement of `brick` has all dots under `line` and left of column `x`
+    fn tucked(board: &Board, brick: Brick, mode: GameMode, level: u32, x: i8, line: i8) -> bool {
+        reachable(board, brick, BRICK_START_DOT, mode, level)
+            .iter()
+            .any(|placement| dots(placement).iter().all(|d| d.0 < x && d.1 < line))
+    }
+
+    /// T fills both lines of the slot
+    fn tspin(board: &Board, mode: GameMode, level: u32) -> Option<Reachable> {
+        reachable(board, T, BRICK_START_DOT, mode, level)
+            .into_iter()
+            .find(|placement| {
+                let mut board = board.clone();
+                board.occupy_brick(&placement.brick, &placement.pos);
+                board.get_clean_lines().len() == 2
+            })
+    }
+
+    #[test]
+    fn tucks_and_spins_with_lock_delay() {
+        for mode in [GameMode::Puzzle, GameMode::Master, GameMode::Versus] {
+            assert!(tucked(&overhang(), O, mode, 0, 6, 2), "{}", mode.name());
+            let spin = tspin(&tslot(), mode, 0);
+            assert!(
+                spin.is_some_and(|spin| spin.moves.contains(&Move::SoftDrop)),
+                "{}",
+                mode.name()
+            );
+        }
+    }
+
+    #[test]
+    fn tucks_before_gravity_locks() {
+        for mode in [GameMode::Classic, GameMode::Nes] {
+            assert!(tucked(&overhang(), O, mode, 0, 6, 2), "{}", mode.name());
+            assert!(tspin(&tslot(), mode, 0).is_some(), "{}", mode.name());
+        }
+        // a row a frame lands and locks a brick at once
+        assert_eq!(tuck_presses(GameMode::Nes, 29), Some(0));
+        assert!(!tucked(&overhang(), O, GameMode::Nes, 29, 6, 2));
+        for placement in reachable(&tslot(), T, BRICK_START_DOT, GameMode::Nes, 29) {
+            assert!(!placement.moves.contains(&Move::SoftDrop));
+        }
+    }
+
+    /// press the moves one by one, each released for a frame. Gravity of `level` runs
+    /// from the first soft drop on, moves in the air are pressed without it.
+    fn replay(mode: GameMode, level: u32, board: &Board, brick: Brick, moves: &[Move]) -> Board {
+        let mut engine = Engine::with_bricks(mode, board.clone(), &[brick, brick], false);
+        engine.stats.level = level;
+        engine.gravity_paused = true;
+        let step = |engine: &mut Engine, input: engine::Input| {
+            engine.step(&input);
+            engine.take_events();
+        };
+        while engine.phase != Phase::Falling {
+            step(&mut engine, engine::Input::default());
+        }
+        for mv in moves {
+            if engine.stats.pieces == 2 {
+                break;
+            }
+            let can_fall = |engine: &Engine| {
+                engine
+                    .board
+                    .valid_brick(&engine.moving_brick, &engine.moving_pos.down())
+            };
+            match mv {
+                Move::SoftDrop => {
+                    engine.gravity_paused = false;
+                    while engine.stats.pieces == 1 && can_fall(&engine) {
+                        step(&mut engine, mv.input());
+                    }
+                }
+                // bricks only lock by soft drop where hard drop doesn't lock them
+                Move::HardDrop if mode.hard_drop() != HardDrop::Firm => {
+                    while engine.stats.pieces == 1 {
+                        step(&mut engine, Move::SoftDrop.input());
+                    }
+                }
+                _ => step(&mut engine, mv.input()),
+            }
+            step(&mut engine, engine::Input::default());
+        }
+        assert_eq!(engine.stats.pieces, 2, "{:?} locked", moves);
+        engine.board
+    }
+
+    fn assert_reached(mode: GameMode, level: u32, tucks_only: bool) {
+        for board in [Board::default(), overhang(), tslot()] {
+            for ty in 0..7 {
+                let brick = Brick { ty, rotation: 0 };
+                for placement in reachable(&board, brick, BRICK_START_DOT, mode, level) {
+                    if tucks_only && !placement.moves.contains(&Move::SoftDrop) {
+                        continue;
+                    }
+                    let mut expected = board.clone();
+                    expected.occupy_brick(&placement.brick, &placement.pos);
+                    expected.clean_lines();
+                    let played = replay(mode, level, &board, brick, &placement.moves);
+                    assert!(
+                        played == expected,
+                        "{} level {} {:?} at {:?} by {:?}",
+                        mode.name(),
+                        level,
+                        placement.brick,
+                        placement.pos,
+                        placement.moves
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn moves_reach_placements_in_engine() {
+        for mode in [GameMode::Puzzle, GameMode::Classic, GameMode::Nes] {
+            assert_reached(mode, 0, false);
+        }
+    }
+
+    #[test]
+    fn tucks_reach_placements_before_gravity_locks() {
+        for level in [0, 5, 10, 15, 20, 25] {
+            assert_reached(GameMode::Classic, level, true);
+        }
+        for level in [0, 9, 13, 16, 18, 19, 29] {
+            assert_reached(GameMode::Nes, level, true);
+        }
+    }
+}
@@ -0,0 +1,572 @@
+use crate::consts::*;
+use crate::engine::{self, Event};
+use crate::modes::GameMode;
+use crate::player::{spawn_player, Lockstep, Player};
+use crate::versus::{versus_engine, Versus};
+use crate::{init_text, GameState, SINGLE_KEYS};
+use bevy::prelude::*;
+use bevy_tetris::bricks::FNV_OFFSET;
+use bevy_tetris::protocol::{Connection, Message, PROTOCOL_VERSION, ROOM_PLAYERS};
+use std::collections::BTreeMap;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// frames a local input waits before it is played, so it reaches the peer in time
+const INPUT_DELAY: u32 = 4;
+/// frames between hashes of the boards sent to the peer
+const HASH_FRAMES: u32 = 60;
+const PING_SECS: f64 = 1.0;
+/// peer is gone when nothing arrives for this long
+const TIMEOUT_SECS: f64 = 5.0;
+/// seconds between tries to connect to the host
+const RETRY_SECS: f64 = 1.0;
+
+/// how the peer is found
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Role {
+    /// wait for the peer to connect, and choose the seed
+    Host,
+    /// connect to the host
+    Join,
+    /// meet in a room of the server (src/bin/server.rs), a new one or by code
+    Server { room: Option<String> },
+}
+
+enum Link {
+    Listening(TcpListener),
+    /// seconds until next try
+    Joining(f64),
+    Connected(Connection),
+    /// failed before connected
+    Failed,
+}
+
+/// versus with a peer over network.
+///
+/// Both peers run both boards from the same seed, and play a frame only when keys of
+/// both players for it are known (lockstep). Local keys are sent [`INPUT_DELAY`] frames
+/// ahead, so the peer usually has them in time. Garbage goes between boards frame by
+/// frame, so both peers see the same game, checked by hashes of the boards.
+/// Through the server, the same messages are relayed between the players of a room.
+#[derive(Resource)]
+pub struct Online {
+    role: Role,
+    address: String,
+    /// board of this side goes first in hashes
+    first: bool,
+    /// code and players of the room on the server
+    room: Option<(String, u32)>,
+    /// said READY to the server
+    ready: bool,
+    /// sent RESULT to the server
+    reported: bool,
+    link: Link,
+    /// (local, remote) once the match started
+    players: Option<(Entity, Entity)>,
+    /// next frame to play
+    frame: u32,
+    /// next frame to send local keys for
+    sent: u32,
+    /// seconds not yet given local keys
+    frame_time: f64,
+    local_inputs: BTreeMap<u32, engine::Input>,
+    remote_inputs: BTreeMap<u32, engine::Input>,
+    /// hashes by frame, waiting for the one of the other side
+    local_hashes: BTreeMap<u32, u64>,
+    remote_hashes: BTreeMap<u32, u64>,
+    /// seconds since setup
+    clock: f64,
+    last_ping: f64,
+    last_heard: f64,
+    /// round trip time
+    ping_ms: Option<u64>,
+    /// why the match ended before someone topped out
+    ended: Option<String>,
+}
+
+/// connection status and why the match ended
+#[derive(Component)]
+pub struct OnlineText;
+
+pub fn online_setup(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    role: Role,
+    address: &str,
+) {
+    let link = if role == Role::Host {
+        // listen on all interfaces at the port of the address
+        let port = address.rsplit(':').next().unwrap_or_default();
+        match TcpListener::bind(format!("0.0.0.0:{}", port)) {
+            Ok(listener) if listener.set_nonblocking(true).is_ok() => Link::Listening(listener),
+            Ok(_) => Link::Failed,
+            Err(e) => {
+                warn!("listen {}: {}", port, e);
+                Link::Failed
+            }
+        }
+    } else {
+        Link::Joining(0.0)
+    };
+    commands.insert_resource(Online::new(role, address, link));
+    commands
+        .spawn(init_text("", TEXT_GAME_X, TEXT_GAME_Y, asset_server))
+        .insert(OnlineText);
+}
+
+impl Online {
+    fn new(role: Role, address: &str, link: Link) -> Self {
+        Self {
+            first: role == Role::Host,
+            role,
+            address: address.to_string(),
+            room: None,
+            ready: false,
+            reported: false,
+            link,
+            players: None,
+            frame: 0,
+            sent: INPUT_DELAY,
+            frame_time: 0.0,
+            local_inputs: (0..INPUT_DELAY)
+                .map(|frame| (frame, engine::Input::default()))
+                .collect(),
+            remote_inputs: (0..INPUT_DELAY)
+                .map(|frame| (frame, engine::Input::default()))
+                .collect(),
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            clock: 0.0,
+            last_ping: 0.0,
+            last_heard: 0.0,
+            ping_ms: None,
+            ended: None,
+        }
+    }
+
+    fn send(&mut self, message: &Message) {
+        if let Link::Connected(connection) = &mut self.link {
+            connection.send(message);
+        }
+    }
+
+    /// accept the peer, or try to reach the host again
+    fn connect(&mut self, delta: f64) {
+        let stream = match &mut self.link {
+            Link::Listening(listener) => match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(_) => return,
+            },
+            Link::Joining(wait) => {
+                *wait -= delta;
+                if *wait > 0.0 {
+                    return;
+                }
+                *wait = RETRY_SECS;
+                let Some(addr) = self
+                    .address
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut a| a.next())
+                else {
+                    self.link = Link::Failed;
+                    return;
+                };
+                match TcpStream::connect_timeout(&addr, Duration::from_millis(500)) {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                }
+            }
+            Link::Connected(_) | Link::Failed => return,
+        };
+        match Connection::new(stream) {
+            Ok(connection) => {
+                self.link = Link::Connected(connection);
+                self.last_heard = self.clock;
+                match self.role.clone() {
+                    Role::Host => {
+                        // seed is echoed by the peer before the match starts
+                        let seed = rand::random();
+                        self.send(&Message::Hello {
+                            version: PROTOCOL_VERSION,
+                            seed,
+                        });
+                    }
+                    Role::Join => {}
+                    Role::Server { room } => {
+                        // the server gives the seed when everyone in the room is ready
+                        self.send(&Message::Hello {
+                            version: PROTOCOL_VERSION,
+                            seed: 0,
+                        });
+                        self.send(&match room {
+                            Some(code) => Message::Join(code),
+                            None => Message::Create,
+                        });
+                    }
+                }
+            }
+            Err(_) => self.link = Link::Failed,
+        }
+    }
+
+    fn status(&self) -> String {
+        if let Some(reason) = &self.ended {
+            return format!(" {}\n\nPRESS SPACE", reason);
+        }
+        match (&self.link, &self.role, &self.room) {
+            (Link::Failed, Role::Host, _) => " CANNOT LISTEN\n\nPRESS SPACE".to_string(),
+            (Link::Failed, _, _) => " BAD ADDRESS\n\nPRESS SPACE".to_string(),
+            (_, Role::Host, _) => format!("HOSTING\n{}\n\nESC BACK", self.address),
+            (_, _, None) => format!("JOINING\n{}\n\nESC BACK", self.address),
+            (_, _, Some((code, players))) => {
+                let ready = if self.ready { "READY" } else { "SPACE READY" };
+                format!(
+                    "ROOM {}\nPLAYERS {}/{}\n\n{}\nESC BACK",
+                    code, players, ROOM_PLAYERS, ready
+                )
+            }
+        }
+    }
+
+    /// a message of the peer or the server, the seed when the match starts
+    fn handle(&mut self, message: Message) -> Option<u64> {
+        match message {
+            Message::Hello { version, .. } if version != PROTOCOL_VERSION => {
+                self.ended = Some("VERSION MISMATCH".to_string());
+            }
+            Message::Hello { seed, .. } => match self.role {
+                Role::Host => return Some(seed),
+                Role::Join => {
+                    self.send(&message);
+                    return Some(seed);
+                }
+                Role::Server { .. } => {}
+            },
+            Message::Room { code, players } => self.room = Some((code, players)),
+            Message::Start { seed, slot } => {
+                self.first = slot == 0;
+                return Some(seed);
+            }
+            Message::Error(text) => self.ended = Some(text),
+            Message::Input { frame, keys } => {
+                self.remote_inputs
+                    .insert(frame, engine::Input::from_bits(keys));
+            }
+            Message::Hash { frame, hash } => {
+                self.remote_hashes.insert(frame, hash);
+                self.check_hash(frame);
+            }
+            Message::Ping(millis) => self.send(&Message::Pong(millis)),
+            Message::Pong(millis) => {
+                self.ping_ms = Some(((self.clock * 1000.0) as u64).saturating_sub(millis));
+            }
+            // the system ends the match on BYE
+            Message::Bye => {}
+            Message::Create | Message::Join(_) | Message::Ready | Message::Result(_) => {}
+        }
+        None
+    }
+
+    /// spawn both players of the match
+    fn start(&mut self, commands: &mut Commands, asset_server: &Res<AssetServer>, seed: u64) {
+        if self.players.is_some() {
+            return;
+        }
+        let local = spawn_player(
+            commands,
+            asset_server,
+            0,
+            versus_engine(seed),
+            Some(&SINGLE_KEYS),
+        );
+        let remote = spawn_player(commands, asset_server, 1, versus_engine(seed), None);
+        commands.entity(local).insert(Lockstep);
+        commands.entity(remote).insert(Lockstep);
+        self.players = Some((local, remote));
+        self.last_heard = self.clock;
+    }
+
+    /// send local keys for frames to come, [`INPUT_DELAY`] ahead of the frame played
+    fn send_inputs(&mut self, delta: f64, input: engine::Input) {
+        let frame_secs = 1.0 / GameMode::Versus.fps();
+        self.frame_time = (self.frame_time + delta).min(0.25);
+        while self.frame_time >= frame_secs && self.sent <= self.frame + INPUT_DELAY {
+            self.frame_time -= frame_secs;
+            let frame = self.sent;
+            self.local_inputs.insert(frame, input);
+            let keys = input.to_bits();
+            self.send(&Message::Input { frame, keys });
+            self.sent += 1;
+        }
+    }
+
+    /// play frames keys of both players are known for, sending hashes of the boards
+    fn play(&mut self, local: &mut Player, remote: &mut Player) {
+        while !local.engine.is_over() && !remote.engine.is_over() {
+            let frame = self.frame;
+            let (Some(local_input), Some(remote_input)) = (
+                self.local_inputs.get(&frame).copied(),
+                self.remote_inputs.get(&frame).copied(),
+            ) else {
+                break;
+            };
+            self.local_inputs.remove(&frame);
+            self.remote_inputs.remove(&frame);
+            local.input = local_input;
+            remote.input = remote_input;
+            let (local_start, remote_start) = (local.events.len(), remote.events.len());
+            local.step();
+            remote.step();
+            let attack = |events: &[Event]| -> u32 {
+                events
+                    .iter()
+                    .map(|event| match event {
+                        Event::Attack(lines) => *lines,
+                        _ => 0,
+                    })
+                    .sum()
+            };
+            let to_remote = attack(&local.events[local_start..]);
+            let to_local = attack(&remote.events[remote_start..]);
+            remote.engine.garbage.receive(to_remote);
+            local.engine.garbage.receive(to_local);
+            self.frame += 1;
+
+            if self.frame.is_multiple_of(HASH_FRAMES) {
+                let hash = self.hash(local, remote);
+                self.local_hashes.insert(self.frame, hash);
+                self.send(&Message::Hash {
+                    frame: self.frame,
+                    hash,
+                });
+                self.check_hash(self.frame);
+            }
+        }
+    }
+
+    /// hash of both boards, board of the first player first
+    fn hash(&self, local: &Player, remote: &Player) -> u64 {
+        let players = if self.first {
+            [local, remote]
+        } else {
+            [remote, local]
+        };
+        players
+            .iter()
+            .fold(FNV_OFFSET, |hash, player| player.engine.board.fnv(hash))
+    }
+
+    /// a desync when hashes of a frame from both sides differ
+    fn check_hash(&mut self, frame: u32) {
+        if let (Some(local), Some(remote)) = (
+            self.local_hashes.get(&frame),
+            self.remote_hashes.get(&frame),
+        ) {
+            if local != remote {
+                warn!("desync at frame {}", frame);
+                self.ended = Some("DESYNC".to_string());
+            }
+            self.local_hashes.remove(&frame);
+            self.remote_hashes.remove(&frame);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn online_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut state: ResMut<NextState<GameState>>,
+    online: Option<ResMut<Online>>,
+    mut versus: ResMut<Versus>,
+    mut players: Query<&mut Player>,
+    mut texts: Query<&mut Text, With<OnlineText>>,
+) {
+    let Some(mut online) = online else {
+        return;
+    };
+    let online = &mut *online;
+    let delta = time.delta_seconds_f64();
+    online.clock += delta;
+    online.connect(delta);
+
+    let mut messages = Vec::new();
+    let mut closed = false;
+    if let Link::Connected(connection) = &mut online.link {
+        messages = connection.receive();
+        closed = connection.closed;
+    }
+    if !messages.is_empty() {
+        online.last_heard = online.clock;
+    }
+    for message in messages {
+        if message == Message::Bye {
+            closed = true;
+        } else if let Some(seed) = online.handle(message) {
+            online.start(&mut commands, &asset_server, seed);
+        }
+    }
+
+    let started = online.players.is_some();
+    let over = versus.winner.is_some();
+    if online.ended.is_none() && !over {
+        if closed {
+            online.ended = Some("DISCONNECTED".to_string());
+        } else if started
+            && matches!(online.link, Link::Connected(_))
+            && online.clock - online.last_heard > TIMEOUT_SECS
+        {
+            online.ended = Some("TIMED OUT".to_string());
+        }
+    }
+    if online.ended.is_some() || matches!(online.link, Link::Failed) {
+        versus.winner.get_or_insert(None);
+    } else if !started && keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set(GameState::Menu);
+    } else if !started
+        && !online.ready
+        && online.room.is_some()
+        && keyboard_input.just_pressed(KeyCode::Space)
+    {
+        online.ready = true;
+        online.send(&Message::Ready);
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = if started && online.ended.is_none() {
+            String::new()
+        } else {
+            online.status()
+        };
+    }
+
+    let Some((local, remote)) = online.players else {
+        return;
+    };
+    if online.clock - online.last_ping >= PING_SECS {
+        online.last_ping = online.clock;
+        online.send(&Message::Ping((online.clock * 1000.0) as u64));
+    }
+    let Ok([mut local, mut remote]) = players.get_many_mut([local, remote]) else {
+        return;
+    };
+    local.events.clear();
+    remote.events.clear();
+    if let (Some(winner), Role::Server { .. }) = (versus.winner, &online.role) {
+        if online.ended.is_none() && !online.reported {
+            // winner by player index in the room, local player is 0 here
+            let first = online.first as u32;
+            let winner = winner.map(|slot| if slot == 0 { 1 - first } else { first });
+            online.reported = true;
+            online.send(&Message::Result(winner));
+        }
+    }
+    if online.ended.is_some() || over {
+        return;
+    }
+    let ping = online
+        .ping_ms
+        .map_or("-".to_string(), |ms| format!("{}MS", ms));
+    local.info += &format!("\nPING {}", ping);
+
+    online.send_inputs(delta, local.input);
+    online.play(&mut local, &mut remote);
+}
+
+/// say bye to the peer when leaving versus
+pub fn online_exit(
+    mut commands: Commands,
+    online: Option<ResMut<Online>>,
+    texts: Query<Entity, With<OnlineText>>,
+) {
+    if let Some(mut online) = online {
+        online.send(&Message::Bye);
+        commands.remove_resource::<Online>();
+    }
+    for entity in &texts {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_tetris::bricks::Dot;
+
+    /// one side of a match of seed 7: online, local and remote player
+    type Side = (Online, Player, Player);
+
+    fn side(role: Role, connection: Connection) -> Side {
+        (
+            Online::new(role, "", Link::Connected(connection)),
+            Player::new(0, versus_engine(7), None),
+            Player::new(1, versus_engine(7), None),
+        )
+    }
+
+    /// one frame on both sides, each holding its own keys
+    fn frame(sides: &mut [Side; 2], keys: [u8; 2]) {
+        for ((online, local, _), keys) in sides.iter_mut().zip(keys) {
+            local.input = engine::Input::from_bits(keys);
+            online.send_inputs(1.0 / GameMode::Versus.fps(), local.input);
+        }
+        for (online, local, remote) in sides.iter_mut() {
+            let Link::Connected(connection) = &mut online.link else {
+                unreachable!();
+            };
+            for message in connection.receive() {
+                online.handle(message);
+            }
+            online.play(local, remote);
+        }
+    }
+
+    #[test]
+    fn hashes_match_then_catch_desync() {
+        let (host, join) = Connection::pair();
+        let mut sides = [side(Role::Host, host), side(Role::Join, join)];
+        // a brick every 32 frames, to the left or right and turned, both sides the other way
+        let keys = |n: usize| {
+            let (left, right, rotate, drop) = (1, 2, 16, 8);
+            let (shift, other) = if n % 64 < 32 {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            match n % 32 {
+                0 | 2 => [shift, other],
+                4 => [rotate, rotate],
+                30 => [drop, drop],
+                _ => [0, 0],
+            }
+        };
+        let mut compared = 0;
+        for n in 0..HASH_FRAMES as usize * 4 {
+            frame(&mut sides, keys(n));
+            let [(a, a_local, a_remote), (b, b_local, b_remote)] = &sides;
+            if a.frame == b.frame {
+                assert_eq!(a.hash(a_local, a_remote), b.hash(b_local, b_remote));
+                compared += 1;
+            }
+            assert_eq!(a_local.engine.board, b_remote.engine.board);
+            assert_eq!(a_remote.engine.board, b_local.engine.board);
+        }
+        assert!(compared > 0);
+        for (online, local, remote) in &sides {
+            assert!(online.frame >= HASH_FRAMES * 3, "frame {}", online.frame);
+            assert_eq!(online.ended, None);
+            assert!(local.engine.stats.pieces > 1 && remote.engine.stats.pieces > 1);
+        }
+
+        // the peer sees another board of the host
+        sides[1].2.engine.board.occupy_dot(&Dot(0, BOARD_Y - 1));
+        for n in 0..HASH_FRAMES as usize * 2 {
+            frame(&mut sides, keys(n));
+        }
+        for (online, _, _) in &sides {
+            assert_eq!(online.ended.as_deref(), Some("DESYNC"));
+        }
+    }
+}
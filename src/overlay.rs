@@ -0,0 +1,243 @@
+use crate::player::Player;
+use crate::{dot_to_vec2, GameData};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::Duration;
+use bevy_tetris::bot::{self, Hint, WEIGHTS};
+use bevy_tetris::bricks::{Board, BrickView};
+use bevy_tetris::consts::{BACKGROUND, BOARD_BOTTOM_PX, BOARD_LEFT_PX, METER_WAITING};
+use bevy_tetris::engine::{Event, Phase};
+use bevy_tetris::solver::{self, Problem, Solution};
+use futures_lite::future;
+
+/// time to look for a perfect clear when a brick spawns
+const PC_BUDGET: Duration = Duration::from_millis(300);
+/// bricks after the moving one a solution may use, 4 lines and hold
+const PC_BRICKS: usize = 10;
+/// key going from no hint to its outline, to the outline with why, and back
+const HINT_KEY: KeyCode = KeyCode::H;
+
+/// perfect clear shown on the board of a puzzle, P toggles it
+#[derive(Component, Default)]
+pub struct PcOverlay {
+    /// `None` until the moving brick is searched for
+    solution: Option<Option<Solution>>,
+    /// search for the moving brick on the async compute pool, so frames go on meanwhile
+    task: Option<Task<Option<Solution>>>,
+}
+
+/// dots of the perfect clear shown, the next brick black and the others grey
+#[derive(Component)]
+pub struct PcDots;
+
+/// placement the computer player would choose for the moving brick,
+/// shown as an outline in practice modes
+#[derive(Component)]
+pub struct HintOverlay {
+    /// tell why in the info text too
+    explain: bool,
+    /// `None` until the moving brick is searched for
+    hint: Option<Option<Hint>>,
+}
+
+/// outline of the hint shown
+#[derive(Component)]
+pub struct HintDots;
+
+/// search a perfect clear for each brick while shown, and tell its bricks beside the board
+pub fn pc_overlay_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    game: Res<GameData>,
+    mut players: Query<(Entity, &mut Player, Option<&mut PcOverlay>)>,
+    drawn: Query<Entity, With<PcDots>>,
+) {
+    if game.puzzle.is_none() {
+        return;
+    }
+    let Ok((root, mut player, overlay)) = players.get_single_mut() else {
+        return;
+    };
+    let toggled = keyboard_input.just_pressed(KeyCode::P);
+    let Some(mut overlay) = overlay else {
+        if toggled {
+            commands.entity(root).insert(PcOverlay::default());
+        }
+        return;
+    };
+    if toggled {
+        // a search going on is cancelled as its task is dropped
+        commands.entity(root).remove::<PcOverlay>();
+        despawn(&mut commands, &drawn);
+        return;
+    }
+    let spawned = player
+        .events
+        .iter()
+        .any(|event| matches!(event, Event::Spawned | Event::Held));
+    let unsearched = overlay.solution.is_none() && overlay.task.is_none();
+    if (spawned || unsearched) && player.engine.phase == Phase::Falling {
+        despawn(&mut commands, &drawn);
+        let problem = Problem::from_engine(&player.engine, PC_BRICKS);
+        overlay.solution = None;
+        overlay.task =
+            Some(AsyncComputeTaskPool::get().spawn(async move { problem.first(PC_BUDGET) }));
+    }
+    if let Some(task) = &mut overlay.task {
+        if let Some(found) = future::block_on(future::poll_once(task)) {
+            overlay.task = None;
+            if let Some(found) = &found {
+                let dots = spawn_solution(&mut commands, &player.engine.board, found);
+                commands.entity(root).add_child(dots);
+            }
+            overlay.solution = Some(found);
+        }
+    }
+    let text = match &overlay.solution {
+        None => "PC ...".to_string(),
+        Some(None) => "NO PC".to_string(),
+        Some(Some(solution)) => {
+            let bricks: String = solution.iter().map(|step| step.brick.name()).collect();
+            format!("PC {}", bricks)
+        }
+    };
+    player.info = format!("{}\n{}", player.info, text);
+}
+
+/// ask the bot where the moving brick goes while hints are shown, H shows and hides them
+pub fn hint_overlay_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut players: Query<(Entity, &mut Player, Option<&HintOverlay>)>,
+    drawn: Query<Entity, With<HintDots>>,
+) {
+    let Ok((root, mut player, overlay)) = players.get_single_mut() else {
+        return;
+    };
+    if !player.engine.mode.practice() {
+        return;
+    }
+    let pressed = keyboard_input.just_pressed(HINT_KEY);
+    let (explain, mut hint) = match (overlay, pressed) {
+        (None, false) => return,
+        (None, true) => (false, None),
+        (Some(overlay), true) if overlay.explain => {
+            commands.entity(root).remove::<HintOverlay>();
+            despawn(&mut commands, &drawn);
+            return;
+        }
+        (Some(overlay), true) => (true, overlay.hint.clone()),
+        (Some(overlay), false) => (overlay.explain, overlay.hint.clone()),
+    };
+    let spawned = player
+        .events
+        .iter()
+        .any(|event| matches!(event, Event::Spawned | Event::Held));
+    let mut changed = false;
+    if player.engine.phase != Phase::Falling {
+        // the brick hinted at is frozen, its outline goes with it
+        if hint.is_some() {
+            despawn(&mut commands, &drawn);
+            hint = None;
+            changed = true;
+        }
+    } else if spawned || hint.is_none() {
+        despawn(&mut commands, &drawn);
+        let found = bot::hint(&player.engine, &*WEIGHTS);
+        if let Some(found) = &found {
+            let dots = spawn_hint(&mut commands, found);
+            commands.entity(root).add_child(dots);
+        }
+        hint = Some(found);
+        changed = true;
+    }
+    if pressed || changed {
+        commands.entity(root).insert(HintOverlay {
+            explain,
+            hint: hint.clone(),
+        });
+    }
+    match &hint {
+        Some(Some(hint)) if explain => {
+            player.info = format!("{}\n{}", player.info, why(hint));
+        }
+        Some(None) => player.info = format!("{}\nNO HINT", player.info),
+        _ => {}
+    }
+}
+
+/// what the hint does to the board, by the features the bot looks at
+fn why(hint: &Hint) -> String {
+    let (before, after) = (&hint.before, &hint.after);
+    let mut lines = Vec::new();
+    if hint.hold {
+        lines.push("HOLD FIRST".to_string());
+    }
+    lines.push(format!("LINES {}", after.lines));
+    lines.push(format!("HOLES {:+}", after.holes - before.holes));
+    lines.push(format!("BUMPS {:+}", after.bumpiness - before.bumpiness));
+    lines.join("\n")
+}
+
+fn despawn<T: Component>(commands: &mut Commands, drawn: &Query<Entity, With<T>>) {
+    for entity in drawn {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// steps after a line clear are drawn above the lines they clear
+fn spawn_solution(commands: &mut Commands, board: &Board, solution: &Solution) -> Entity {
+    commands
+        .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+            10.0 + BOARD_LEFT_PX,
+            10.0 + BOARD_BOTTOM_PX,
+            0.0,
+        )))
+        .insert(PcDots)
+        .with_children(|parent| {
+            for (i, dots) in solver::board_dots(board, solution).iter().enumerate() {
+                let color = if i == 0 { Color::BLACK } else { METER_WAITING };
+                for dot in dots {
+                    let trans = dot_to_vec2(dot);
+                    // under the moving brick
+                    parent.spawn(square(trans, 0.05, 12.0, color));
+                }
+            }
+        })
+        .id()
+}
+
+fn spawn_hint(commands: &mut Commands, hint: &Hint) -> Entity {
+    let placement = &hint.placement;
+    commands
+        .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+            10.0 + BOARD_LEFT_PX,
+            10.0 + BOARD_BOTTOM_PX,
+            0.0,
+        )))
+        .insert(HintDots)
+        .with_children(|parent| {
+            for dot in BrickView::from(placement.brick).dots {
+                let trans = dot_to_vec2(&dot.with_original_dot(&placement.pos));
+                // a frame of the dot, under the moving brick and a perfect clear shown
+                parent
+                    .spawn(square(trans, 0.03, 20.0, METER_WAITING))
+                    .with_children(|parent| {
+                        parent.spawn(square(Vec2::ZERO, 0.01, 16.0, BACKGROUND));
+                    });
+            }
+        })
+        .id()
+}
+
+fn square(trans: Vec2, z: f32, width: f32, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        transform: Transform::from_xyz(trans.x, trans.y, z),
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::new(width, width)),
+            ..default()
+        },
+        ..default()
+    }
+}
@@ -0,0 +1,349 @@
+use crate::bot::Bot;
+use crate::consts::*;
+use crate::engine::{self, Engine, Phase};
+use crate::modes::GameMode;
+use crate::{
+    init_text, spawn_board, spawn_brick_board, spawn_brick_next, Background, BoardBundle,
+    BrickBoardBundle, BrickNextBundle,
+};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::window::PrimaryWindow;
+
+/// keys of one player, any of them works
+pub struct KeyMap {
+    pub left: &'static [KeyCode],
+    pub right: &'static [KeyCode],
+    pub soft_drop: &'static [KeyCode],
+    pub hard_drop: &'static [KeyCode],
+    pub rotate: &'static [KeyCode],
+    pub rotate_back: &'static [KeyCode],
+    pub hold: &'static [KeyCode],
+}
+
+impl KeyMap {
+    pub fn input(&self, keyboard_input: &Input<KeyCode>) -> engine::Input {
+        let pressed = |keys: &[KeyCode]| keyboard_input.any_pressed(keys.iter().copied());
+        engine::Input {
+            left: pressed(self.left),
+            right: pressed(self.right),
+            soft_drop: pressed(self.soft_drop),
+            hard_drop: pressed(self.hard_drop),
+            rotate: pressed(self.rotate),
+            rotate_back: pressed(self.rotate_back),
+            hold: pressed(self.hold),
+        }
+    }
+}
+
+/// a board in play, with its own engine and timers.
+///
+/// The entity is the render root: board and bricks are drawn as its children,
+/// placed on screen `slot` by [`layout_system`].
+#[derive(Component)]
+pub struct Player {
+    /// screen from the left, screens are side by side
+    pub slot: usize,
+    pub engine: Engine,
+    /// `None` when not played by keyboard
+    pub keys: Option<&'static KeyMap>,
+    pub input: engine::Input,
+    /// seconds not yet run as engine frames
+    frame_time: f64,
+    /// engine events of the frames run in this update
+    pub events: Vec<engine::Event>,
+    /// shown in the info text, set by the rules of the game
+    pub info: String,
+    /// board need to be redrawn
+    freeze: bool,
+    /// moving brick need to be redrawn
+    moved: bool,
+    /// next brick need to be redrawn
+    spawned: bool,
+}
+
+impl Player {
+    pub fn new(slot: usize, engine: Engine, keys: Option<&'static KeyMap>) -> Self {
+        Self {
+            slot,
+            engine,
+            keys,
+            input: engine::Input::default(),
+            frame_time: 0.0,
+            events: Vec::new(),
+            info: String::new(),
+            freeze: true,
+            moved: false,
+            spawned: false,
+        }
+    }
+
+    /// play on another engine, everything is redrawn
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.freeze = true;
+        self.moved = true;
+        self.spawned = true;
+    }
+
+    /// run the engine one frame with `input`, keeping its events
+    pub fn step(&mut self) {
+        self.engine.step(&self.input);
+        for event in self.engine.take_events() {
+            match event {
+                engine::Event::Spawned | engine::Event::Held => {
+                    self.moved = true;
+                    self.spawned = true;
+                }
+                engine::Event::Moved | engine::Event::Locked { .. } => self.moved = true,
+                engine::Event::BoardChanged => self.freeze = true,
+                engine::Event::Attack(_) | engine::Event::Over => {}
+            }
+            self.events.push(event);
+        }
+    }
+}
+
+/// player stepped frame by frame with a peer over network, instead of by time
+#[derive(Component)]
+pub struct Lockstep;
+
+/// player played by the computer
+#[derive(Component)]
+pub struct Cpu(pub Bot);
+
+/// score texts of a player
+#[derive(Component)]
+pub enum PlayerText {
+    Score,
+    Lines,
+    Level,
+    Info,
+}
+
+/// bar of garbage received beside the board, black when it is out of delay
+#[derive(Component)]
+pub struct GarbageMeter {
+    ready: bool,
+}
+
+/// player a text belongs to, UI texts can't be children of sprites
+#[derive(Component)]
+pub struct Owner(pub Entity);
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    slot: usize,
+    engine: Engine,
+    keys: Option<&'static KeyMap>,
+) -> Entity {
+    let player = commands
+        .spawn(SpatialBundle::default())
+        .insert(Player::new(slot, engine, keys))
+        .id();
+    commands.entity(player).with_children(|parent| {
+        // first screen has the background of menu
+        if slot > 0 {
+            parent.spawn(SpriteBundle {
+                texture: asset_server.load("screen.png"),
+                transform: Transform::from_xyz(WINDOWS_WIDTH / 2.0, WINDOWS_HEIGHT / 2.0, 0.0),
+                ..default()
+            });
+        }
+        for (ready, color) in [(false, METER_WAITING), (true, Color::BLACK)] {
+            parent
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::ZERO),
+                        anchor: Anchor::BottomLeft,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(
+                        METER_LEFT_PX,
+                        BOARD_BOTTOM_PX,
+                        if ready { 0.2 } else { 0.1 },
+                    ),
+                    ..default()
+                })
+                .insert(GarbageMeter { ready });
+        }
+    });
+    let left = slot as f32 * WINDOWS_WIDTH;
+    for (text, x, y) in [
+        (PlayerText::Score, TEXT_SCORE_X, TEXT_SCORE_Y),
+        (PlayerText::Lines, TEXT_LINES_X, TEXT_LINES_Y),
+        (PlayerText::Level, TEXT_LEVEL_X, TEXT_LEVEL_Y),
+        (PlayerText::Info, TEXT_INFO_X, TEXT_INFO_Y),
+    ] {
+        commands
+            .spawn(init_text("", x + left, y, asset_server))
+            .insert((text, Owner(player)));
+    }
+    player
+}
+
+/// despawn all players and their texts
+#[allow(clippy::type_complexity)]
+pub fn despawn_players(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Player>, With<Owner>)>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// left bottom corner of the screen `slot` of `screens`,
+/// screens are side by side
+fn screen_origin(slot: usize, screens: usize) -> Vec2 {
+    Vec2::new(
+        (slot as f32 - screens as f32 / 2.0) * WINDOWS_WIDTH,
+        -WINDOWS_HEIGHT / 2.0,
+    )
+}
+
+/// widen the window to a screen for each player when players come and go
+pub fn layout_system(
+    added: Query<(), Added<Player>>,
+    mut removed: RemovedComponents<Player>,
+    mut players: Query<(&Player, &mut Transform), Without<Background>>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut background: Query<&mut Transform, With<Background>>,
+) {
+    if added.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+    let screens = players
+        .iter()
+        .map(|(player, _)| player.slot + 1)
+        .max()
+        .unwrap_or(1);
+    if let Ok(mut window) = window.get_single_mut() {
+        window
+            .resolution
+            .set(WINDOWS_WIDTH * screens as f32, WINDOWS_HEIGHT);
+    }
+    for mut transform in &mut background {
+        transform.translation.x = screen_origin(0, screens).x + WINDOWS_WIDTH / 2.0;
+    }
+    for (player, mut transform) in &mut players {
+        transform.translation = screen_origin(player.slot, screens).extend(0.0);
+    }
+}
+
+/// keyboard_system only handle keyboard input
+/// won't handle tick-tick falling
+pub fn keyboard_system(keyboard_input: Res<Input<KeyCode>>, mut players: Query<&mut Player>) {
+    for mut player in &mut players {
+        if let Some(keys) = player.keys {
+            player.input = keys.input(&keyboard_input);
+        }
+    }
+}
+
+pub fn cpu_system(mut players: Query<(&mut Player, &mut Cpu)>) {
+    for (mut player, mut cpu) in &mut players {
+        player.input = cpu.0.input(&player.engine);
+    }
+}
+
+/// run each engine for frames elapsed, keeping its events for the rules of the game
+pub fn step_system(time: Res<Time>, mut players: Query<&mut Player, Without<Lockstep>>) {
+    for mut player in &mut players {
+        player.events.clear();
+        let frame_secs = 1.0 / player.engine.mode.fps();
+        // don't catch up too many frames, e.g. after window was dragged
+        player.frame_time = (player.frame_time + time.delta_seconds_f64()).min(0.25);
+        while player.frame_time >= frame_secs {
+            player.frame_time -= frame_secs;
+            player.step();
+        }
+    }
+}
+
+/// redraw what changed on each board
+#[allow(clippy::type_complexity)]
+pub fn draw_system(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut Player)>,
+    drawn: Query<
+        (
+            Entity,
+            &Parent,
+            Option<&BoardBundle>,
+            Option<&BrickBoardBundle>,
+            Option<&BrickNextBundle>,
+        ),
+        Or<(
+            With<BoardBundle>,
+            With<BrickBoardBundle>,
+            With<BrickNextBundle>,
+        )>,
+    >,
+    mut meters: Query<(&Parent, &GarbageMeter, &mut Sprite)>,
+) {
+    for (root, mut player) in &mut players {
+        let (freeze, moved, spawned) = (player.freeze, player.moved, player.spawned);
+        for (entity, parent, board, brick, next) in &drawn {
+            if parent.get() == root
+                && ((freeze && board.is_some())
+                    || (moved && brick.is_some())
+                    || (spawned && next.is_some()))
+            {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        let engine = &player.engine;
+        let mut children = Vec::new();
+        if freeze {
+            children.push(spawn_board(&mut commands, &engine.board));
+        }
+        if moved && engine.phase == Phase::Falling {
+            children.push(spawn_brick_board(
+                &mut commands,
+                engine.moving_brick.into(),
+                engine.moving_pos,
+            ));
+        }
+        if let (true, Some(brick)) = (spawned, engine.next_brick) {
+            children.push(spawn_brick_next(&mut commands, brick.into()));
+        }
+        commands.entity(root).push_children(&children);
+
+        let garbage = &player.engine.garbage;
+        for (parent, meter, mut sprite) in &mut meters {
+            if parent.get() == root {
+                let lines = if meter.ready {
+                    garbage.ready()
+                } else {
+                    garbage.pending()
+                };
+                let height = lines.min(BOARD_Y_VALID as u32) as f32 * DOT_WIDTH_PX;
+                sprite.custom_size = Some(Vec2::new(METER_WIDTH_PX, height));
+            }
+        }
+        player.freeze = false;
+        player.moved = false;
+        player.spawned = false;
+    }
+}
+
+pub fn text_system(players: Query<&Player>, mut texts: Query<(&mut Text, &Owner, &PlayerText)>) {
+    for (mut text, owner, kind) in &mut texts {
+        let Ok(player) = players.get(owner.0) else {
+            continue;
+        };
+        let stats = &player.engine.stats;
+        text.sections[0].value = match kind {
+            PlayerText::Score => format!("{:06}", stats.score),
+            PlayerText::Lines => format!("{:06}", stats.lines),
+            PlayerText::Level => match player.engine.mode {
+                GameMode::Master => format!("{:03}", stats.level),
+                _ => format!("{:02}", stats.level),
+            },
+            PlayerText::Info => player.info.clone(),
+        };
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// peers and server must speak the same version
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// players in a room of the server
+pub const ROOM_PLAYERS: usize = 2;
+
+/// port the server listens on without `--addr`
+pub const SERVER_PORT: u16 = 7878;
+
+/// longest line a peer may send, a connection sending longer ones is dropped
+pub const MAX_LINE: usize = 1024;
+
+/// message of online versus, between two peers or through the server.
+///
+/// Messages are text lines over TCP, words separated by a space.
+/// Between peers:
+///
+/// ```text
+/// HELLO <version> <seed>   host sends its seed when a peer connects, the peer echoes it
+/// INPUT <frame> <keys>     keys held in a frame, bits of left, right, soft drop,
+///                          hard drop, rotate, rotate back and hold, lowest first
+/// HASH <frame> <hash>      hash of both boards after a frame, first player's board first
+/// PING <millis>            peer answers PONG with the same number
+/// PONG <millis>
+/// RESULT <slot>|DRAW       who won the match, by player index in the room
+/// BYE                      leaving
+/// ```
+///
+/// With the server, a client says `HELLO <version> 0`, then:
+///
+/// ```text
+/// CREATE                   client opens a new room
+/// JOIN <code>              client enters a room
+/// ROOM <code> <players>    server: client is in room, sent again when players change
+/// READY                    client is ready to play
+/// START <seed> <slot>      server: all players are ready, match starts
+/// ERROR <text>             server: request failed, e.g. no such room
+/// ```
+///
+/// In a started room the server relays INPUT, HASH, PING and PONG to the other player,
+/// keeps RESULT of the match, and sends BYE when the other player leaves.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Message {
+    Hello {
+        version: u32,
+        seed: u64,
+    },
+    Input {
+        frame: u32,
+        keys: u8,
+    },
+    Hash {
+        frame: u32,
+        hash: u64,
+    },
+    Ping(u64),
+    Pong(u64),
+    Create,
+    Join(String),
+    Room {
+        code: String,
+        players: u32,
+    },
+    Ready,
+    Start {
+        seed: u64,
+        slot: u32,
+    },
+    /// `None` for a draw
+    Result(Option<u32>),
+    Error(String),
+    Bye,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Hello { version, seed } => write!(f, "HELLO {} {}", version, seed),
+            Message::Input { frame, keys } => write!(f, "INPUT {} {}", frame, keys),
+            Message::Hash { frame, hash } => write!(f, "HASH {} {}", frame, hash),
+            Message::Ping(millis) => write!(f, "PING {}", millis),
+            Message::Pong(millis) => write!(f, "PONG {}", millis),
+            Message::Create => write!(f, "CREATE"),
+            Message::Join(code) => write!(f, "JOIN {}", code),
+            Message::Room { code, players } => write!(f, "ROOM {} {}", code, players),
+            Message::Ready => write!(f, "READY"),
+            Message::Start { seed, slot } => write!(f, "START {} {}", seed, slot),
+            Message::Result(Some(slot)) => write!(f, "RESULT {}", slot),
+            Message::Result(None) => write!(f, "RESULT DRAW"),
+            Message::Error(text) => write!(f, "ERROR {}", text),
+            Message::Bye => write!(f, "BYE"),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = s.split_whitespace().collect();
+        let number = |i: usize| -> Result<u64, String> {
+            words
+                .get(i)
+                .and_then(|word| word.parse().ok())
+                .ok_or_else(|| format!("bad message `{}`", s))
+        };
+        let word = |i: usize| -> Result<String, String> {
+            words
+                .get(i)
+                .map(|word| word.to_string())
+                .ok_or_else(|| format!("bad message `{}`", s))
+        };
+        match words.first().copied() {
+            Some("HELLO") => Ok(Message::Hello {
+                version: number(1)? as u32,
+                seed: number(2)?,
+            }),
+            Some("INPUT") => Ok(Message::Input {
+                frame: number(1)? as u32,
+                keys: number(2)? as u8,
+            }),
+            Some("HASH") => Ok(Message::Hash {
+                frame: number(1)? as u32,
+                hash: number(2)?,
+            }),
+            Some("PING") => Ok(Message::Ping(number(1)?)),
+            Some("PONG") => Ok(Message::Pong(number(1)?)),
+            Some("CREATE") => Ok(Message::Create),
+            Some("JOIN") => Ok(Message::Join(word(1)?)),
+            Some("ROOM") => Ok(Message::Room {
+                code: word(1)?,
+                players: number(2)? as u32,
+            }),
+            Some("READY") => Ok(Message::Ready),
+            Some("START") => Ok(Message::Start {
+                seed: number(1)?,
+                slot: number(2)? as u32,
+            }),
+            Some("RESULT") if words.get(1) == Some(&"DRAW") => Ok(Message::Result(None)),
+            Some("RESULT") => Ok(Message::Result(Some(number(1)? as u32))),
+            Some("ERROR") => Ok(Message::Error(words[1..].join(" "))),
+            Some("BYE") => Ok(Message::Bye),
+            _ => Err(format!("unknown message `{}`", s)),
+        }
+    }
+}
+
+/// byte stream under a connection, reads and writes don't block
+pub trait Stream: Read + Write + Send + Sync {
+    /// stop reading and writing both ways
+    fn shutdown(&self);
+}
+
+impl Stream for TcpStream {
+    fn shutdown(&self) {
+        TcpStream::shutdown(self, Shutdown::Both).ok();
+    }
+}
+
+/// bytes going one way between two [`MemoryStream`]s
+#[derive(Default)]
+struct Pipe {
+    bytes: VecDeque<u8>,
+    closed: bool,
+}
+
+/// one end of a stream in memory, e.g. to test peers and the server without sockets
+struct MemoryStream {
+    incoming: Arc<Mutex<Pipe>>,
+    outgoing: Arc<Mutex<Pipe>>,
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut pipe = self.incoming.lock().unwrap();
+        if pipe.bytes.is_empty() && pipe.closed {
+            return Ok(0);
+        }
+        if pipe.bytes.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let n = buf.len().min(pipe.bytes.len());
+        for (to, from) in buf.iter_mut().zip(pipe.bytes.drain(..n)) {
+            *to = from;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut pipe = self.outgoing.lock().unwrap();
+        if pipe.closed {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        pipe.bytes.extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Stream for MemoryStream {
+    fn shutdown(&self) {
+        self.incoming.lock().unwrap().closed = true;
+        self.outgoing.lock().unwrap().closed = true;
+    }
+}
+
+impl Drop for MemoryStream {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// non-blocking line connection, polled every frame
+pub struct Connection {
+    stream: Box<dyn Stream>,
+    /// bytes read, not yet a full line
+    incoming: Vec<u8>,
+    /// bytes not yet written
+    outgoing: Vec<u8>,
+    /// peer closed the connection or it failed
+    pub closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::with_stream(Box::new(stream)))
+    }
+
+    fn with_stream(stream: Box<dyn Stream>) -> Self {
+        Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// two connections to each other in memory, closing one closes the other
+    pub fn pair() -> (Self, Self) {
+        let (there, back) = Default::default();
+        let ends = [(&there, &back), (&back, &there)].map(|(incoming, outgoing)| {
+            Self::with_stream(Box::new(MemoryStream {
+                incoming: Arc::clone(incoming),
+                outgoing: Arc::clone(outgoing),
+            }))
+        });
+        let [a, b] = ends;
+        (a, b)
+    }
+
+    /// close both ways, the peer reads the end of the stream
+    pub fn close(&mut self) {
+        self.flush();
+        self.stream.shutdown();
+        self.closed = true;
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        self.outgoing
+            .extend_from_slice(format!("{}\n", message).as_bytes());
+        self.flush();
+    }
+
+    /// write what the socket takes now, the rest on next call
+    pub fn flush(&mut self) {
+        while !self.closed && !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    /// messages arrived since last call, lines which don't parse are skipped
+    pub fn receive(&mut self) -> Vec<Message> {
+        self.flush();
+        let mut buffer = [0; 4096];
+        let mut messages = Vec::new();
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.incoming.extend_from_slice(&buffer[..n]);
+                    self.lines(&mut messages);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        messages
+    }
+
+    /// full lines of `incoming` parsed into `messages`, drops the connection on a too long line
+    fn lines(&mut self, messages: &mut Vec<Message>) {
+        while let Some(end) = self.incoming.iter().position(|b| *b == b'\n') {
+            if end > MAX_LINE {
+                break;
+            }
+            let line: Vec<_> = self.incoming.drain(..=end).collect();
+            if let Ok(message) = String::from_utf8_lossy(&line).trim().parse() {
+                messages.push(message);
+            }
+        }
+        if self.incoming.len() > MAX_LINE && !self.incoming[..=MAX_LINE].contains(&b'\n') {
+            self.incoming.clear();
+            self.closed = true;
+            self.stream.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    /// connection of the server side and the stream of the client
+    fn pair() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (Connection::new(stream).unwrap(), client)
+    }
+
+    /// what arrives in a few polls
+    fn poll(connection: &mut Connection) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for _ in 0..20 {
+            messages.extend(connection.receive());
+            thread::sleep(Duration::from_millis(5));
+        }
+        messages
+    }
+
+    #[test]
+    fn lines_are_messages() {
+        let (mut connection, mut client) = pair();
+        client.write_all(b"PING 1\nREADY\nPO").unwrap();
+        assert_eq!(poll(&mut connection), [Message::Ping(1), Message::Ready]);
+        client.write_all(b"NG 2\n").unwrap();
+        assert_eq!(poll(&mut connection), [Message::Pong(2)]);
+        assert!(!connection.closed);
+    }
+
+    #[test]
+    fn too_long_line_drops_connection() {
+        let (mut connection, mut client) = pair();
+        client.write_all(b"PING 1\n").unwrap();
+        client.write_all(&[b'A'; MAX_LINE + 1]).unwrap();
+        assert_eq!(poll(&mut connection), [Message::Ping(1)]);
+        assert!(connection.closed);
+        assert!(connection.incoming.is_empty());
+    }
+
+    #[test]
+    fn pair_in_memory() {
+        let (mut a, mut b) = Connection::pair();
+        a.send(&Message::Ready);
+        a.send(&Message::Ping(3));
+        b.send(&Message::Bye);
+        assert_eq!(b.receive(), [Message::Ready, Message::Ping(3)]);
+        assert_eq!(a.receive(), [Message::Bye]);
+        assert!(b.receive().is_empty());
+        a.close();
+        assert!(b.receive().is_empty());
+        assert!(b.closed);
+    }
+
+    #[test]
+    fn too_long_full_line_drops_connection() {
+        let (mut connection, mut client) = pair();
+        let mut line = vec![b'A'; MAX_LINE + 1];
+        line.extend_from_slice(b"\nPING 1\n");
+        client.write_all(&line).unwrap();
+        assert!(poll(&mut connection).is_empty());
+        assert!(connection.closed);
+    }
+}
@@ -0,0 +1,417 @@
+use crate::bricks::{Board, Brick, Dot};
+use crate::consts::{BOARD_X, BOARD_Y_VALID};
+use crate::engine::{Engine, Event};
+use crate::modes::GameMode;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// what a puzzle asks for
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Objective {
+    /// clean at least N lines
+    Lines(u32),
+    /// board is empty after cleaning lines
+    PerfectClear,
+    /// T-spin cleaning 2 lines
+    TSpinDouble,
+    /// freeze N bricks without topping out
+    Survive(u32),
+}
+
+impl Objective {
+    /// short text shown beside the board
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Lines(n) => format!("CLEAR {} LINES", n),
+            Objective::PerfectClear => "PERFECT CLEAR".to_string(),
+            Objective::TSpinDouble => "T-SPIN DOUBLE".to_string(),
+            Objective::Survive(n) => format!("SURVIVE {}", n),
+        }
+    }
+
+    /// `Some(true)` when objective is met by the event,
+    /// `Some(false)` when game is over before that.
+    pub fn check(&self, engine: &Engine, event: &Event) -> Option<bool> {
+        match *event {
+            Event::Locked {
+                brick,
+                lines,
+                tspin,
+                perfect_clear,
+                ..
+            } => {
+                let met = match *self {
+                    Objective::Lines(n) => engine.stats.lines >= n,
+                    Objective::PerfectClear => perfect_clear,
+                    Objective::TSpinDouble => tspin && lines == 2 && brick.name() == 'T',
+                    Objective::Survive(n) => engine.stats.pieces >= n,
+                };
+                met.then_some(true)
+            }
+            Event::Over => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::Lines(n) => write!(f, "lines {}", n),
+            Objective::PerfectClear => write!(f, "perfect"),
+            Objective::TSpinDouble => write!(f, "tsd"),
+            Objective::Survive(n) => write!(f, "survive {}", n),
+        }
+    }
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let kind = words.next().unwrap_or_default();
+        let mut count = || -> Result<u32, String> {
+            words
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| format!("objective `{}` needs a number", kind))
+        };
+        match kind {
+            "lines" => Ok(Objective::Lines(count()?)),
+            "perfect" => Ok(Objective::PerfectClear),
+            "tsd" => Ok(Objective::TSpinDouble),
+            "survive" => Ok(Objective::Survive(count()?)),
+            _ => Err(format!("unknown objective `{}`", s)),
+        }
+    }
+}
+
+/// a prepared board with bricks given in advance.
+///
+/// Puzzle file is plain text, lines starting with `#` before `board:` are comments:
+///
+/// ```text
+/// name: FIRST TETRIS
+/// objective: lines 4
+/// bricks: I
+/// hold: no
+/// board:
+/// #########.
+/// #########.
+/// ```
+///
+/// Objective is one of `lines N`, `perfect`, `tsd` or `survive N`.
+/// Board rows are listed from top to bottom, the last row is the bottom line.
+/// `.` is empty, any other letter is occupied.
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub board: Board,
+    pub bricks: Vec<Brick>,
+    pub hold: bool,
+    pub objective: Objective,
+}
+
+impl Puzzle {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        text.parse()
+    }
+
+    pub fn engine(&self) -> Engine {
+        Engine::with_bricks(
+            GameMode::Puzzle,
+            self.board.clone(),
+            &self.bricks,
+            self.hold,
+        )
+    }
+}
+
+/// write the puzzle in the same format as puzzle files
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "objective: {}", self.objective)?;
+        let bricks: String = self.bricks.iter().map(Brick::name).collect();
+        writeln!(f, "bricks: {}", bricks)?;
+        writeln!(f, "hold: {}", if self.hold { "yes" } else { "no" })?;
+        writeln!(f, "board:")?;
+        let top = (0..BOARD_Y_VALID)
+            .rev()
+            .find(|y| (0..BOARD_X).any(|x| self.board.occupied_dot(&Dot(x, *y))));
+        for y in (0..=top.unwrap_or(-1)).rev() {
+            let row: String = (0..BOARD_X)
+                .map(|x| {
+                    if self.board.occupied_dot(&Dot(x, y)) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Puzzle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut bricks = None;
+        let mut hold = false;
+        let mut objective = None;
+        let mut rows = Vec::new();
+        let mut in_board = false;
+
+        for line in s.lines().map(str::trim_end) {
+            if in_board {
+                if !line.is_empty() {
+                    rows.push(line);
+                }
+                continue;
+            }
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("expect `key: value`, found `{}`", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "objective" => objective = Some(value.parse()?),
+                "bricks" => bricks = Some(parse_bricks(value)?),
+                "hold" => hold = parse_hold(value)?,
+                "board" => in_board = true,
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        Ok(Self {
+            name: name.ok_or("missing `name`")?,
+            board: parse_board(&rows)?,
+            bricks: bricks.ok_or("missing `bricks`")?,
+            hold,
+            objective: objective.ok_or("missing `objective`")?,
+        })
+    }
+}
+
+/// brick letters as `SZ` or `S Z`
+pub fn parse_bricks(value: &str) -> Result<Vec<Brick>, String> {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| Brick::from_name(c).ok_or(format!("unknown brick `{}`", c)))
+        .collect()
+}
+
+/// `yes` or `no`
+pub fn parse_hold(value: &str) -> Result<bool, String> {
+    match value {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("hold is `yes` or `no`, found `{}`", value)),
+    }
+}
+
+/// board rows from top to bottom, `.` is empty
+pub fn parse_board(rows: &[&str]) -> Result<Board, String> {
+    if rows.len() > BOARD_Y_VALID as usize {
+        return Err(format!("board has more than {} rows", BOARD_Y_VALID));
+    }
+    let mut board = Board::default();
+    for (y, row) in rows.iter().rev().enumerate() {
+        if row.chars().count() != BOARD_X as usize {
+            return Err(format!("board row `{}` is not {} wide", row, BOARD_X));
+        }
+        for (x, c) in row.chars().enumerate() {
+            if c != '.' {
+                board.occupy_dot(&Dot(x as i8, y as i8));
+            }
+        }
+    }
+    Ok(board)
+}
+
+/// puzzles in a folder, and which of them are solved
+#[derive(Default)]
+pub struct PuzzlePack {
+    /// (file name, puzzle), sorted by file name
+    pub puzzles: Vec<(String, Puzzle)>,
+    pub solved: HashSet<String>,
+    /// files failed to load
+    pub errors: Vec<String>,
+    progress: PathBuf,
+}
+
+impl PuzzlePack {
+    pub fn load(dir: &str, progress: &str) -> Self {
+        let mut pack = Self {
+            progress: PathBuf::from(progress),
+            ..Self::default()
+        };
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                pack.errors.push(format!("{}: {}", dir, e));
+                return pack;
+            }
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension() != Some(OsStr::new("txt")) {
+                continue;
+            }
+            let file = path.file_name().unwrap().to_string_lossy().to_string();
+            match Puzzle::load(&path) {
+                Ok(puzzle) => pack.puzzles.push((file, puzzle)),
+                Err(e) => pack.errors.push(format!("{}: {}", file, e)),
+            }
+        }
+        pack.puzzles.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if let Ok(text) = std::fs::read_to_string(&pack.progress) {
+            pack.solved = text.lines().map(str::to_string).collect();
+        }
+        pack
+    }
+
+    pub fn mark_solved(&mut self, file: &str) -> std::io::Result<()> {
+        if !self.solved.insert(file.to_string()) {
+            return Ok(());
+        }
+        let mut files: Vec<_> = self.solved.iter().cloned().collect();
+        files.sort();
+        std::fs::write(&self.progress, files.join("\n") + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Phase;
+    use crate::movegen;
+
+    const TETRIS: &str = "name: FIRST TETRIS
+objective: perfect
+bricks: IO
+hold: yes
+board:
+#########.
+#########.
+#########.
+#########.
+";
+
+    #[test]
+    fn round_trip() {
+        let puzzle: Puzzle = TETRIS.parse().unwrap();
+        assert_eq!(puzzle.name, "FIRST TETRIS");
+        assert_eq!(puzzle.objective, Objective::PerfectClear);
+        assert_eq!(
+            puzzle.bricks.iter().map(Brick::name).collect::<String>(),
+            "IO"
+        );
+        assert!(puzzle.hold);
+        assert_eq!(puzzle.board.height(), 4);
+        assert_eq!(puzzle.to_string(), TETRIS);
+
+        let again: Puzzle = puzzle.to_string().parse().unwrap();
+        assert_eq!(again.board, puzzle.board);
+        for objective in ["lines 3", "perfect", "tsd", "survive 12"] {
+            let parsed: Objective = objective.parse().unwrap();
+            assert_eq!(parsed.to_string(), objective);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_bricks("S Z").is_ok());
+        assert!(parse_bricks("SX").unwrap_err().contains("`X`"));
+        assert!(parse_board(&["#########."]).is_ok());
+        assert!(parse_board(&["####"]).unwrap_err().contains("wide"));
+        let tall = vec![".........."; BOARD_Y_VALID as usize + 1];
+        assert!(parse_board(&tall).unwrap_err().contains("rows"));
+
+        assert!(parse_hold("maybe").is_err());
+        for (from, to) in [
+            ("hold: yes", "hold: true"),
+            ("objective: perfect", "objective: lines"),
+            ("objective: perfect", "objective: win"),
+            ("name: FIRST TETRIS\n", ""),
+            ("bricks: IO\n", ""),
+            ("hold: yes", "hold yes"),
+            ("hold: yes", "colour: red"),
+        ] {
+            let text = TETRIS.replace(from, to);
+            assert!(text.parse::<Puzzle>().is_err(), "{}", text);
+        }
+    }
+
+    /// lock the first brick where it leaves `filled` rows full, checking every event
+    fn check(puzzle: &Puzzle, filled: usize) -> Option<bool> {
+        let mut engine = puzzle.engine();
+        engine.gravity_paused = true;
+        while engine.phase != Phase::Falling {
+            engine.step(&Default::default());
+        }
+        let placement = movegen::reachable(
+            &engine.board,
+            engine.moving_brick,
+            engine.start,
+            GameMode::Puzzle,
+            0,
+        )
+        .into_iter()
+        .find(|placement| {
+            let mut board = engine.board.clone();
+            board.occupy_brick(&placement.brick, &placement.pos);
+            board.get_clean_lines().len() == filled
+        })
+        .unwrap();
+        let mut result = None;
+        let mut locked = false;
+        for mv in placement.moves {
+            for input in [mv.input(), Default::default()] {
+                engine.step(&input);
+                for event in engine.take_events() {
+                    locked |= matches!(event, Event::Locked { .. });
+                    result = result.or(puzzle.objective.check(&engine, &event));
+                }
+            }
+        }
+        assert!(locked);
+        result
+    }
+
+    #[test]
+    fn objective_check() {
+        let puzzle: Puzzle = TETRIS.parse().unwrap();
+        // the I brick upright in the well cleans the board
+        assert_eq!(check(&puzzle, 4), Some(true));
+        // anywhere else the board isn't clean, but the game isn't over either
+        assert_eq!(check(&puzzle, 0), None);
+
+        let lines = Puzzle {
+            objective: Objective::Lines(4),
+            ..puzzle.clone()
+        };
+        assert_eq!(check(&lines, 4), Some(true));
+        assert_eq!(check(&lines, 0), None);
+
+        // the last brick locks without meeting the objective
+        let last = Puzzle {
+            bricks: puzzle.bricks[..1].to_vec(),
+            ..puzzle
+        };
+        assert_eq!(check(&last, 0), Some(false));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::bricks::Brick;
+use crate::consts::BRICKS_TYPES;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+
+/// how the next brick is chosen
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RandomizerKind {
+    /// every brick type with the same chance
+    Uniform,
+    /// TGM: remember last 4 bricks, and re-roll up to `rolls` times
+    /// while the new brick is in history.
+    ///
+    /// https://tetris.wiki/TGM_randomizer
+    History { rolls: u32 },
+    /// NES: roll a 8-sided die, re-roll once (as 7-sided) on 8 or the same brick as last.
+    ///
+    /// https://tetris.wiki/Tetris_(NES,_Nintendo)#Randomizer
+    Nes,
+    /// bricks given in advance, e.g. by a puzzle
+    Fixed,
+}
+
+/// seeded brick generator, so that same seed gives same bricks.
+#[derive(Clone)]
+pub struct Randomizer {
+    kind: RandomizerKind,
+    rng: StdRng,
+    history: VecDeque<usize>,
+    first: bool,
+    /// bricks left for `Fixed`
+    fixed: VecDeque<Brick>,
+}
+
+// brick types, same order as BRICK_VIEWS
+const O: usize = 0;
+const Z: usize = 5;
+const S: usize = 4;
+
+impl Randomizer {
+    pub fn new(kind: RandomizerKind, seed: u64) -> Self {
+        Self {
+            kind,
+            rng: StdRng::seed_from_u64(seed),
+            history: VecDeque::from(vec![Z; 4]),
+            first: true,
+            fixed: VecDeque::new(),
+        }
+    }
+
+    pub fn fixed(bricks: &[Brick]) -> Self {
+        Self {
+            fixed: bricks.iter().copied().collect(),
+            ..Self::new(RandomizerKind::Fixed, 0)
+        }
+    }
+
+    /// `None` when fixed bricks run out
+    pub fn next_brick(&mut self) -> Option<Brick> {
+        let ty = match self.kind {
+            RandomizerKind::Uniform => self.rng.gen_range(0..BRICKS_TYPES),
+            RandomizerKind::History { rolls } => self.roll_with_history(rolls),
+            RandomizerKind::Nes => self.roll_nes(),
+            RandomizerKind::Fixed => return self.fixed.pop_front(),
+        };
+        self.first = false;
+        Some(Brick { ty, rotation: 0 })
+    }
+
+    fn roll_nes(&mut self) -> usize {
+        let last = self.history.back().copied();
+        let mut ty = self.rng.gen_range(0..=BRICKS_TYPES);
+        if ty == BRICKS_TYPES || (!self.first && Some(ty) == last) {
+            ty = self.rng.gen_range(0..BRICKS_TYPES);
+        }
+        self.history.pop_front();
+        self.history.push_back(ty);
+        ty
+    }
+
+    fn roll_with_history(&mut self, rolls: u32) -> usize {
+        let mut ty = self.rng.gen_range(0..BRICKS_TYPES);
+        if self.first {
+            //first brick is never S, Z or O
+            while ty == O || ty == S || ty == Z {
+                ty = self.rng.gen_range(0..BRICKS_TYPES);
+            }
+        } else {
+            for _ in 1..rolls {
+                if !self.history.contains(&ty) {
+                    break;
+                }
+                ty = self.rng.gen_range(0..BRICKS_TYPES);
+            }
+        }
+        self.history.pop_front();
+        self.history.push_back(ty);
+        ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nes_rerolls_once() {
+        let seed = 11;
+        let mut randomizer = Randomizer::new(RandomizerKind::Nes, seed);
+        // same rolls by hand: 8-sided die, one 7-sided re-roll on 8 or a repeat
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut last = None;
+        let mut repeats = 0;
+        for _ in 0..1000 {
+            let mut ty = rng.gen_range(0..=BRICKS_TYPES);
+            if ty == BRICKS_TYPES || Some(ty) == last {
+                ty = rng.gen_range(0..BRICKS_TYPES);
+            }
+            let brick = randomizer.next_brick().unwrap();
+            assert_eq!(brick.ty, ty);
+            assert!(brick.ty < BRICKS_TYPES);
+            if Some(ty) == last {
+                repeats += 1;
+            }
+            last = Some(ty);
+        }
+        // a repeat needs the re-roll to hit it again, 1/7 of re-rolls instead of 1/7 of bricks
+        assert!(repeats < 1000 / BRICKS_TYPES / 2, "{} repeats", repeats);
+    }
+
+    #[test]
+    fn nes_first_brick_is_not_rerolled() {
+        // history starts as Z, the first brick may still be a Z without a re-roll
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut ty = rng.gen_range(0..=BRICKS_TYPES);
+            if ty == BRICKS_TYPES {
+                ty = rng.gen_range(0..BRICKS_TYPES);
+            }
+            let mut randomizer = Randomizer::new(RandomizerKind::Nes, seed);
+            assert_eq!(randomizer.next_brick().unwrap().ty, ty);
+        }
+    }
+}
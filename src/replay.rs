@@ -0,0 +1,181 @@
+use crate::engine::{Engine, Input};
+use crate::modes::{GameMode, GAME_MODES};
+use std::fmt;
+use std::str::FromStr;
+
+/// frames a replay may have, a day at 60 frames per second
+pub const MAX_FRAMES: usize = 60 * 60 * 60 * 24;
+
+/// keys of a game frame by frame, with what is needed to play it again.
+///
+/// Written as
+///
+/// ```text
+/// mode: CLASSIC
+/// level: 0
+/// seed: 42
+/// hold: no
+/// inputs:
+/// 30 00
+/// 1 02
+/// ```
+///
+/// Inputs are lines of frames and the keys held in them, as hex of [`Input::to_bits`],
+/// [`MAX_FRAMES`] at most.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub mode: GameMode,
+    pub level: u32,
+    pub seed: u64,
+    pub hold: bool,
+    pub inputs: Vec<Input>,
+}
+
+impl Replay {
+    pub fn new(mode: GameMode, level: u32, seed: u64, hold: bool) -> Self {
+        Self {
+            mode,
+            level,
+            seed,
+            hold,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// engine of the game before the first frame
+    pub fn engine(&self) -> Engine {
+        let mut engine = Engine::new(self.mode, self.level, self.seed);
+        engine.hold_enabled = self.hold;
+        engine
+    }
+}
+
+/// game mode by the name shown in menu, as `CLASSIC`
+pub fn parse_mode(name: &str) -> Result<GameMode, String> {
+    GAME_MODES
+        .into_iter()
+        .find(|mode| mode.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown mode `{}`", name))
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "mode: {}", self.mode.name())?;
+        writeln!(f, "level: {}", self.level)?;
+        writeln!(f, "seed: {}", self.seed)?;
+        writeln!(f, "hold: {}", if self.hold { "yes" } else { "no" })?;
+        writeln!(f, "inputs:")?;
+        let mut inputs = self.inputs.iter().peekable();
+        while let Some(input) = inputs.next() {
+            let mut frames = 1;
+            while inputs.next_if_eq(&input).is_some() {
+                frames += 1;
+            }
+            writeln!(f, "{} {:02x}", frames, input.to_bits())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mode = None;
+        let mut level = 0;
+        let mut seed = None;
+        let mut hold = false;
+        let mut inputs = Vec::new();
+        let mut in_inputs = false;
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if in_inputs {
+                let (frames, keys) = line
+                    .split_once(' ')
+                    .ok_or_else(|| format!("expect `frames keys`, found `{}`", line))?;
+                let frames: usize = frames
+                    .parse()
+                    .map_err(|_| format!("bad frames `{}`", frames))?;
+                if frames > MAX_FRAMES - inputs.len() {
+                    return Err(format!("more than {} frames", MAX_FRAMES));
+                }
+                let keys = u8::from_str_radix(keys.trim(), 16)
+                    .map_err(|_| format!("bad keys `{}`", keys))?;
+                inputs.extend(std::iter::repeat_n(Input::from_bits(keys), frames));
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("expect `key: value`, found `{}`", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "mode" => mode = Some(parse_mode(value)?),
+                "level" => {
+                    level = value
+                        .parse()
+                        .map_err(|_| format!("bad level `{}`", value))?
+                }
+                "seed" => seed = Some(value.parse().map_err(|_| format!("bad seed `{}`", value))?),
+                "hold" => hold = value == "yes",
+                "inputs" => in_inputs = true,
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        Ok(Self {
+            mode: mode.ok_or("missing `mode`")?,
+            level,
+            seed: seed.ok_or("missing `seed`")?,
+            hold,
+            inputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{Bot, DIFFICULTIES};
+
+    #[test]
+    fn round_trip() {
+        let mut replay = Replay::new(GameMode::Versus, 3, 42, true);
+        let mut engine = replay.engine();
+        // EXPERT
+        let mut bot = Bot::new(&DIFFICULTIES[3], 42);
+        bot.blocking = true;
+        while !engine.is_over() && engine.stats.frames < 3000 {
+            let input = bot.input(&engine);
+            engine.step(&input);
+            engine.take_events();
+            replay.inputs.push(input);
+        }
+        assert!(engine.stats.pieces > 10);
+
+        let parsed: Replay = replay.to_string().parse().unwrap();
+        assert_eq!(parsed.to_string(), replay.to_string());
+        let mut played = parsed.engine();
+        for input in &parsed.inputs {
+            played.step(input);
+            played.take_events();
+        }
+        assert_eq!(format!("{:?}", played.stats), format!("{:?}", engine.stats));
+        assert!(played.board == engine.board);
+    }
+
+    #[test]
+    fn too_many_frames() {
+        let text = format!("mode: CLASSIC\nseed: 1\ninputs:\n{} 00\n", MAX_FRAMES);
+        assert_eq!(text.parse::<Replay>().unwrap().inputs.len(), MAX_FRAMES);
+        let text = format!("{}1 02\n", text);
+        assert_eq!(
+            text.parse::<Replay>().unwrap_err(),
+            format!("more than {} frames", MAX_FRAMES)
+        );
+        let text = format!("mode: CLASSIC\nseed: 1\ninputs:\n{} 00\n", usize::MAX);
+        assert!(text.parse::<Replay>().is_err());
+    }
+}
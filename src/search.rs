@@ -0,0 +1,301 @@
+use crate::bot::{placements, Evaluator};
+use crate::bricks::{Board, Brick};
+use crate::engine::Engine;
+use crate::modes::GameMode;
+use crate::movegen::Reachable;
+use crate::tbp::Suggestion;
+use instant::{Duration, Instant};
+use std::collections::HashMap;
+
+/// time searched in one [`Search::poll`], so a frame of the game isn't held up
+const SLICE: Duration = Duration::from_millis(8);
+/// time a search for one brick takes at most
+pub const BUDGET: Duration = Duration::from_millis(150);
+/// boards a search for one brick expands at most when it is limited by nodes instead of time
+pub const NODES: usize = 1000;
+/// evaluations kept before the cache is emptied
+const CACHE_SIZE: usize = 1 << 20;
+
+/// a board the search got to, with how it got there
+struct Node {
+    board: Board,
+    hold: Option<Brick>,
+    /// bricks of the queue played or put in hold
+    used: usize,
+    /// line rewards of the placements on the way
+    reward: f64,
+    /// reward and evaluation of the board
+    value: f64,
+    /// index into `Search::firsts` of the placement of the moving brick
+    first: usize,
+}
+
+/// beam search over placements of the moving brick, hold and the bricks after it.
+///
+/// Each layer places one more brick on the `width` best boards of the layer before,
+/// the best board of the last layer searched gives the placement of the moving brick.
+/// Started by [`Search::start`] when a brick spawns, then polled frame by frame
+/// until all bricks are searched or `budget` is spent.
+pub struct Search {
+    /// boards kept in each layer
+    pub width: usize,
+    pub budget: Duration,
+    /// boards expanded at most instead of `budget`, so the same game is played on any computer
+    pub nodes: Option<usize>,
+    mode: GameMode,
+    level: u32,
+    hold_enabled: bool,
+    /// hold is allowed for the moving brick
+    can_hold: bool,
+    /// moving brick, then the bricks after it
+    queue: Vec<Brick>,
+    /// placements of the moving brick, and whether to hold first
+    firsts: Vec<(Reachable, bool)>,
+    /// bricks placed in each board of `layer`
+    depth: usize,
+    layer: Vec<Node>,
+    /// nodes of `layer` expanded into `next`
+    expanded: usize,
+    /// nodes expanded since the start
+    expansions: usize,
+    next: Vec<Node>,
+    /// index into `next` by board, type in hold and bricks used, to keep the same board once
+    seen: HashMap<(Board, Option<usize>, usize), usize>,
+    /// first placement of the best board of the last layer searched
+    best: Option<usize>,
+    started: Instant,
+    done: bool,
+    /// evaluations by board and lines cleared
+    cache: HashMap<(Board, u32), f64>,
+}
+
+impl Search {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            budget: BUDGET,
+            nodes: None,
+            mode: GameMode::Classic,
+            level: 0,
+            hold_enabled: false,
+            can_hold: false,
+            queue: Vec::new(),
+            firsts: Vec::new(),
+            depth: 0,
+            layer: Vec::new(),
+            expanded: 0,
+            expansions: 0,
+            next: Vec::new(),
+            seen: HashMap::new(),
+            best: None,
+            started: Instant::now(),
+            done: true,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// search for the moving brick of `engine`, looking at up to `bricks` bricks with it
+    pub fn start(&mut self, engine: &Engine, bricks: usize) {
+        self.mode = engine.mode;
+        self.level = engine.stats.level;
+        self.hold_enabled = engine.hold_enabled;
+        self.can_hold = engine.can_hold();
+        self.queue = std::iter::once(engine.moving_brick)
+            .chain(engine.preview(bricks.max(1) - 1))
+            .collect();
+        self.firsts.clear();
+        self.depth = 0;
+        self.layer = vec![Node {
+            board: engine.board.clone(),
+            hold: engine.hold_brick,
+            used: 0,
+            reward: 0.0,
+            value: 0.0,
+            first: 0,
+        }];
+        self.expanded = 0;
+        self.expansions = 0;
+        self.next.clear();
+        self.seen.clear();
+        self.best = None;
+        self.started = Instant::now();
+        self.done = false;
+        if self.cache.len() > CACHE_SIZE {
+            self.cache.clear();
+        }
+    }
+
+    /// search a while, the placement once the search is done
+    pub fn poll(&mut self, evaluator: &dyn Evaluator) -> Suggestion {
+        let polled = Instant::now();
+        while !self.done {
+            // the first layer is always searched, so there is a placement to give
+            if self.best.is_some() {
+                let spent = match self.nodes {
+                    Some(nodes) => self.expansions >= nodes,
+                    None => self.started.elapsed() >= self.budget,
+                };
+                if spent {
+                    self.done = true;
+                    break;
+                }
+                if polled.elapsed() >= SLICE {
+                    break;
+                }
+            }
+            self.step(evaluator);
+        }
+        match self.best {
+            _ if !self.done => Suggestion::Waiting,
+            Some(best) => {
+                let (placement, hold) = self.firsts[best].clone();
+                Suggestion::Placement(placement, hold)
+            }
+            None => Suggestion::Failed,
+        }
+    }
+
+    /// expand the next node of the layer, or start the next layer
+    fn step(&mut self, evaluator: &dyn Evaluator) {
+        if self.expanded < self.layer.len() {
+            self.expand(self.expanded, evaluator);
+            self.expanded += 1;
+            self.expansions += 1;
+            return;
+        }
+        // stable, so of equal boards the one with fewer moves stays first
+        self.next.sort_by(|a, b| b.value.total_cmp(&a.value));
+        self.next.truncate(self.width);
+        if let Some(best) = self.next.first() {
+            self.best = Some(best.first);
+        }
+        self.layer = std::mem::take(&mut self.next);
+        self.seen.clear();
+        self.expanded = 0;
+        self.depth += 1;
+        self.done = self.layer.is_empty() || self.depth >= self.queue.len();
+    }
+
+    fn expand(&mut self, index: usize, evaluator: &dyn Evaluator) {
+        let node = &self.layer[index];
+        let Some(&brick) = self.queue.get(node.used) else {
+            return;
+        };
+        let root = self.depth == 0;
+        // brick to place, hold first, hold after it and bricks used after it
+        let mut choices = vec![(brick, false, node.hold, node.used + 1)];
+        if (root && self.can_hold) || (!root && self.hold_enabled) {
+            // out of hold comes the held brick, or the next one when hold is empty
+            match node.hold {
+                Some(held) if held.ty != brick.ty => {
+                    choices.push((held, true, Some(brick), node.used + 1))
+                }
+                None => {
+                    if let Some(&next) = self.queue.get(node.used + 1) {
+                        choices.push((next, true, Some(brick), node.used + 2));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let (reward, first) = (node.reward, node.first);
+        let board = node.board.clone();
+        for (brick, hold, held, used) in choices {
+            for (placement, after, lines) in placements(&board, brick, self.mode, self.level) {
+                let value = reward
+                    + *self
+                        .cache
+                        .entry((after.clone(), lines))
+                        .or_insert_with(|| evaluator.evaluate(&after, lines));
+                let first = if root {
+                    self.firsts.push((placement, hold));
+                    self.firsts.len() - 1
+                } else {
+                    first
+                };
+                let key = (after.clone(), held.map(|held| held.ty), used);
+                let child = Node {
+                    board: after,
+                    hold: held,
+                    used,
+                    reward: reward + evaluator.lines(lines),
+                    value,
+                    first,
+                };
+                match self.seen.get(&key).copied() {
+                    Some(i) if self.next[i].value >= value => {}
+                    Some(i) => self.next[i] = child,
+                    None => {
+                        self.seen.insert(key, self.next.len());
+                        self.next.push(child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::WEIGHTS;
+    use crate::engine::{Input, Phase};
+
+    fn falling(seed: u64) -> Engine {
+        let mut engine = Engine::new(GameMode::Versus, 0, seed);
+        engine.hold_enabled = true;
+        while engine.phase != Phase::Falling {
+            engine.step(&Input::default());
+        }
+        engine
+    }
+
+    fn placement(search: &mut Search) -> (i8, i8, usize, bool) {
+        loop {
+            match search.poll(&*WEIGHTS) {
+                Suggestion::Waiting => {}
+                Suggestion::Placement(placement, hold) => {
+                    break (
+                        placement.pos.0,
+                        placement.pos.1,
+                        placement.brick.rotation,
+                        hold,
+                    )
+                }
+                Suggestion::Failed => panic!("no placement"),
+            }
+        }
+    }
+
+    #[test]
+    fn searches_preview_bricks() {
+        let engine = falling(3);
+        let mut search = Search::new(8);
+        search.start(&engine, 6);
+        let types = |bricks: &[Brick]| bricks.iter().map(|brick| brick.ty).collect::<Vec<_>>();
+        assert_eq!(search.queue.len(), 6);
+        assert_eq!(search.queue[0].ty, engine.moving_brick.ty);
+        assert_eq!(types(&search.queue[1..]), types(&engine.preview(5)));
+        search.start(&engine, 1);
+        assert_eq!(search.queue.len(), 1);
+    }
+
+    #[test]
+    fn node_budget_is_reproducible() {
+        for seed in 0..8 {
+            let engine = falling(seed);
+            let mut played = Vec::new();
+            for nodes in [1, 1, 5, 5] {
+                let mut search = Search::new(48);
+                search.nodes = Some(nodes);
+                // no time left, only nodes stop the search
+                search.budget = Duration::ZERO;
+                search.start(&engine, 2);
+                played.push(placement(&mut search));
+                assert!(search.expansions <= nodes.max(1));
+            }
+            assert_eq!(played[0], played[1]);
+            assert_eq!(played[2], played[3]);
+        }
+    }
+}
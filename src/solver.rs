@@ -0,0 +1,396 @@
+use crate::bricks::{Board, Brick, BrickView, Dot};
+use crate::consts::{BOARD_Y, BRICK_START_DOT, BRICK_VIEWS};
+use crate::engine::Engine;
+use crate::modes::GameMode;
+use crate::movegen::reachable;
+use instant::{Duration, Instant};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// lines a perfect clear is searched in, or as high as the stack when it is higher
+const PC_ROWS: i8 = 4;
+/// solutions [`Problem::all`] gives at most
+const MAX_SOLUTIONS: usize = 1000;
+
+/// a brick of a solution and where it goes, keys to get there are found by
+/// [`reachable`] on the board before it
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    /// hold first, the brick is the one out of hold
+    pub hold: bool,
+    pub brick: Brick,
+    pub pos: Dot,
+}
+
+/// bricks placed one after another until the board is empty
+pub type Solution = Vec<Step>;
+
+/// dots of each step on `board` the solution starts from. Steps are placed on the board
+/// left by the steps before, lines cleared by them are skipped
+pub fn board_dots(board: &Board, solution: &Solution) -> Vec<[Dot; 4]> {
+    let mut board = board.clone();
+    // line of `board` in each line of the board the steps are placed on
+    let mut lines: Vec<i8> = (0..2 * BOARD_Y).collect();
+    let mut result = Vec::new();
+    for step in solution {
+        let dots = BrickView::from(step.brick)
+            .dots
+            .map(|dot| dot.with_original_dot(&step.pos));
+        result.push(dots.map(|dot| Dot(dot.0, lines[dot.1.max(0) as usize])));
+        board.occupy_brick(&step.brick, &step.pos);
+        // highest first, lines below keep their index
+        for y in board.get_clean_lines() {
+            lines.remove(y as usize);
+        }
+        board.clean_lines();
+    }
+    result
+}
+
+#[derive(Debug, Default)]
+pub struct Solutions {
+    pub solutions: Vec<Solution>,
+    /// every way was tried, there are no solutions but these
+    pub complete: bool,
+}
+
+/// a board and bricks to clear it with
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub board: Board,
+    /// bricks in order, the first one is placed first
+    pub queue: Vec<Brick>,
+    pub hold: Option<Brick>,
+    /// hold is allowed
+    pub hold_enabled: bool,
+    /// hold is allowed for the first brick, not when it came out of hold
+    pub can_hold: bool,
+    /// rules of moving bricks, e.g. kicks and lock delay
+    pub mode: GameMode,
+    /// level of the gravity that limits tucks in modes without lock delay
+    pub level: u32,
+}
+
+impl Problem {
+    pub fn new(board: Board, queue: Vec<Brick>, hold: Option<Brick>, hold_enabled: bool) -> Self {
+        Self {
+            board,
+            queue,
+            hold,
+            hold_enabled,
+            can_hold: hold_enabled,
+            mode: GameMode::Puzzle,
+            level: 0,
+        }
+    }
+
+    /// the board of a game with its moving brick and up to `bricks` bricks after it
+    pub fn from_engine(engine: &Engine, bricks: usize) -> Self {
+        Self {
+            board: engine.board.clone(),
+            queue: std::iter::once(engine.moving_brick)
+                .chain(engine.preview(bricks))
+                .collect(),
+            hold: engine.hold_brick,
+            hold_enabled: engine.hold_enabled,
+            can_hold: engine.can_hold(),
+            mode: engine.mode,
+            level: engine.stats.level,
+        }
+    }
+
+    /// first solution found in `budget`
+    pub fn first(&self, budget: Duration) -> Option<Solution> {
+        self.solve(budget, 1).solutions.pop()
+    }
+
+    /// solutions found in `budget`, lowest perfect clears first
+    pub fn all(&self, budget: Duration) -> Solutions {
+        self.solve(budget, MAX_SOLUTIONS)
+    }
+
+    fn solve(&self, budget: Duration, limit: usize) -> Solutions {
+        let mut search = Search {
+            problem: self,
+            deadline: Instant::now() + budget,
+            limit,
+            late: false,
+            failed: HashSet::new(),
+            path: Vec::new(),
+            solutions: Vec::new(),
+        };
+        let height = self.board.height();
+        for rows in height.max(1)..=height.max(PC_ROWS) {
+            let filled = (0..rows)
+                .flat_map(|y| (0..self.board.width()).map(move |x| Dot(x, y)))
+                .filter(|dot| self.board.occupied_dot(dot))
+                .count();
+            let empty = rows as usize * self.board.width() as usize - filled;
+            if !empty.is_multiple_of(4)
+                || empty / 4 > self.queue.len() + self.hold.is_some() as usize
+            {
+                continue;
+            }
+            // bricks placed fail the same way on every board of the same height only
+            search.failed.clear();
+            search.dfs(&self.board, rows, self.hold, 0);
+            if search.late || search.solutions.len() >= limit {
+                break;
+            }
+        }
+        Solutions {
+            complete: !search.late && search.solutions.len() < limit,
+            solutions: search.solutions,
+        }
+    }
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// depth first search of one perfect clear height
+struct Search<'a> {
+    problem: &'a Problem,
+    deadline: Instant,
+    limit: usize,
+    /// out of time
+    late: bool,
+    /// hashes of board, hold and bricks used with no solution
+    failed: HashSet<u64>,
+    path: Vec<Step>,
+    solutions: Vec<Solution>,
+}
+
+impl Search<'_> {
+    /// true when a solution is found from here, or the search stopped
+    fn dfs(&mut self, board: &Board, rows: i8, hold: Option<Brick>, used: usize) -> bool {
+        if board.is_empty() && !self.path.is_empty() {
+            self.solutions.push(self.path.clone());
+            return true;
+        }
+        if Instant::now() >= self.deadline {
+            self.late = true;
+            return true;
+        }
+        let key = hash((board, hold.map(|brick| brick.ty), used));
+        if self.failed.contains(&key) || !self.can_fill(board, rows, hold, used) {
+            return false;
+        }
+        let problem = self.problem;
+        let Some(&brick) = problem.queue.get(used) else {
+            return false;
+        };
+        // brick to place, hold first, hold after it and bricks used after it
+        let mut choices = vec![(brick, false, hold, used + 1)];
+        let can_hold = if used == 0 {
+            problem.can_hold
+        } else {
+            problem.hold_enabled
+        };
+        if can_hold {
+            // out of hold comes the held brick, or the next one when hold is empty
+            match hold {
+                Some(held) if held.ty != brick.ty => {
+                    choices.push((held, true, Some(brick), used + 1))
+                }
+                None => {
+                    if let Some(&next) = problem.queue.get(used + 1) {
+                        choices.push((next, true, Some(brick), used + 2));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut found = false;
+        for (brick, hold_first, held, used) in choices {
+            for (brick, pos) in self.placements(board, brick, rows) {
+                let mut after = board.clone();
+                after.occupy_brick(&brick, &pos);
+                let lines = after.clean_lines() as i8;
+                self.path.push(Step {
+                    hold: hold_first,
+                    brick,
+                    pos,
+                });
+                found |= self.dfs(&after, rows - lines, held, used);
+                self.path.pop();
+                if self.late || self.solutions.len() >= self.limit {
+                    return true;
+                }
+            }
+        }
+        if !found {
+            self.failed.insert(key);
+        }
+        found
+    }
+
+    /// where `brick` can lock below `rows`. Most places are reached by dropping
+    /// straight down, the others by tucks and spins are looked up in [`reachable`]
+    fn placements(&self, board: &Board, brick: Brick, rows: i8) -> Vec<(Brick, Dot)> {
+        let mut tucks = None;
+        let mut found = Vec::new();
+        for rotation in 0..BRICK_VIEWS[brick.ty].len() {
+            let brick = Brick { rotation, ..brick };
+            let view = BrickView::from(brick);
+            let top = view.dots.iter().map(|dot| dot.1).max().unwrap_or(0);
+            // dots of views are 0 to 3 from the position
+            for x in -3..board.width() {
+                for y in -3..rows - top {
+                    let pos = Dot(x, y);
+                    if !board.valid_brick(&brick, &pos) || board.valid_brick(&brick, &pos.down()) {
+                        continue;
+                    }
+                    let dropped =
+                        (y + 1..=board.height()).all(|y| board.valid_brick(&brick, &Dot(x, y)));
+                    let reached = dropped
+                        || tucks
+                            .get_or_insert_with(|| {
+                                reachable(
+                                    board,
+                                    brick,
+                                    BRICK_START_DOT,
+                                    self.problem.mode,
+                                    self.problem.level,
+                                )
+                                .into_iter()
+                                .map(|placement| {
+                                    let (brick, pos) = (placement.brick, placement.pos);
+                                    (brick.rotation, pos.0, pos.1)
+                                })
+                                .collect::<HashSet<_>>()
+                            })
+                            .contains(&(rotation, x, y));
+                    if reached {
+                        found.push((brick, pos));
+                    }
+                }
+            }
+        }
+        // lowest first, boards are filled from the bottom and solutions are found sooner
+        found.sort_by_key(|(_, pos)| (pos.1, pos.0));
+        found
+    }
+
+    /// enough bricks are left for the empty dots below `rows`, and parts of the board
+    /// between filled columns, which line clears never join, can be filled by whole bricks
+    fn can_fill(&self, board: &Board, rows: i8, hold: Option<Brick>, used: usize) -> bool {
+        let left = self.problem.queue.len().saturating_sub(used) + hold.is_some() as usize;
+        let mut empty: usize = 0;
+        let mut part: usize = 0;
+        for x in 0..board.width() {
+            let column = (0..rows)
+                .filter(|y| !board.occupied_dot(&Dot(x, *y)))
+                .count();
+            if column == 0 && !part.is_multiple_of(4) {
+                return false;
+            }
+            part = if column == 0 { 0 } else { part + column };
+            empty += column;
+        }
+        part.is_multiple_of(4) && empty / 4 <= left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// flat I whose dots are in columns `x` to `x + 3` of line `y`
+    fn flat_i(x: i8, y: i8) -> Step {
+        let brick = Brick { ty: 1, rotation: 0 };
+        let view = BrickView::from(brick);
+        let left = view.dots.iter().map(|dot| dot.0).min().unwrap();
+        let bottom = view.dots.iter().map(|dot| dot.1).min().unwrap();
+        assert!(view.dots.iter().all(|dot| dot.1 == bottom), "I is flat");
+        Step {
+            hold: false,
+            brick,
+            pos: Dot(x - left, y - bottom),
+        }
+    }
+
+    #[test]
+    fn steps_after_clears_are_drawn_above_them() {
+        // lines 0 and 2 miss columns 0 to 3, line 1 misses columns 6 to 9
+        let mut board = Board::default();
+        for x in 0..10 {
+            for (y, empty) in [(0, 0..4), (1, 6..10), (2, 0..4)] {
+                if !empty.contains(&x) {
+                    board.occupy_dot(&Dot(x, y));
+                }
+            }
+        }
+        // line 0 is cleared first, then lines 1 and 2 are lines 0 and 1
+        let solution = vec![flat_i(0, 0), flat_i(6, 0), flat_i(0, 0)];
+        let lines: Vec<Vec<i8>> = board_dots(&board, &solution)
+            .iter()
+            .map(|dots| dots.iter().map(|dot| dot.1).collect())
+            .collect();
+        assert_eq!(lines, [[0; 4], [1; 4], [2; 4]]);
+        let mut played = board.clone();
+        for step in &solution {
+            played.occupy_brick(&step.brick, &step.pos);
+            played.clean_lines();
+        }
+        assert!(played.is_empty());
+    }
+
+    /// lines 0 to 3 filled but columns `empty`
+    fn well(empty: std::ops::Range<i8>) -> Board {
+        let mut board = Board::default();
+        for y in 0..4 {
+            for x in (0..10).filter(|x| !empty.contains(x)) {
+                board.occupy_dot(&Dot(x, y));
+            }
+        }
+        board
+    }
+
+    fn bricks(names: &str) -> Vec<Brick> {
+        names.chars().filter_map(Brick::from_name).collect()
+    }
+
+    #[test]
+    fn perfect_clear_of_four_lines() {
+        let board = well(3..7);
+        let problem = Problem::new(board.clone(), bricks("OIOI"), None, false);
+        let solution = problem
+            .first(Duration::from_secs(5))
+            .expect("a perfect clear");
+        assert_eq!(solution.len(), 4);
+        let mut played = board;
+        for step in &solution {
+            assert!(!step.hold);
+            // keys get there from spawn
+            let reached = reachable(&played, step.brick, BRICK_START_DOT, problem.mode, 0)
+                .iter()
+                .any(|placement| {
+                    placement.brick.rotation == step.brick.rotation
+                        && (placement.pos.0, placement.pos.1) == (step.pos.0, step.pos.1)
+                });
+            assert!(reached, "{:?}", step);
+            played.occupy_brick(&step.brick, &step.pos);
+            played.clean_lines();
+        }
+        assert!(played.is_empty());
+    }
+
+    #[test]
+    fn impossible_queue_is_complete_without_solutions() {
+        // S bricks don't fill a box with a flat bottom
+        let problem = Problem::new(well(3..7), bricks("SSSS"), None, true);
+        let solutions = problem.all(Duration::from_secs(5));
+        assert!(solutions.solutions.is_empty());
+        assert!(solutions.complete);
+        // too few bricks for the empty dots
+        let problem = Problem::new(well(3..7), bricks("III"), None, false);
+        let solutions = problem.all(Duration::from_secs(5));
+        assert!(solutions.solutions.is_empty());
+        assert!(solutions.complete);
+    }
+}
@@ -0,0 +1,452 @@
+use crate::bricks::{Board, Brick, Dot};
+use crate::consts::{BRICK_START_DOT, BRICK_VIEWS};
+use crate::engine::Engine;
+use crate::movegen::{reachable, Reachable};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// rows of a board in the protocol, from the bottom
+const TBP_ROWS: i8 = 40;
+/// filled dot of a board in the protocol, the board doesn't keep brick types
+const TBP_FILLED: char = 'G';
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// piece at its SRS center, x from the left and y from the bottom
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Location {
+    #[serde(rename = "type")]
+    piece: char,
+    orientation: Orientation,
+    x: i8,
+    y: i8,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Move {
+    location: Location,
+    spin: Spin,
+}
+
+/// messages to the bot
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Frontend {
+    Rules {},
+    Start {
+        hold: Option<char>,
+        /// moving brick first
+        queue: Vec<char>,
+        combo: u32,
+        back_to_back: bool,
+        /// rows from the bottom, `None` for empty dots
+        board: Vec<Vec<Option<char>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        played: Move,
+    },
+    NewPiece {
+        piece: char,
+    },
+    Quit,
+}
+
+/// messages from the bot
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FromBot {
+    Error {
+        reason: String,
+    },
+    Ready,
+    Info {
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        version: String,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+impl Location {
+    /// dots covered, turned from the SRS pieces pointing north
+    fn dots(&self) -> Option<[Dot; 4]> {
+        let north = match self.piece {
+            'I' => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            'O' => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            'T' => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            'L' => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            'J' => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            'S' => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            'Z' => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+            _ => return None,
+        };
+        Some(north.map(|(x, y)| {
+            let (x, y) = match self.orientation {
+                Orientation::North => (x, y),
+                Orientation::East => (y, -x),
+                Orientation::South => (-x, -y),
+                Orientation::West => (-y, x),
+            };
+            Dot(self.x + x, self.y + y)
+        }))
+    }
+
+    /// brick and position covering the same dots, rotations differ from SRS
+    fn placement(&self) -> Option<(Brick, Dot)> {
+        let brick = Brick::from_name(self.piece)?;
+        let order = |dot: &Dot| (dot.1, dot.0);
+        let mut dots = self.dots()?;
+        dots.sort_by_key(order);
+        BRICK_VIEWS[brick.ty]
+            .iter()
+            .enumerate()
+            .find_map(|(rotation, view)| {
+                let mut view = view.dots;
+                view.sort_by_key(order);
+                let pos = Dot(dots[0].0 - view[0].0, dots[0].1 - view[0].1);
+                view.iter()
+                    .zip(&dots)
+                    .all(|(v, d)| v.0 + pos.0 == d.0 && v.1 + pos.1 == d.1)
+                    .then_some((Brick { rotation, ..brick }, pos))
+            })
+    }
+}
+
+/// what the bot knows of the game after the moves it was told about
+struct Known {
+    board: Board,
+    hold: Option<usize>,
+    /// brick types, moving brick first
+    queue: Vec<usize>,
+}
+
+/// moving brick and next brick, as types
+fn queue(engine: &Engine) -> Vec<usize> {
+    let mut queue = vec![engine.moving_brick.ty];
+    queue.extend(engine.next_brick.map(|brick| brick.ty));
+    queue
+}
+
+/// answer of [`External::poll`]
+pub enum Suggestion {
+    Waiting,
+    /// where the brick goes, and whether to hold first
+    Placement(Reachable, bool),
+    /// no suggested move can be played, or the bot is gone
+    Failed,
+}
+
+/// external bot talking Tetris Bot Protocol, JSON lines on its stdin and stdout,
+/// https://github.com/tetris-bot-protocol/tbp-spec
+///
+/// The bot is told about each move played and each brick coming into the queue,
+/// and started again on the game as it is when anything else changed the board,
+/// e.g. garbage.
+pub struct External {
+    child: Child,
+    stdin: ChildStdin,
+    /// lines of its stdout, read by a thread
+    lines: Mutex<Receiver<String>>,
+    /// answered `rules` with `ready`
+    ready: bool,
+    /// failed or quit
+    dead: bool,
+    known: Option<Known>,
+    /// a suggestion is wanted for the moving brick, not yet asked for
+    wanted: bool,
+    /// asked for the moving brick, waiting for the suggestion
+    asked: bool,
+    /// suggestions asked for bricks already gone
+    stale: u32,
+}
+
+impl External {
+    /// run a bot by a command line, e.g. `python3 bots/dummy_bot.py`
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines: Mutex::new(receiver),
+            ready: false,
+            dead: false,
+            known: None,
+            wanted: false,
+            asked: false,
+            stale: 0,
+        })
+    }
+
+    fn send(&mut self, message: &Frontend) {
+        let line = serde_json::to_string(message).expect("messages are plain JSON");
+        if let Err(e) = writeln!(self.stdin, "{}", line).and_then(|_| self.stdin.flush()) {
+            warn!("bot: {}", e);
+            self.dead = true;
+        }
+    }
+
+    /// ask where the brick just spawned goes, answered by [`External::poll`]
+    pub fn suggest(&mut self) {
+        if self.asked {
+            self.asked = false;
+            self.stale += 1;
+        }
+        self.wanted = true;
+    }
+
+    /// read what the bot said, and ask it for a suggestion when it is ready
+    pub fn poll(&mut self, engine: &Engine) -> Suggestion {
+        let mut lines = Vec::new();
+        {
+            let receiver = self.lines.lock().unwrap();
+            loop {
+                match receiver.try_recv() {
+                    Ok(line) => lines.push(line),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.dead = true;
+                        break;
+                    }
+                }
+            }
+        }
+        let mut suggested = None;
+        for line in lines {
+            match serde_json::from_str(&line) {
+                Ok(FromBot::Info { name, version }) => {
+                    info!("bot {} {}", name, version);
+                    self.send(&Frontend::Rules {});
+                }
+                Ok(FromBot::Ready) => self.ready = true,
+                Ok(FromBot::Error { reason }) => {
+                    warn!("bot: {}", reason);
+                    self.dead = true;
+                }
+                Ok(FromBot::Suggestion { moves }) => {
+                    if self.stale > 0 {
+                        self.stale -= 1;
+                    } else if self.asked {
+                        self.asked = false;
+                        suggested = Some(moves);
+                    }
+                }
+                Err(e) => warn!("bot: {}: {}", e, line),
+            }
+        }
+        if self.dead {
+            return Suggestion::Failed;
+        }
+        if let Some(moves) = suggested {
+            return self.choose(engine, &moves);
+        }
+        if self.wanted && self.ready {
+            self.wanted = false;
+            self.asked = true;
+            self.sync(engine);
+            self.send(&Frontend::Suggest);
+        }
+        Suggestion::Waiting
+    }
+
+    /// tell the bot about bricks new in the queue, or start it again on the game
+    fn sync(&mut self, engine: &Engine) {
+        let queue = queue(engine);
+        let hold = engine.hold_brick.map(|brick| brick.ty);
+        let new_pieces = match &self.known {
+            Some(known)
+                if known.board == engine.board
+                    && known.hold == hold
+                    && queue.starts_with(&known.queue) =>
+            {
+                Some(queue[known.queue.len()..].to_vec())
+            }
+            _ => None,
+        };
+        if let Some(new_pieces) = new_pieces {
+            for ty in new_pieces {
+                self.send(&Frontend::NewPiece {
+                    piece: Brick { ty, rotation: 0 }.name(),
+                });
+            }
+        } else {
+            if self.known.is_some() {
+                self.send(&Frontend::Stop);
+            }
+            let board = &engine.board;
+            let name = |ty| Brick { ty, rotation: 0 }.name();
+            self.send(&Frontend::Start {
+                hold: hold.map(name),
+                queue: queue.iter().map(|ty| name(*ty)).collect(),
+                combo: engine.garbage.combo(),
+                back_to_back: engine.garbage.back_to_back(),
+                board: (0..TBP_ROWS)
+                    .map(|y| {
+                        (0..board.width())
+                            .map(|x| board.occupied_dot(&Dot(x, y)).then_some(TBP_FILLED))
+                            .collect()
+                    })
+                    .collect(),
+            });
+        }
+        self.known = Some(Known {
+            board: engine.board.clone(),
+            hold,
+            queue,
+        });
+    }
+
+    /// first suggested move that can be played here, told to the bot as played
+    fn choose(&mut self, engine: &Engine, moves: &[Move]) -> Suggestion {
+        // out of hold comes the held brick, or the next one when hold is empty
+        let held = engine.hold_brick.or(engine.next_brick);
+        for played in moves {
+            let Some((brick, pos)) = played.location.placement() else {
+                continue;
+            };
+            let (hold, from, start) = if brick.ty == engine.moving_brick.ty {
+                (false, engine.moving_brick, engine.moving_pos)
+            } else {
+                match held {
+                    Some(held) if engine.can_hold() && held.ty == brick.ty => {
+                        (true, held, BRICK_START_DOT)
+                    }
+                    _ => continue,
+                }
+            };
+            let target = reachable(&engine.board, from, start, engine.mode, engine.stats.level)
+                .into_iter()
+                .find(|r| {
+                    r.brick.rotation == brick.rotation && r.pos.0 == pos.0 && r.pos.1 == pos.1
+                });
+            if let Some(target) = target {
+                self.play(engine, played, &target, hold);
+                return Suggestion::Placement(target, hold);
+            }
+        }
+        warn!("bot: no suggested move can be played");
+        Suggestion::Failed
+    }
+
+    fn play(&mut self, engine: &Engine, played: &Move, target: &Reachable, hold: bool) {
+        self.send(&Frontend::Play { played: *played });
+        let mut board = engine.board.clone();
+        board.occupy_brick(&target.brick, &target.pos);
+        board.clean_lines();
+        let mut queue = queue(engine);
+        let mut held = engine.hold_brick.map(|brick| brick.ty);
+        let moving = queue.remove(0);
+        if hold {
+            if held.is_none() && !queue.is_empty() {
+                queue.remove(0);
+            }
+            held = Some(moving);
+        }
+        self.known = Some(Known {
+            board,
+            hold: held,
+            queue,
+        });
+    }
+}
+
+impl Drop for External {
+    fn drop(&mut self) {
+        if !self.dead {
+            self.send(&Frontend::Quit);
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickView;
+    use crate::consts::BRICK_NAMES;
+    use std::collections::HashSet;
+
+    const ORIENTATIONS: [Orientation; 4] = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+    ];
+
+    fn sorted(dots: impl Iterator<Item = Dot>) -> Vec<(i8, i8)> {
+        let mut dots: Vec<_> = dots.map(|dot| (dot.0, dot.1)).collect();
+        dots.sort();
+        dots
+    }
+
+    #[test]
+    fn locations_cover_the_same_dots() {
+        for piece in BRICK_NAMES {
+            let mut rotations = HashSet::new();
+            for orientation in ORIENTATIONS {
+                let location = Location {
+                    piece,
+                    orientation,
+                    x: 4,
+                    y: 10,
+                };
+                let (brick, pos) = location.placement().expect("placement of a location");
+                assert_eq!(brick.name(), piece);
+                let view = BrickView::from(brick);
+                let dots = view.dots.iter().map(|dot| dot.with_original_dot(&pos));
+                assert_eq!(
+                    sorted(dots),
+                    sorted(location.dots().unwrap().into_iter()),
+                    "{piece} {orientation:?}"
+                );
+                rotations.insert(brick.rotation);
+            }
+            // every view of the brick is some orientation
+            let brick = Brick::from_name(piece).unwrap();
+            assert_eq!(rotations.len(), BRICK_VIEWS[brick.ty].len(), "{piece}");
+        }
+    }
+}
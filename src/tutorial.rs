@@ -0,0 +1,494 @@
+use crate::bricks::{Board, Brick};
+use crate::engine::{Engine, Event, Input};
+use crate::modes::GameMode;
+use crate::puzzle::{parse_board, parse_bricks, parse_hold};
+use std::ffi::OsStr;
+use std::str::FromStr;
+
+/// what the player is asked to do in a step
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Action {
+    Move,
+    Rotate,
+    RotateBack,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    /// clean at least N lines with one brick
+    Clear(u32),
+    /// T-spin cleaning lines
+    TSpin,
+}
+
+impl Action {
+    /// the action is done by keys held in the frame and what the engine reported
+    pub fn done(&self, input: &Input, event: &Event) -> bool {
+        match (self, event) {
+            (Action::Move, Event::Moved) => input.left || input.right,
+            (Action::Rotate, Event::Moved) => input.rotate,
+            (Action::RotateBack, Event::Moved) => input.rotate_back,
+            (Action::SoftDrop, Event::Moved) => input.soft_drop,
+            (Action::HardDrop, Event::Locked { .. }) => input.hard_drop,
+            (Action::Hold, Event::Held) => true,
+            (Action::Clear(n), Event::Locked { lines, .. }) => lines >= n,
+            (Action::TSpin, Event::Locked { lines, tspin, .. }) => *tspin && *lines > 0,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = s.split_whitespace().collect();
+        match words[..] {
+            ["move"] => Ok(Action::Move),
+            ["rotate"] => Ok(Action::Rotate),
+            ["rotate", "back"] => Ok(Action::RotateBack),
+            ["soft", "drop"] => Ok(Action::SoftDrop),
+            ["hard", "drop"] => Ok(Action::HardDrop),
+            ["hold"] => Ok(Action::Hold),
+            ["clear", n] => n
+                .parse()
+                .map(Action::Clear)
+                .map_err(|_| format!("`clear` needs a number, found `{}`", n)),
+            ["tspin"] => Ok(Action::TSpin),
+            _ => Err(format!("unknown action `{}`", s)),
+        }
+    }
+}
+
+/// board and bricks a step starts with
+#[derive(Debug, Clone)]
+pub struct Setup {
+    pub board: Board,
+    pub bricks: Vec<Brick>,
+    pub hold: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub text: String,
+    pub expect: Action,
+    /// `None` to go on with the board of the step before
+    pub setup: Option<Setup>,
+}
+
+/// a lesson of the tutorial.
+///
+/// Lesson file is plain text with a header and steps, each starts with a `---` line:
+///
+/// ```text
+/// name: MOVING
+/// ---
+/// text: LEFT AND RIGHT
+/// text: MOVE THE BRICK
+/// expect: move
+/// bricks: TTT
+/// board:
+/// ##..######
+/// ```
+///
+/// `text` lines are shown beside the board, up to 3 short lines.
+/// `expect` is one of `move`, `rotate`, `rotate back`, `soft drop`, `hard drop`, `hold`,
+/// `clear N` or `tspin`.
+/// A step with `bricks` starts a new board as in puzzle files (`hold` and `board` optional),
+/// other steps go on with the board before. The first step needs `bricks`.
+#[derive(Debug, Clone)]
+pub struct Lesson {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+impl FromStr for Lesson {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sections = s.split("\n---");
+        let mut name = None;
+        for line in sections.next().unwrap_or_default().lines() {
+            if let Some(value) = line.strip_prefix("name:") {
+                name = Some(value.trim().to_string());
+            }
+        }
+        let steps = sections.map(str::parse).collect::<Result<Vec<Step>, _>>()?;
+        match steps.first() {
+            None => return Err("lesson has no step".to_string()),
+            Some(step) if step.setup.is_none() => {
+                return Err("first step needs `bricks`".to_string())
+            }
+            _ => {}
+        }
+        Ok(Self {
+            name: name.ok_or("missing `name`")?,
+            steps,
+        })
+    }
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut text = Vec::new();
+        let mut expect = None;
+        let mut bricks = None;
+        let mut hold = false;
+        let mut rows = Vec::new();
+        let mut in_board = false;
+
+        for line in s.lines().map(str::trim_end) {
+            if in_board {
+                if !line.is_empty() {
+                    rows.push(line);
+                }
+                continue;
+            }
+            // rest of the `---` line
+            if line.trim().is_empty() || line.starts_with('#') || line.starts_with('-') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("expect `key: value`, found `{}`", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "text" => text.push(value),
+                "expect" => expect = Some(value.parse()?),
+                "bricks" => bricks = Some(parse_bricks(value)?),
+                "hold" => hold = parse_hold(value)?,
+                "board" => in_board = true,
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        let setup = match bricks {
+            Some(bricks) => Some(Setup {
+                board: parse_board(&rows)?,
+                bricks,
+                hold,
+            }),
+            None if !rows.is_empty() => return Err("`board` needs `bricks`".to_string()),
+            None => None,
+        };
+        Ok(Self {
+            text: text.join("\n"),
+            expect: expect.ok_or("missing `expect`")?,
+            setup,
+        })
+    }
+}
+
+/// what to do with the game after an event
+pub enum Progress {
+    Playing,
+    /// play on a new board
+    NewBoard(Box<Engine>),
+    /// all lessons are done
+    Finished,
+}
+
+/// lessons played in order, step by step
+#[derive(Debug, Default)]
+pub struct Tutorial {
+    pub lessons: Vec<Lesson>,
+    /// files failed to load
+    pub errors: Vec<String>,
+    lesson: usize,
+    step: usize,
+    /// step with the board to go back to when a step fails
+    checkpoint: usize,
+    retried: bool,
+}
+
+impl Tutorial {
+    /// lessons from `.txt` files in `dir`, sorted by file name
+    pub fn load(dir: &str) -> Self {
+        let mut tutorial = Self::default();
+        let mut paths: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+            Err(e) => {
+                tutorial.errors.push(format!("{}: {}", dir, e));
+                return tutorial;
+            }
+        };
+        paths.sort();
+        for path in paths {
+            if path.extension() != Some(OsStr::new("txt")) {
+                continue;
+            }
+            let lesson = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| text.parse());
+            match lesson {
+                Ok(lesson) => tutorial.lessons.push(lesson),
+                Err(e) => tutorial.errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        tutorial
+    }
+
+    fn current(&self) -> Option<&Step> {
+        self.lessons.get(self.lesson)?.steps.get(self.step)
+    }
+
+    /// a game on the board of the last step with one, gravity paused
+    pub fn engine(&self) -> Option<Engine> {
+        let setup = self.lessons.get(self.lesson)?.steps[self.checkpoint]
+            .setup
+            .as_ref()?;
+        let mut engine = Engine::with_bricks(
+            GameMode::Tutorial,
+            setup.board.clone(),
+            &setup.bricks,
+            setup.hold,
+        );
+        engine.gravity_paused = true;
+        Some(engine)
+    }
+
+    /// lesson and what to do now
+    pub fn text(&self) -> String {
+        let (Some(lesson), Some(step)) = (self.lessons.get(self.lesson), self.current()) else {
+            return String::new();
+        };
+        let title = if self.retried {
+            "TRY AGAIN".to_string()
+        } else {
+            format!("{}/{} {}", self.lesson + 1, self.lessons.len(), lesson.name)
+        };
+        format!("{}\n{}", title, step.text)
+    }
+
+    /// go to next step when the expected action is done,
+    /// or back to the last board when it can't be done any more.
+    pub fn check(&mut self, input: &Input, event: &Event) -> Progress {
+        let Some(step) = self.current() else {
+            return Progress::Playing;
+        };
+        if step.expect.done(input, event) {
+            self.retried = false;
+            self.step += 1;
+            if self.step == self.lessons[self.lesson].steps.len() {
+                self.lesson += 1;
+                self.step = 0;
+            }
+            return match self.current() {
+                None => Progress::Finished,
+                Some(step) if step.setup.is_some() => {
+                    self.checkpoint = self.step;
+                    self.new_board()
+                }
+                Some(_) => Progress::Playing,
+            };
+        }
+        let failed = match event {
+            Event::Over => true,
+            Event::Locked { .. } => matches!(step.expect, Action::Clear(_) | Action::TSpin),
+            _ => false,
+        };
+        if !failed {
+            return Progress::Playing;
+        }
+        self.retried = true;
+        self.step = self.checkpoint;
+        self.new_board()
+    }
+
+    /// the board of the checkpoint, finished when there is none
+    fn new_board(&self) -> Progress {
+        match self.engine() {
+            Some(engine) => Progress::NewBoard(Box::new(engine)),
+            None => Progress::Finished,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LESSON: &str = "name: CLEARING
+---
+text: MOVE
+expect: move
+bricks: II
+hold: no
+board:
+.........#
+---
+text: CLEAR
+expect: clear 1
+---
+text: NEW BOARD
+expect: hard drop
+bricks: O
+";
+
+    fn locked(lines: u32, tspin: bool) -> Event {
+        Event::Locked {
+            brick: Brick::from_name('T').unwrap(),
+            lines,
+            tspin,
+            perfect_clear: false,
+            height: 1,
+            holes: 0,
+        }
+    }
+
+    fn tutorial(lessons: &[&str]) -> Tutorial {
+        Tutorial {
+            lessons: lessons.iter().map(|text| text.parse().unwrap()).collect(),
+            ..Tutorial::default()
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let lesson: Lesson = LESSON.parse().unwrap();
+        assert_eq!(lesson.name, "CLEARING");
+        let expects: Vec<_> = lesson.steps.iter().map(|step| step.expect).collect();
+        assert_eq!(expects, [Action::Move, Action::Clear(1), Action::HardDrop]);
+        let setups: Vec<_> = lesson
+            .steps
+            .iter()
+            .map(|step| step.setup.is_some())
+            .collect();
+        assert_eq!(setups, [true, false, true]);
+        assert_eq!(lesson.steps[0].setup.as_ref().unwrap().board.height(), 1);
+
+        for (from, to, error) in [
+            ("expect: move\n", "", "missing `expect`"),
+            ("expect: move", "expect: jump", "unknown action"),
+            ("expect: clear 1", "expect: clear", "unknown action"),
+            ("bricks: II\n", "", "`board` needs `bricks`"),
+            (
+                "bricks: II\nhold: no\nboard:\n.........#\n",
+                "",
+                "first step needs `bricks`",
+            ),
+            ("name: CLEARING", "title: CLEARING", "missing `name`"),
+            ("hold: no", "hold: nope", "`yes` or `no`"),
+        ] {
+            let text = LESSON.replacen(from, to, 1);
+            let error_found = text.parse::<Lesson>().unwrap_err();
+            assert!(error_found.contains(error), "{}: {}", error, error_found);
+        }
+        let step = "text: CLEAR\nexpect: clear 1\nboard:\n.........#\n";
+        assert_eq!(step.parse::<Step>().unwrap_err(), "`board` needs `bricks`");
+        assert!("name: EMPTY\n".parse::<Lesson>().is_err());
+    }
+
+    #[test]
+    fn advances() {
+        let mut tutorial = tutorial(&[LESSON, LESSON]);
+        let left = Input {
+            left: true,
+            ..Input::default()
+        };
+        // the wrong keys or events don't count
+        assert!(matches!(
+            tutorial.check(&Input::default(), &Event::Moved),
+            Progress::Playing
+        ));
+        assert!(matches!(
+            tutorial.check(&left, &Event::Held),
+            Progress::Playing
+        ));
+        assert_eq!(tutorial.step, 0);
+
+        assert!(matches!(
+            tutorial.check(&left, &Event::Moved),
+            Progress::Playing
+        ));
+        assert_eq!(tutorial.step, 1);
+        assert!(matches!(
+            tutorial.check(&left, &Event::Moved),
+            Progress::Playing
+        ));
+        assert!(matches!(
+            tutorial.check(&Input::default(), &locked(1, false)),
+            Progress::NewBoard(_)
+        ));
+        assert_eq!((tutorial.step, tutorial.checkpoint), (2, 2));
+        assert!(tutorial.text().ends_with("NEW BOARD"));
+
+        let drop = Input {
+            hard_drop: true,
+            ..Input::default()
+        };
+        let Progress::NewBoard(engine) = tutorial.check(&drop, &locked(0, false)) else {
+            panic!("next lesson starts on a new board");
+        };
+        assert!(engine.gravity_paused);
+        assert_eq!(
+            (tutorial.lesson, tutorial.step, tutorial.checkpoint),
+            (1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn back_to_checkpoint() {
+        let mut tutorial = tutorial(&[LESSON]);
+        tutorial.step = 1;
+        // a brick locks without clearing
+        assert!(matches!(
+            tutorial.check(&Input::default(), &locked(0, false)),
+            Progress::NewBoard(_)
+        ));
+        assert_eq!(tutorial.step, 0);
+        assert!(tutorial.text().starts_with("TRY AGAIN"));
+
+        let tspin = "name: SPIN\n---\ntext: SPIN\nexpect: tspin\nbricks: T\n";
+        let mut tutorial = self::tutorial(&[tspin]);
+        // lines without the spin, or the spin without lines
+        for event in [locked(2, false), locked(0, true)] {
+            assert!(matches!(
+                tutorial.check(&Input::default(), &event),
+                Progress::NewBoard(_)
+            ));
+            assert!(tutorial.retried);
+        }
+        // game over fails any step
+        assert!(matches!(
+            tutorial.check(&Input::default(), &Event::Over),
+            Progress::NewBoard(_)
+        ));
+        assert!(matches!(
+            tutorial.check(&Input::default(), &locked(1, true)),
+            Progress::Finished
+        ));
+        assert!(!tutorial.retried);
+    }
+
+    #[test]
+    fn finished() {
+        let mut tutorial = tutorial(&[LESSON]);
+        tutorial.step = 2;
+        tutorial.checkpoint = 2;
+        let drop = Input {
+            hard_drop: true,
+            ..Input::default()
+        };
+        assert!(matches!(
+            tutorial.check(&drop, &locked(0, false)),
+            Progress::Finished
+        ));
+        assert!(tutorial.engine().is_none());
+        assert!(tutorial.text().is_empty());
+        // nothing left to check
+        assert!(matches!(
+            tutorial.check(&drop, &Event::Over),
+            Progress::Playing
+        ));
+
+        // no lessons at all
+        let mut tutorial = Tutorial::default();
+        assert!(matches!(
+            tutorial.check(&drop, &Event::Over),
+            Progress::Playing
+        ));
+        assert!(matches!(tutorial.new_board(), Progress::Finished));
+    }
+}
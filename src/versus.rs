@@ -0,0 +1,211 @@
+use crate::bot::{Bot, DIFFICULTIES};
+use crate::consts::*;
+use crate::engine::{Engine, Event, Outcome};
+use crate::modes::GameMode;
+use crate::online::{self, Role};
+use crate::player::{spawn_player, Cpu, KeyMap, Lockstep, Owner, Player};
+use crate::{init_text, GameData, GameState};
+use bevy::prelude::*;
+
+const PLAYERS: usize = 2;
+
+/// player 1 on the left, player 2 on the right
+pub const VERSUS_KEYS: [KeyMap; PLAYERS] = [
+    KeyMap {
+        left: &[KeyCode::A],
+        right: &[KeyCode::D],
+        soft_drop: &[KeyCode::S],
+        hard_drop: &[KeyCode::Space],
+        rotate: &[KeyCode::W],
+        rotate_back: &[KeyCode::Q],
+        hold: &[KeyCode::LShift],
+    },
+    KeyMap {
+        left: &[KeyCode::Left],
+        right: &[KeyCode::Right],
+        soft_drop: &[KeyCode::Down],
+        hard_drop: &[KeyCode::Return],
+        rotate: &[KeyCode::Up],
+        rotate_back: &[KeyCode::Comma],
+        hold: &[KeyCode::Period],
+    },
+];
+
+/// who plays against player 1
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Opponent {
+    /// player 2 on the same keyboard
+    #[default]
+    Keyboard,
+    /// computer by index of [`DIFFICULTIES`]
+    Cpu(usize),
+    /// wait for a peer to connect over network
+    Host,
+    /// connect to a host over network
+    Join,
+    /// meet the peer in a room of the server
+    Server,
+    /// external bot given by `--bot`, on the right
+    External,
+}
+
+impl Opponent {
+    /// choices in menu, the external bot only when there is one
+    pub fn all(external: bool) -> Vec<Opponent> {
+        let mut all = vec![Opponent::Keyboard];
+        all.extend((0..DIFFICULTIES.len()).map(Opponent::Cpu));
+        if external {
+            all.push(Opponent::External);
+        }
+        all.extend([Opponent::Host, Opponent::Join, Opponent::Server]);
+        all
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Opponent::Keyboard => "2P".to_string(),
+            Opponent::Cpu(i) => format!("CPU {}", DIFFICULTIES[*i].name),
+            Opponent::Host => "HOST".to_string(),
+            Opponent::Join => "JOIN".to_string(),
+            Opponent::Server => "SERVER".to_string(),
+            Opponent::External => "BOT".to_string(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Versus {
+    /// `Some(None)` for a draw, or when the match ends otherwise
+    pub winner: Option<Option<usize>>,
+}
+
+pub fn versus_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game: Res<GameData>,
+    mut versus: ResMut<Versus>,
+) {
+    versus.winner = None;
+    let (role, address) = match game.opponent {
+        Opponent::Host => (Role::Host, &game.address),
+        Opponent::Join => (Role::Join, &game.address),
+        Opponent::Server => (
+            Role::Server {
+                room: game.room.clone(),
+            },
+            &game.server,
+        ),
+        _ => {
+            // same bricks for both players
+            let seed = rand::random();
+            for (slot, keys) in VERSUS_KEYS.iter().enumerate() {
+                // computer plays on the right
+                let cpu = match game.opponent {
+                    Opponent::Cpu(difficulty) if slot == PLAYERS - 1 => {
+                        Some(Bot::new(&DIFFICULTIES[difficulty], seed))
+                    }
+                    Opponent::External if slot == PLAYERS - 1 => Some(game.computer(seed)),
+                    _ => None,
+                };
+                let keys = if cpu.is_some() { None } else { Some(keys) };
+                let player = spawn_player(
+                    &mut commands,
+                    &asset_server,
+                    slot,
+                    versus_engine(seed),
+                    keys,
+                );
+                if let Some(bot) = cpu {
+                    commands.entity(player).insert(Cpu(bot));
+                }
+            }
+            return;
+        }
+    };
+    online::online_setup(&mut commands, &asset_server, role, address);
+}
+
+/// a board of versus, both boards of a match have the same seed
+pub fn versus_engine(seed: u64) -> Engine {
+    let mut engine = Engine::new(GameMode::Versus, 0, seed);
+    engine.hold_enabled = true;
+    engine
+}
+
+/// send garbage between players on this computer, online players send it frame by frame
+pub fn garbage_system(mut players: Query<&mut Player, Without<Lockstep>>) {
+    let mut sent = Vec::new();
+    for player in &players {
+        for event in &player.events {
+            if let Event::Attack(lines) = event {
+                sent.push(((player.slot + 1) % PLAYERS, *lines));
+            }
+        }
+    }
+    for (slot, lines) in sent {
+        for mut player in &mut players {
+            if player.slot == slot {
+                player.engine.garbage.receive(lines);
+            }
+        }
+    }
+}
+
+/// show garbage waiting, and end the match when someone tops out
+pub fn versus_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut versus: ResMut<Versus>,
+    mut players: Query<(Entity, &mut Player)>,
+) {
+    if versus.winner.is_some() || players.is_empty() {
+        return;
+    }
+    for (_, mut player) in &mut players {
+        let hold = player.engine.hold_brick.map_or('-', |brick| brick.name());
+        player.info = format!("HOLD {}\nGARBAGE {}", hold, player.engine.garbage.pending());
+    }
+
+    let alive: Vec<_> = players
+        .iter()
+        .filter(|(_, player)| !player.engine.is_over())
+        .map(|(_, player)| player.slot)
+        .collect();
+    if alive.len() == PLAYERS {
+        return;
+    }
+    let winner = (alive.len() == 1).then(|| alive[0]);
+    for (entity, mut player) in &mut players {
+        let outcome = if Some(player.slot) == winner {
+            Outcome::Completed
+        } else {
+            Outcome::TopOut
+        };
+        player.engine.end(outcome);
+        let msg = match winner {
+            Some(_) if outcome == Outcome::Completed => " YOU WIN",
+            Some(_) => " YOU LOSE",
+            None => " DRAW",
+        };
+        commands
+            .spawn(init_text(
+                &format!("{}\n\nPRESS SPACE", msg),
+                TEXT_GAME_X + player.slot as f32 * WINDOWS_WIDTH,
+                TEXT_GAME_Y,
+                &asset_server,
+            ))
+            .insert(Owner(entity));
+    }
+    versus.winner = Some(winner);
+}
+
+/// back to menu when the match is over
+pub fn versus_over_system(
+    mut state: ResMut<NextState<GameState>>,
+    versus: Res<Versus>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if versus.winner.is_some() && keyboard_input.just_pressed(KeyCode::Space) {
+        state.set(GameState::Menu);
+    }
+}